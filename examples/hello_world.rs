@@ -20,5 +20,7 @@ fn main() {
         .unwrap();
     manager.start_updates().unwrap();
 
-    loop {}
+    loop {
+        std::thread::park();
+    }
 }
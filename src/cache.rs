@@ -0,0 +1,439 @@
+//! The most recent location delivered to a manager, optionally saved to a file so
+//! that it is available as soon as the next manager is created.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Coordinates, LocationSnapshot, Result};
+
+/// How often the cached location is saved while updates are being delivered.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Which locations a manager remembers, see [`Manager::set_cache_config`].
+///
+/// [`Manager::set_cache_config`]: crate::Manager::set_cache_config
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheConfig {
+    /// How long a location is remembered for. Defaults to indefinitely if `None`.
+    pub max_age: Option<Duration>,
+    /// The largest horizontal accuracy, in meters, of a location worth remembering.
+    /// Locations with an unknown accuracy are always remembered.
+    pub max_accuracy: Option<f64>,
+    /// The file the location is saved to and restored from, e.g. in the user's
+    /// state directory.
+    pub path: Option<PathBuf>,
+}
+
+/// A location remembered by a manager, see [`Manager::last_known_location`].
+///
+/// [`Manager::last_known_location`]: crate::Manager::last_known_location
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedLocation {
+    pub location: LocationSnapshot,
+    /// When the location was delivered to the manager, which may have been in a
+    /// previous run of the application.
+    pub received: SystemTime,
+}
+
+impl CachedLocation {
+    /// The time since the location was delivered.
+    pub fn age(&self) -> Duration {
+        self.received.elapsed().unwrap_or_default()
+    }
+}
+
+pub(crate) struct Cache {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    config: CacheConfig,
+    last: Option<CachedLocation>,
+    /// When the location was last saved, if ever.
+    saved: Option<Instant>,
+}
+
+impl Cache {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                config: CacheConfig::default(),
+                last: None,
+                saved: None,
+            }),
+        }
+    }
+
+    /// Replaces the configuration, restoring the location saved at its path if
+    /// it is more recent than the one in memory.
+    pub(crate) fn configure(&self, config: CacheConfig) -> Result<()> {
+        let restored = match &config.path {
+            Some(path) => load(path)?,
+            None => None,
+        };
+
+        let Ok(mut inner) = self.inner.lock() else {
+            return Ok(());
+        };
+        inner.config = config;
+        if let Some(restored) = restored {
+            if inner
+                .last
+                .as_ref()
+                .is_none_or(|last| last.received < restored.received)
+            {
+                inner.last = Some(restored);
+            }
+        }
+        if inner
+            .last
+            .as_ref()
+            .is_some_and(|last| !inner.is_valid(last))
+        {
+            inner.last = None;
+        }
+        Ok(())
+    }
+
+    /// Returns the remembered location, if it is still valid.
+    pub(crate) fn get(&self) -> Option<CachedLocation> {
+        let inner = self.inner.lock().ok()?;
+        inner.last.clone().filter(|last| inner.is_valid(last))
+    }
+
+    /// Remembers `location` if it is accurate enough, saving it if it hasn't been
+    /// saved recently.
+    pub(crate) fn insert(&self, location: &LocationSnapshot) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let cached = CachedLocation {
+            // The last known location doesn't answer any request.
+            location: LocationSnapshot {
+                request: None,
+                ..location.clone()
+            },
+            received: SystemTime::now(),
+        };
+        if !inner.is_valid(&cached) {
+            return;
+        }
+        inner.last = Some(cached);
+
+        if inner
+            .saved
+            .is_none_or(|saved| saved.elapsed() >= SAVE_INTERVAL)
+        {
+            inner.save();
+        }
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        if let Ok(inner) = self.inner.get_mut() {
+            inner.save();
+        }
+    }
+}
+
+impl Inner {
+    fn is_valid(&self, cached: &CachedLocation) -> bool {
+        let fresh = self.config.max_age.is_none_or(|age| cached.age() <= age);
+        let accurate = match (
+            self.config.max_accuracy,
+            cached.location.horizontal_accuracy,
+        ) {
+            (Some(max), Some(accuracy)) => accuracy <= max,
+            _ => true,
+        };
+        fresh && accurate
+    }
+
+    fn save(&mut self) {
+        let (Some(path), Some(last)) = (&self.config.path, &self.last) else {
+            return;
+        };
+        // The cache is best-effort, so failing to save it isn't worth reporting.
+        if store(path, last).is_ok() {
+            self.saved = Some(Instant::now());
+        }
+    }
+}
+
+/// Reads a location saved by [`store`], treating a missing or malformed file as
+/// empty.
+fn load(path: &Path) -> Result<Option<CachedLocation>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let fields: HashMap<&str, &str> = text
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+    let float = |key| fields.get(key).and_then(|value| value.parse::<f64>().ok());
+    let time = |key| fields.get(key).and_then(|value| parse_time(value));
+
+    let (Some(received), Some(latitude), Some(longitude)) =
+        (time("received"), float("latitude"), float("longitude"))
+    else {
+        return Ok(None);
+    };
+    let location = LocationSnapshot {
+        coordinates: Coordinates {
+            latitude,
+            longitude,
+        },
+        altitude: float("altitude"),
+        bearing: float("bearing"),
+        speed: float("speed"),
+        time: time("time"),
+        horizontal_accuracy: float("horizontal_accuracy"),
+        vertical_accuracy: float("vertical_accuracy"),
+        speed_accuracy: float("speed_accuracy"),
+        bearing_accuracy: float("bearing_accuracy"),
+        request: None,
+    };
+    Ok(Some(CachedLocation { location, received }))
+}
+
+/// Saves a location as `key value` lines, replacing the file atomically.
+fn store(path: &Path, cached: &CachedLocation) -> io::Result<()> {
+    let location = &cached.location;
+    let mut text = String::new();
+    let times = [("received", Some(cached.received)), ("time", location.time)];
+    for (key, time) in times {
+        if let Some(value) = time.and_then(format_time) {
+            let _ = writeln!(text, "{key} {value}");
+        }
+    }
+    let floats = [
+        ("latitude", Some(location.coordinates.latitude)),
+        ("longitude", Some(location.coordinates.longitude)),
+        ("altitude", location.altitude),
+        ("bearing", location.bearing),
+        ("speed", location.speed),
+        ("horizontal_accuracy", location.horizontal_accuracy),
+        ("vertical_accuracy", location.vertical_accuracy),
+        ("speed_accuracy", location.speed_accuracy),
+        ("bearing_accuracy", location.bearing_accuracy),
+    ];
+    for (key, value) in floats {
+        if let Some(value) = value {
+            let _ = writeln!(text, "{key} {value}");
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)
+}
+
+/// Formats a time as nanoseconds since the Unix epoch.
+fn format_time(time: SystemTime) -> Option<u128> {
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+fn parse_time(value: &str) -> Option<SystemTime> {
+    let nanos = value.parse::<u64>().ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_nanos(nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocationBuilder;
+
+    /// A path in the temporary directory, unique to this process and test.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "robius-location-{}-cache-{name}",
+            std::process::id()
+        ))
+    }
+
+    fn fix(latitude: f64, accuracy: Option<f64>) -> LocationSnapshot {
+        let mut location = LocationBuilder::new(Coordinates {
+            latitude,
+            longitude: 2.2945,
+        });
+        if let Some(accuracy) = accuracy {
+            location = location.horizontal_accuracy(accuracy);
+        }
+        location.build().snapshot().unwrap()
+    }
+
+    fn cache(config: CacheConfig) -> Cache {
+        let cache = Cache::new();
+        cache.configure(config).unwrap();
+        cache
+    }
+
+    fn latitude(cache: &Cache) -> Option<f64> {
+        cache
+            .get()
+            .map(|cached| cached.location.coordinates.latitude)
+    }
+
+    #[test]
+    fn persistence() {
+        let path = temp_path("persistence");
+        let config = CacheConfig {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let location = LocationBuilder::new(Coordinates {
+            latitude: 48.858222,
+            longitude: -2.294_5e-3,
+        })
+        .altitude(35.25)
+        .bearing(359.9)
+        .speed(0.0)
+        .time(UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789))
+        .horizontal_accuracy(4.5)
+        .vertical_accuracy(1e-7)
+        .speed_accuracy(0.1)
+        .bearing_accuracy(15.0)
+        .build()
+        .snapshot()
+        .unwrap();
+        let first = cache(config.clone());
+        first.insert(&location);
+        let saved = first.get().unwrap();
+        drop(first);
+        assert_eq!(cache(config.clone()).get(), Some(saved));
+
+        // The first location is saved straight away, later ones only every so often
+        // and when the cache is dropped.
+        let second = cache(config.clone());
+        second.insert(&fix(1.0, None));
+        second.insert(&fix(2.0, None));
+        assert_eq!(latitude(&cache(config.clone())), Some(1.0));
+        drop(second);
+        assert_eq!(latitude(&cache(config.clone())), Some(2.0));
+
+        // A location more recent than the saved one isn't replaced by it.
+        let third = Cache::new();
+        third.insert(&fix(3.0, None));
+        third.configure(config.clone()).unwrap();
+        assert_eq!(latitude(&third), Some(3.0));
+        drop(third);
+
+        // Malformed and missing files are treated as empty.
+        fs::write(&path, "received 12\nlatitude north\n").unwrap();
+        assert_eq!(cache(config.clone()).get(), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(cache(config).get(), None);
+    }
+
+    #[test]
+    fn max_accuracy() {
+        let cache = cache(CacheConfig {
+            max_accuracy: Some(20.0),
+            ..Default::default()
+        });
+        cache.insert(&fix(1.0, Some(50.0)));
+        assert_eq!(latitude(&cache), None);
+        cache.insert(&fix(2.0, Some(20.0)));
+        assert_eq!(latitude(&cache), Some(2.0));
+        // An inaccurate location doesn't replace an accurate one.
+        cache.insert(&fix(3.0, Some(20.1)));
+        assert_eq!(latitude(&cache), Some(2.0));
+        // Locations of unknown accuracy are remembered.
+        cache.insert(&fix(4.0, None));
+        assert_eq!(latitude(&cache), Some(4.0));
+
+        // Tightening the configuration forgets locations that no longer qualify.
+        cache.insert(&fix(5.0, Some(15.0)));
+        cache
+            .configure(CacheConfig {
+                max_accuracy: Some(10.0),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(latitude(&cache), None);
+    }
+
+    #[test]
+    fn max_age() {
+        let cache = cache(CacheConfig {
+            max_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+        cache.insert(&fix(1.0, None));
+        assert!(cache.get().unwrap().age() < Duration::from_secs(60));
+
+        {
+            let mut inner = cache.inner.lock().unwrap();
+            inner.last.as_mut().unwrap().received -= Duration::from_secs(61);
+        }
+        assert_eq!(latitude(&cache), None);
+        // Expired locations are kept until the configuration allows them again.
+        cache.configure(CacheConfig::default()).unwrap();
+        assert_eq!(latitude(&cache), Some(1.0));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn flagged_locations_are_not_cached() {
+        use crate::{
+            event::FnHandler,
+            mock::Mock,
+            validation::{Rejection, ValidationConfig},
+            LocationEvent, Manager,
+        };
+
+        let mock = Mock::new();
+        let (send, events) = std::sync::mpsc::channel();
+        let manager = Manager::with_backend(
+            FnHandler(move |event: LocationEvent| {
+                let _ = send.send(event);
+            }),
+            mock.backend(),
+        )
+        .unwrap();
+        manager
+            .set_validation_config(Some(ValidationConfig {
+                flag_only: true,
+                ..Default::default()
+            }))
+            .unwrap();
+
+        let request = manager.update_once().unwrap();
+        mock.push(fix(48.0, Some(10.0)).into());
+        let LocationEvent::Location(location) = events.try_recv().unwrap() else {
+            panic!("expected a location");
+        };
+        assert_eq!(location.request, Some(request.id()));
+        // The cached location doesn't answer the request.
+        let cached = manager.last_known_location().unwrap();
+        assert_eq!(cached.location.coordinates.latitude, 48.0);
+        assert_eq!(cached.location.request, None);
+
+        // A jump of 5000 km in no time.
+        mock.push(fix(0.0, Some(10.0)).into());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            LocationEvent::Rejected(_, Rejection::Teleport { .. })
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            LocationEvent::Location(_)
+        ));
+        let cached = manager.last_known_location().unwrap();
+        assert_eq!(cached.location.coordinates.latitude, 48.0);
+    }
+}
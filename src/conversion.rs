@@ -0,0 +1,564 @@
+//! Conversions between [`Coordinates`] and other coordinate systems: UTM and MGRS
+//! grid references, Earth-centered Earth-fixed (ECEF) positions, and East-North-Up
+//! (ENU) offsets in a local frame.
+//!
+//! All conversions use the WGS-84 ellipsoid. Altitudes are heights above the
+//! ellipsoid in meters. [`Location::altitude`](crate::Location::altitude) is
+//! usually a height above mean sea level instead, as reported by gpsd and NMEA
+//! receivers, which differs from the ellipsoid by up to about 100 m. ECEF positions
+//! and ENU heights computed from it are off by as much, although horizontal ENU
+//! offsets are barely affected.
+//!
+//! ```
+//! # use robius_location::{conversion::*, Coordinates};
+//! let coordinates = Coordinates { latitude: 59.9139, longitude: 10.7522 };
+//!
+//! // Oslo lies in zone 32 because of the exception for southern Norway.
+//! let utm = coordinates.to_utm().unwrap();
+//! assert_eq!((utm.zone, utm.hemisphere), (32, Hemisphere::North));
+//!
+//! assert_eq!(coordinates.to_mgrs(3).unwrap(), "32VNM979431");
+//! ```
+
+// `is_multiple_of` is only stable since Rust 1.87.
+#![allow(clippy::manual_is_multiple_of)]
+
+use std::fmt;
+
+use crate::{
+    geodesy::{WGS84_A, WGS84_F},
+    Coordinates, Error, ErrorKind, Result,
+};
+
+/// The scale factor on the central meridian of a UTM zone.
+const UTM_SCALE: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+/// The northing added in the southern hemisphere, so that northings are positive.
+const FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// The latitude bands of UTM and MGRS, 8° high starting at 80°S, except for `X`
+/// which is 12° high.
+const BANDS: &[u8; 20] = b"CDEFGHJKLMNPQRSTUVWX";
+/// The column letters of MGRS 100 km squares, which cycle every three zones.
+const COLUMNS: [&[u8; 8]; 3] = [b"STUVWXYZ", b"ABCDEFGH", b"JKLMNPQR"];
+/// The row letters of MGRS 100 km squares.
+const ROWS: &[u8; 20] = b"ABCDEFGHJKLMNPQRSTUV";
+/// The highest number of digits per axis of an MGRS reference, giving millimeter
+/// precision.
+const MAX_MGRS_PRECISION: usize = 8;
+
+/// The third flattening of the ellipsoid.
+const N: f64 = WGS84_F / (2.0 - WGS84_F);
+/// The first eccentricity of the ellipsoid, squared.
+const E2: f64 = WGS84_F * (2.0 - WGS84_F);
+
+/// The hemisphere a UTM coordinate is in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Hemisphere {
+    North,
+    South,
+}
+
+/// A position on the Universal Transverse Mercator grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Utm {
+    /// The longitude zone, from 1 to 60.
+    pub zone: u8,
+    pub hemisphere: Hemisphere,
+    /// The distance east of the zone's false origin, in meters.
+    pub easting: f64,
+    /// The distance north of the equator in meters, plus 10 000 km in the southern
+    /// hemisphere so that it is never negative.
+    pub northing: f64,
+}
+
+impl fmt::Display for Utm {
+    /// Formats the position as e.g. `32N 597979.90 6643118.99`, with the precision
+    /// of the formatter applied to the easting and northing.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hemisphere = match self.hemisphere {
+            Hemisphere::North => 'N',
+            Hemisphere::South => 'S',
+        };
+        let precision = f.precision().unwrap_or(2);
+        write!(
+            f,
+            "{}{hemisphere} {:.precision$} {:.precision$}",
+            self.zone, self.easting, self.northing
+        )
+    }
+}
+
+impl Utm {
+    /// Converts the position back to latitude and longitude.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] if the zone isn't between 1 and 60.
+    pub fn to_coordinates(&self) -> Result<Coordinates> {
+        if !(1..=60).contains(&self.zone) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("invalid UTM zone {}", self.zone)));
+        }
+        let northing = match self.hemisphere {
+            Hemisphere::North => self.northing,
+            Hemisphere::South => self.northing - FALSE_NORTHING,
+        };
+        let scale = UTM_SCALE * rectifying_radius();
+        let xi = northing / scale;
+        let eta = (self.easting - FALSE_EASTING) / scale;
+
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in BETA.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+        let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let mut latitude = chi;
+        for (j, delta) in DELTA.iter().enumerate() {
+            latitude += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+        let longitude =
+            central_meridian(self.zone) + eta_prime.sinh().atan2(xi_prime.cos()).to_degrees();
+
+        Ok(Coordinates {
+            latitude: latitude.to_degrees(),
+            longitude: normalize_longitude(longitude),
+        })
+    }
+}
+
+// The coefficients of Krüger's series, to the third order in `N`, which is accurate
+// to about 0.1 mm within a zone.
+const ALPHA: [f64; 3] = [
+    N / 2.0 - 2.0 / 3.0 * N * N + 5.0 / 16.0 * N * N * N,
+    13.0 / 48.0 * N * N - 3.0 / 5.0 * N * N * N,
+    61.0 / 240.0 * N * N * N,
+];
+const BETA: [f64; 3] = [
+    N / 2.0 - 2.0 / 3.0 * N * N + 37.0 / 96.0 * N * N * N,
+    1.0 / 48.0 * N * N + 1.0 / 15.0 * N * N * N,
+    17.0 / 480.0 * N * N * N,
+];
+const DELTA: [f64; 3] = [
+    2.0 * N - 2.0 / 3.0 * N * N - 2.0 * N * N * N,
+    7.0 / 3.0 * N * N - 8.0 / 5.0 * N * N * N,
+    56.0 / 15.0 * N * N * N,
+];
+
+/// The radius of the circle with the same circumference as a meridian.
+fn rectifying_radius() -> f64 {
+    WGS84_A / (1.0 + N) * (1.0 + N * N / 4.0 + N.powi(4) / 64.0)
+}
+
+fn central_meridian(zone: u8) -> f64 {
+    f64::from(zone) * 6.0 - 183.0
+}
+
+fn normalize_longitude(longitude: f64) -> f64 {
+    if (-180.0..=180.0).contains(&longitude) {
+        longitude
+    } else {
+        (longitude + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+/// The index of the latitude band containing `latitude`, which must be within the
+/// UTM grid.
+fn band(latitude: f64) -> usize {
+    (((latitude + 80.0) / 8.0).floor() as usize).min(BANDS.len() - 1)
+}
+
+/// The number of rows by which the lettering of MGRS squares is shifted in `zone`.
+fn row_offset(zone: u8) -> usize {
+    if zone % 2 == 0 {
+        5
+    } else {
+        0
+    }
+}
+
+/// A position relative to the center of the Earth, in meters.
+///
+/// The x axis points towards 0° latitude and longitude, the y axis towards 0°
+/// latitude and 90°E, and the z axis towards the north pole.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ecef {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Ecef {
+    /// Converts the position back to latitude, longitude and altitude.
+    pub fn to_coordinates(&self) -> (Coordinates, f64) {
+        let p = self.x.hypot(self.y);
+        let longitude = self.y.atan2(self.x);
+        // Iterate `tan(latitude) = (z + e² N sin(latitude)) / p`, which converges
+        // to well under a millimeter in a few steps, also near the poles.
+        let mut latitude = self.z.atan2(p * (1.0 - E2));
+        for _ in 0..10 {
+            let radius = prime_vertical_radius(latitude);
+            let next = (self.z + E2 * radius * latitude.sin()).atan2(p);
+            let converged = (next - latitude).abs() < 1e-15;
+            latitude = next;
+            if converged {
+                break;
+            }
+        }
+        let (sin, cos) = latitude.sin_cos();
+        let altitude = p * cos + self.z * sin - WGS84_A * (1.0 - E2 * sin * sin).sqrt();
+
+        (
+            Coordinates {
+                latitude: latitude.to_degrees(),
+                longitude: longitude.to_degrees(),
+            },
+            altitude,
+        )
+    }
+}
+
+/// The radius of curvature perpendicular to the meridian.
+fn prime_vertical_radius(latitude: f64) -> f64 {
+    WGS84_A / (1.0 - E2 * latitude.sin().powi(2)).sqrt()
+}
+
+/// An offset from the origin of a [`LocalFrame`], in meters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Enu {
+    pub east: f64,
+    pub north: f64,
+    pub up: f64,
+}
+
+/// A local East-North-Up frame, whose axes are tangent to the ellipsoid at its
+/// origin.
+///
+/// ```
+/// # use robius_location::{conversion::LocalFrame, Coordinates};
+/// let origin = Coordinates { latitude: 48.8584, longitude: 2.2945 };
+/// let frame = LocalFrame::new(origin, 35.0);
+///
+/// let north = frame.to_enu(Coordinates { latitude: 48.8594, longitude: 2.2945 }, 35.0);
+/// assert!((north.north - 111.2).abs() < 0.1);
+/// assert!(north.east.abs() < 1e-6);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LocalFrame {
+    origin: Ecef,
+    /// The sine and cosine of the origin's latitude and longitude.
+    latitude: (f64, f64),
+    longitude: (f64, f64),
+}
+
+impl LocalFrame {
+    /// Creates a frame centered on `origin` at `altitude`.
+    pub fn new(origin: Coordinates, altitude: f64) -> Self {
+        Self {
+            origin: origin.to_ecef(altitude),
+            latitude: origin.latitude.to_radians().sin_cos(),
+            longitude: origin.longitude.to_radians().sin_cos(),
+        }
+    }
+
+    /// The offset of `coordinates` at `altitude` from the origin.
+    pub fn to_enu(&self, coordinates: Coordinates, altitude: f64) -> Enu {
+        let ecef = coordinates.to_ecef(altitude);
+        let (dx, dy, dz) = (
+            ecef.x - self.origin.x,
+            ecef.y - self.origin.y,
+            ecef.z - self.origin.z,
+        );
+        let (sin_lat, cos_lat) = self.latitude;
+        let (sin_lon, cos_lon) = self.longitude;
+        Enu {
+            east: -sin_lon * dx + cos_lon * dy,
+            north: -sin_lat * cos_lon * dx - sin_lat * sin_lon * dy + cos_lat * dz,
+            up: cos_lat * cos_lon * dx + cos_lat * sin_lon * dy + sin_lat * dz,
+        }
+    }
+
+    /// The latitude, longitude and altitude at `offset` from the origin.
+    pub fn to_coordinates(&self, offset: Enu) -> (Coordinates, f64) {
+        let (sin_lat, cos_lat) = self.latitude;
+        let (sin_lon, cos_lon) = self.longitude;
+        let Enu { east, north, up } = offset;
+        Ecef {
+            x: self.origin.x - sin_lon * east - sin_lat * cos_lon * north + cos_lat * cos_lon * up,
+            y: self.origin.y + cos_lon * east - sin_lat * sin_lon * north + cos_lat * sin_lon * up,
+            z: self.origin.z + cos_lat * north + sin_lat * up,
+        }
+        .to_coordinates()
+    }
+}
+
+impl Coordinates {
+    /// Converts the coordinates to UTM, in the standard zone for their position.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] outside of the UTM grid, which covers
+    /// latitudes from 80°S to 84°N.
+    pub fn to_utm(self) -> Result<Utm> {
+        if !(-80.0..=84.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message("coordinates are outside of the UTM grid"));
+        }
+        Ok(project(self, utm_zone(self)))
+    }
+
+    /// Formats the coordinates as an MGRS grid reference with `precision` digits
+    /// per axis, e.g. `32VNM9797943118` for a precision of 5, which is 1 m.
+    ///
+    /// The reference names the south-west corner of the square containing the
+    /// coordinates. Precisions of up to 8 digits, or 1 mm, are supported, and a
+    /// precision of 0 names the 100 km square, e.g. `32VNM`.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] outside of the UTM grid, as the polar
+    /// regions aren't supported, or if the precision is too high.
+    pub fn to_mgrs(self, precision: usize) -> Result<String> {
+        if precision > MAX_MGRS_PRECISION {
+            return Err(Error::new(ErrorKind::OutOfRange).with_message(format!(
+                "MGRS precision {precision} is above {MAX_MGRS_PRECISION}"
+            )));
+        }
+        let utm = self.to_utm()?;
+        let column = (utm.easting / 100_000.0).floor() as usize;
+        let row = (utm.northing / 100_000.0).floor() as usize;
+
+        let mut reference = format!(
+            "{}{}{}{}",
+            utm.zone,
+            BANDS[band(self.latitude)] as char,
+            COLUMNS[usize::from(utm.zone) % 3][(column.max(1) - 1).min(7)] as char,
+            ROWS[(row + row_offset(utm.zone)) % ROWS.len()] as char,
+        );
+        if precision > 0 {
+            let scale = 10f64.powi(precision as i32 - 5);
+            let digits = |meters: f64| (meters.rem_euclid(100_000.0) * scale).floor() as u64;
+            reference += &format!(
+                "{:0precision$}{:0precision$}",
+                digits(utm.easting),
+                digits(utm.northing),
+            );
+        }
+        Ok(reference)
+    }
+
+    /// Parses an MGRS grid reference, returning the south-west corner of the square
+    /// it names.
+    ///
+    /// Spaces are ignored and letters may be lowercase, so `32V NM 97979 43118` is
+    /// accepted. Fails with [`ErrorKind::Parse`] if the reference is malformed.
+    pub fn from_mgrs(reference: &str) -> Result<Coordinates> {
+        let error = || {
+            Error::new(ErrorKind::Parse)
+                .with_message(format!("invalid MGRS reference {reference:?}"))
+        };
+        let reference: Vec<u8> = reference
+            .bytes()
+            .filter(|c| !c.is_ascii_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+
+        let zone_len = reference.iter().take_while(|c| c.is_ascii_digit()).count();
+        if !(1..=2).contains(&zone_len) || reference.len() < zone_len + 3 {
+            return Err(error());
+        }
+        let zone: u8 = std::str::from_utf8(&reference[..zone_len])
+            .ok()
+            .and_then(|zone| zone.parse().ok())
+            .filter(|zone| (1..=60).contains(zone))
+            .ok_or_else(error)?;
+        let band = BANDS
+            .iter()
+            .position(|&c| c == reference[zone_len])
+            .ok_or_else(error)?;
+        let column = COLUMNS[usize::from(zone) % 3]
+            .iter()
+            .position(|&c| c == reference[zone_len + 1])
+            .ok_or_else(error)?;
+        let row = ROWS
+            .iter()
+            .position(|&c| c == reference[zone_len + 2])
+            .ok_or_else(error)?;
+
+        let digits = &reference[zone_len + 3..];
+        if digits.len() % 2 != 0
+            || digits.len() > 2 * MAX_MGRS_PRECISION
+            || !digits.iter().all(u8::is_ascii_digit)
+        {
+            return Err(error());
+        }
+        let precision = digits.len() / 2;
+        let meters = |digits: &[u8]| {
+            let value = digits
+                .iter()
+                .fold(0u64, |value, digit| value * 10 + u64::from(digit - b'0'));
+            value as f64 * 10f64.powi(5 - precision as i32)
+        };
+        let easting = (column + 1) as f64 * 100_000.0 + meters(&digits[..precision]);
+
+        let row = (row + ROWS.len() - row_offset(zone)) % ROWS.len();
+        let mut northing = row as f64 * 100_000.0 + meters(&digits[precision..]);
+        // The rows repeat every 2000 km, so the band decides which repetition is
+        // meant. The lowest northing in the band is on its southern edge, at the
+        // central meridian in the north and at the edge of the zone in the south.
+        let south = band as f64 * 8.0 - 80.0;
+        let hemisphere = if south >= 0.0 {
+            Hemisphere::North
+        } else {
+            Hemisphere::South
+        };
+        let edge = Coordinates {
+            latitude: south,
+            longitude: central_meridian(zone)
+                + if hemisphere == Hemisphere::North {
+                    0.0
+                } else {
+                    3.0
+                },
+        };
+        let lowest = project(edge, zone).northing - 100_000.0;
+        while northing < lowest {
+            northing += 2_000_000.0;
+        }
+
+        Utm {
+            zone,
+            hemisphere,
+            easting,
+            northing,
+        }
+        .to_coordinates()
+    }
+
+    /// Converts the coordinates at `altitude` to an ECEF position.
+    pub fn to_ecef(self, altitude: f64) -> Ecef {
+        let latitude = self.latitude.to_radians();
+        let (sin_lon, cos_lon) = self.longitude.to_radians().sin_cos();
+        let radius = prime_vertical_radius(latitude);
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        Ecef {
+            x: (radius + altitude) * cos_lat * cos_lon,
+            y: (radius + altitude) * cos_lat * sin_lon,
+            z: (radius * (1.0 - E2) + altitude) * sin_lat,
+        }
+    }
+}
+
+/// The UTM zone of `coordinates`, including the exceptions for southern Norway and
+/// Svalbard.
+fn utm_zone(coordinates: Coordinates) -> u8 {
+    let Coordinates {
+        latitude,
+        longitude,
+    } = coordinates;
+    if (56.0..64.0).contains(&latitude) && (3.0..12.0).contains(&longitude) {
+        return 32;
+    }
+    if (72.0..=84.0).contains(&latitude) && (0.0..42.0).contains(&longitude) {
+        return match longitude {
+            longitude if longitude < 9.0 => 31,
+            longitude if longitude < 21.0 => 33,
+            longitude if longitude < 33.0 => 35,
+            _ => 37,
+        };
+    }
+    (((longitude + 180.0) / 6.0).floor() as u8 % 60) + 1
+}
+
+/// Projects `coordinates` onto the UTM grid of `zone`.
+fn project(coordinates: Coordinates, zone: u8) -> Utm {
+    let latitude = coordinates.latitude.to_radians();
+    let longitude =
+        normalize_longitude(coordinates.longitude - central_meridian(zone)).to_radians();
+    let e = E2.sqrt();
+    let t = (latitude.sin().atanh() - e * (e * latitude.sin()).atanh()).sinh();
+    let xi_prime = t.atan2(longitude.cos());
+    let eta_prime = (longitude.sin() / (1.0 + t * t).sqrt()).atanh();
+
+    let (mut xi, mut eta) = (xi_prime, eta_prime);
+    for (j, alpha) in ALPHA.iter().enumerate() {
+        let k = 2.0 * (j + 1) as f64;
+        xi += alpha * (k * xi_prime).sin() * (k * eta_prime).cosh();
+        eta += alpha * (k * xi_prime).cos() * (k * eta_prime).sinh();
+    }
+    let scale = UTM_SCALE * rectifying_radius();
+    let (hemisphere, false_northing) = if coordinates.latitude >= 0.0 {
+        (Hemisphere::North, 0.0)
+    } else {
+        (Hemisphere::South, FALSE_NORTHING)
+    };
+
+    Utm {
+        zone,
+        hemisphere,
+        easting: FALSE_EASTING + scale * eta,
+        northing: false_northing + scale * xi,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EarthModel;
+
+    const OSLO: Coordinates = Coordinates {
+        latitude: 59.9139,
+        longitude: 10.7522,
+    };
+
+    #[test]
+    fn mgrs_precision() {
+        let references = (0..=5)
+            .map(|precision| OSLO.to_mgrs(precision).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            [
+                "32VNM",
+                "32VNM94",
+                "32VNM9743",
+                "32VNM979431",
+                "32VNM97974311",
+                "32VNM9797943118",
+            ]
+        );
+        assert!(OSLO.to_mgrs(MAX_MGRS_PRECISION + 1).is_err());
+    }
+
+    /// Coordinates spread over the UTM grid, on the edges of the zones where the
+    /// projection is least accurate.
+    fn grid() -> impl Iterator<Item = Coordinates> {
+        (0..=40).flat_map(|i| {
+            (0..=60).map(move |j| Coordinates {
+                latitude: -79.9 + 163.8 * f64::from(i) / 40.0,
+                longitude: -180.0 + 6.0 * f64::from(j),
+            })
+        })
+    }
+
+    #[test]
+    fn utm_round_trip() {
+        for coordinates in grid() {
+            let utm = coordinates.to_utm().unwrap();
+            let decoded = utm.to_coordinates().unwrap();
+            let error = coordinates.distance_to(decoded, EarthModel::Wgs84);
+            assert!(error < 1e-3, "{coordinates:?} -> {utm} -> {decoded:?}");
+        }
+    }
+
+    #[test]
+    fn mgrs_round_trip() {
+        // The reference names the south-west corner of a 1 mm square, which adds
+        // up to 1.42 mm to the error of the projection.
+        for coordinates in grid() {
+            let reference = coordinates.to_mgrs(MAX_MGRS_PRECISION).unwrap();
+            let decoded = Coordinates::from_mgrs(&reference).unwrap();
+            let error = coordinates.distance_to(decoded, EarthModel::Wgs84);
+            assert!(
+                error < 2.5e-3,
+                "{coordinates:?} -> {reference} -> {decoded:?}"
+            );
+        }
+    }
+}
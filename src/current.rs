@@ -0,0 +1,460 @@
+//! One-shot location requests, see [`Manager::current_location`].
+//!
+//! [`Manager::current_location`]: crate::Manager::current_location
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dispatch::Listener, request::Pending, Error, ErrorKind, LocationSnapshot, RequestId, Result,
+};
+
+/// How a call for the current location is answered.
+pub(crate) enum Current {
+    /// A recent enough location was already known.
+    Cached(LocationSnapshot),
+    /// The location was requested from the backend.
+    Requested(Waiter),
+}
+
+/// A single update requested from the backend, shared by every caller waiting
+/// for it.
+pub(crate) struct Request {
+    id: RequestId,
+    slot: Mutex<Slot>,
+    condvar: Condvar,
+    /// The pending single update of the manager, which is cancelled once nobody
+    /// waits for the request anymore.
+    pending: Weak<Pending>,
+}
+
+struct Slot {
+    result: Option<Result<LocationSnapshot>>,
+    wakers: Vec<Waker>,
+    /// The number of callers waiting for the request.
+    waiters: usize,
+}
+
+impl Request {
+    /// Creates a request for the single update `id`, with the caller that made it
+    /// waiting for it.
+    pub(crate) fn new(id: RequestId, pending: Weak<Pending>) -> Self {
+        Self {
+            id,
+            slot: Mutex::new(Slot {
+                result: None,
+                wakers: Vec::new(),
+                waiters: 1,
+            }),
+            condvar: Condvar::new(),
+            pending,
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Slot>> {
+        self.slot.lock().map_err(|_| Error::new(ErrorKind::Unknown))
+    }
+
+    /// Blocks until the request completes or `deadline` passes.
+    pub(crate) fn wait(&self, deadline: Option<Instant>) -> Result<LocationSnapshot> {
+        let mut slot = self.lock()?;
+        loop {
+            if let Some(result) = &slot.result {
+                return result.clone();
+            }
+            slot = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout.is_zero() {
+                        return Err(Error::new(ErrorKind::Timeout));
+                    }
+                    self.condvar
+                        .wait_timeout(slot, timeout)
+                        .map_err(|_| Error::new(ErrorKind::Unknown))?
+                        .0
+                }
+                None => self
+                    .condvar
+                    .wait(slot)
+                    .map_err(|_| Error::new(ErrorKind::Unknown))?,
+            };
+        }
+    }
+
+    fn complete(&self, result: Result<LocationSnapshot>) {
+        let Ok(mut slot) = self.lock() else {
+            return;
+        };
+        if slot.result.is_some() {
+            return;
+        }
+        slot.result = Some(result);
+        let wakers = std::mem::take(&mut slot.wakers);
+        drop(slot);
+
+        self.condvar.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Wakes every task waiting on the request, without completing it.
+    fn wake(&self) {
+        if let Ok(mut slot) = self.lock() {
+            slot.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+impl Listener for Request {
+    fn location(&self, location: &LocationSnapshot) {
+        self.complete(Ok(location.clone()));
+    }
+
+    fn error(&self, error: Error) {
+        // Errors answering other requests, or none, don't concern this one.
+        if error.request_id() == Some(self.id) {
+            self.complete(Err(error));
+        }
+    }
+}
+
+/// A caller waiting for a [`Request`], which is cancelled once every caller has
+/// timed out or dropped its future.
+pub(crate) struct Waiter {
+    request: Arc<Request>,
+    /// Whether the caller has already given up on the request.
+    gone: bool,
+}
+
+impl Waiter {
+    /// The caller that made `request`.
+    pub(crate) fn new(request: Arc<Request>) -> Self {
+        Self {
+            request,
+            gone: false,
+        }
+    }
+
+    /// Adds a caller to `request`, unless it has already completed.
+    pub(crate) fn join(request: Arc<Request>) -> Option<Self> {
+        let mut slot = request.lock().ok()?;
+        if slot.result.is_some() {
+            return None;
+        }
+        slot.waiters += 1;
+        drop(slot);
+        Some(Self::new(request))
+    }
+
+    /// Blocks until the request completes or `deadline` passes.
+    pub(crate) fn wait(&self, deadline: Option<Instant>) -> Result<LocationSnapshot> {
+        self.request.wait(deadline)
+    }
+
+    /// Removes the caller from the request. If it was the last one waiting for it,
+    /// the request is cancelled, and this returns whether it was the last request
+    /// for the manager's pending single update, which the backend can then stop
+    /// looking for.
+    pub(crate) fn give_up(mut self) -> bool {
+        self.leave()
+    }
+
+    fn leave(&mut self) -> bool {
+        if std::mem::replace(&mut self.gone, true) {
+            return false;
+        }
+        let request = &self.request;
+        let Ok(mut slot) = request.lock() else {
+            return false;
+        };
+        slot.waiters -= 1;
+        if slot.waiters > 0 || slot.result.is_some() {
+            return false;
+        }
+        drop(slot);
+        request.complete(Err(Error::new(ErrorKind::Cancelled)));
+        request
+            .pending
+            .upgrade()
+            .is_some_and(|pending| pending.cancel(request.id))
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // The backend isn't told, as the future may be dropped on any thread, but
+        // the location it still delivers isn't attributed to the request.
+        self.leave();
+    }
+}
+
+/// The future returned by [`Manager::current_location`].
+///
+/// [`Manager::current_location`]: crate::Manager::current_location
+pub(crate) struct CurrentLocation {
+    inner: Inner,
+}
+
+enum Inner {
+    Ready(Option<Result<LocationSnapshot>>),
+    Waiting {
+        waiter: Waiter,
+        deadline: Option<Instant>,
+        timer: bool,
+    },
+}
+
+impl CurrentLocation {
+    pub(crate) fn new(current: Result<Current>, timeout: Duration) -> Self {
+        let inner = match current {
+            Ok(Current::Cached(location)) => Inner::Ready(Some(Ok(location))),
+            Ok(Current::Requested(waiter)) => Inner::Waiting {
+                waiter,
+                deadline: Instant::now().checked_add(timeout),
+                timer: false,
+            },
+            Err(e) => Inner::Ready(Some(Err(e))),
+        };
+        Self { inner }
+    }
+}
+
+impl Future for CurrentLocation {
+    type Output = Result<LocationSnapshot>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (request, deadline, timer) = match &mut self.inner {
+            Inner::Ready(result) => {
+                return Poll::Ready(result.take().expect("polled after completion"))
+            }
+            Inner::Waiting {
+                waiter,
+                deadline,
+                timer,
+            } => (&waiter.request, *deadline, timer),
+        };
+
+        let mut slot = request.lock()?;
+        let result = match &slot.result {
+            Some(result) => result.clone(),
+            None if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                Err(Error::new(ErrorKind::Timeout))
+            }
+            None => {
+                if !slot.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    slot.wakers.push(cx.waker().clone());
+                }
+                drop(slot);
+
+                // The timeout doesn't depend on a particular async runtime, so a
+                // thread waits for the deadline and wakes the task if the request
+                // is still pending by then.
+                if let (Some(deadline), false) = (deadline, *timer) {
+                    let request = request.clone();
+                    thread::spawn(move || {
+                        if request.wait(Some(deadline)).is_err() {
+                            request.wake();
+                        }
+                    });
+                    *timer = true;
+                }
+                return Poll::Pending;
+            }
+        };
+        drop(slot);
+        // Giving up on the request cancels it, unless other callers still wait for
+        // it.
+        self.inner = Inner::Ready(None);
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::mpsc::{self, Receiver},
+        task::Wake,
+        thread::Thread,
+    };
+
+    use super::*;
+    use crate::{
+        event::FnHandler,
+        mock::{Call, Mock},
+        Coordinates, LocationBuilder, LocationEvent, Manager,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Wakes the thread blocked in [`block_on`].
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    fn manager() -> (Manager, Mock, Receiver<LocationEvent>) {
+        let mock = Mock::new();
+        let (sender, events) = mpsc::channel();
+        let manager = Manager::with_backend(
+            FnHandler(move |event| {
+                let _ = sender.send(event);
+            }),
+            mock.backend(),
+        )
+        .unwrap();
+        (manager, mock, events)
+    }
+
+    fn push(mock: &Mock, longitude: f64) {
+        mock.push(
+            LocationBuilder::new(Coordinates {
+                latitude: 48.0,
+                longitude,
+            })
+            .build(),
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let (manager, mock, _events) = manager();
+        let started = Instant::now();
+        let error = manager
+            .current_location_blocking(Duration::from_millis(50), Duration::ZERO)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Timeout);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        // The backend is told to stop looking.
+        assert_eq!(mock.calls(), [Call::UpdateOnce, Call::CancelUpdateOnce]);
+
+        // The future times out without the task being polled again by anything
+        // but its waker.
+        mock.clear_calls();
+        let error = block_on(manager.current_location(Duration::from_millis(50), Duration::ZERO))
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Timeout);
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+    }
+
+    #[test]
+    fn cache_hit() {
+        let (manager, mock, _events) = manager();
+        push(&mock, 2.0);
+
+        let location = manager
+            .current_location_blocking(TIMEOUT, Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(location.coordinates.longitude, 2.0);
+        let location =
+            block_on(manager.current_location(TIMEOUT, Duration::from_secs(60))).unwrap();
+        assert_eq!(location.coordinates.longitude, 2.0);
+        // The cached location doesn't answer a request.
+        assert_eq!(location.request, None);
+        assert_eq!(mock.calls(), []);
+
+        // An older location isn't good enough.
+        thread::sleep(Duration::from_millis(10));
+        let future = manager.current_location(TIMEOUT, Duration::from_millis(5));
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+        push(&mock, 3.0);
+        assert_eq!(block_on(future).unwrap().coordinates.longitude, 3.0);
+    }
+
+    #[test]
+    fn shared_request() {
+        let (manager, mock, _events) = manager();
+        let first = manager.current_location(TIMEOUT, Duration::ZERO);
+        let second = manager.current_location(TIMEOUT, Duration::ZERO);
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+
+        // A blocking caller joins the same request from another thread.
+        let third = thread::scope(|scope| {
+            let third = scope.spawn(|| manager.current_location_blocking(TIMEOUT, Duration::ZERO));
+            let waiters = || {
+                let request = manager.shared.lock().unwrap().current.upgrade().unwrap();
+                let waiters = request.lock().unwrap().waiters;
+                waiters
+            };
+            while waiters() < 3 {
+                thread::yield_now();
+            }
+            push(&mock, 2.0);
+            third.join().unwrap().unwrap()
+        });
+        let first = block_on(first).unwrap();
+        let second = block_on(second).unwrap();
+        assert_eq!(first.coordinates.longitude, 2.0);
+        assert_eq!(first, second);
+        assert_eq!(first, third);
+        assert!(first.request.is_some());
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+
+        // Once answered, the next call makes a new request.
+        let future = manager.current_location(TIMEOUT, Duration::ZERO);
+        assert_eq!(mock.calls(), [Call::UpdateOnce, Call::UpdateOnce]);
+        push(&mock, 3.0);
+        assert_ne!(block_on(future).unwrap().request, first.request);
+    }
+
+    #[test]
+    fn cancellation() {
+        let (manager, mock, events) = manager();
+        let first = manager.current_location(TIMEOUT, Duration::ZERO);
+        let second = manager.current_location(TIMEOUT, Duration::ZERO);
+
+        // The request is kept while anyone waits for it.
+        drop(first);
+        let third = manager.current_location(TIMEOUT, Duration::ZERO);
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+
+        // Once every caller has dropped its future, the request is cancelled, and
+        // the location the backend still delivers doesn't answer it.
+        drop(second);
+        drop(third);
+        push(&mock, 2.0);
+        let Ok(LocationEvent::Location(location)) = events.try_recv() else {
+            panic!("expected a location");
+        };
+        assert_eq!(location.request, None);
+        // The backend isn't told, as the future may be dropped on any thread.
+        assert_eq!(mock.calls(), [Call::UpdateOnce]);
+
+        // A new caller makes a new request.
+        let future = manager.current_location(TIMEOUT, Duration::ZERO);
+        assert_eq!(mock.calls(), [Call::UpdateOnce, Call::UpdateOnce]);
+        push(&mock, 3.0);
+        assert!(block_on(future).unwrap().request.is_some());
+    }
+
+    #[test]
+    fn error() {
+        let (manager, mock, _events) = manager();
+        let future = manager.current_location(TIMEOUT, Duration::ZERO);
+        // Errors that don't end the request are ignored.
+        mock.push_error(Error::new(ErrorKind::Parse));
+        mock.push_error(ErrorKind::TemporarilyUnavailable);
+        let error = block_on(future).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TemporarilyUnavailable);
+    }
+}
@@ -0,0 +1,301 @@
+//! Fans out the events a backend delivers to the user's handler and to the other
+//! consumers of a [`Manager`](crate::Manager), such as update streams and
+//! subscriptions.
+
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::{
+    cache::Cache,
+    filter::KalmanFilter,
+    request::Pending,
+    validation::{Rejection, Validator},
+    AuthorizationStatus, Error, ErrorKind, Handler, Location, LocationSnapshot,
+};
+
+/// An event whose delivery was deferred.
+type Event = Box<dyn FnOnce()>;
+
+thread_local! {
+    /// The events raised on this thread while it holds the lock of a manager, which
+    /// are delivered once it is released, or `None` if it doesn't hold one.
+    static DEFERRED: RefCell<Option<Vec<Event>>> = const { RefCell::new(None) };
+}
+
+/// Defers the events raised on the current thread until it is dropped.
+///
+/// Backends are called with the manager locked, and some of them report events
+/// straight away, e.g. a cached location or a denied authorization. Delivering
+/// those later lets handlers call back into the manager.
+pub(crate) struct Deferral {
+    /// Whether this is the outermost deferral on the thread, which delivers the
+    /// events.
+    outermost: bool,
+}
+
+impl Deferral {
+    pub(crate) fn new() -> Self {
+        let outermost = DEFERRED.with(|deferred| {
+            let mut deferred = deferred.borrow_mut();
+            let outermost = deferred.is_none();
+            if outermost {
+                *deferred = Some(Vec::new());
+            }
+            outermost
+        });
+        Self { outermost }
+    }
+}
+
+impl Drop for Deferral {
+    fn drop(&mut self) {
+        if !self.outermost {
+            return;
+        }
+        let events = DEFERRED.with(|deferred| deferred.borrow_mut().take());
+        events.into_iter().flatten().for_each(|event| event());
+    }
+}
+
+/// Returns whether events raised on the current thread are being deferred.
+fn deferring() -> bool {
+    DEFERRED.with(|deferred| deferred.borrow().is_some())
+}
+
+/// A consumer of the events delivered to a manager, other than its handler.
+pub(crate) trait Listener: Send + Sync {
+    fn location(&self, location: &LocationSnapshot);
+
+    fn error(&self, error: Error);
+
+    /// Called when the backend has no more updates to deliver.
+    fn finished(&self) {}
+
+    fn authorization_changed(&self, _status: AuthorizationStatus) {}
+}
+
+/// The handler given to backends, which forwards everything to the user's handler
+/// and any registered listeners.
+#[derive(Clone)]
+pub(crate) struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    handler: Box<dyn Handler>,
+    cache: Cache,
+    pending: Arc<Pending>,
+    /// The validator dropping or flagging invalid locations, if enabled.
+    validator: Mutex<Option<Validator>>,
+    /// The filter smoothing locations before they are delivered, if enabled.
+    filter: Mutex<Option<KalmanFilter>>,
+    /// Listeners are held weakly so that dropping a consumer unregisters it.
+    listeners: Mutex<Vec<Weak<dyn Listener>>>,
+}
+
+impl Dispatcher {
+    pub(crate) fn new<T>(handler: T) -> Self
+    where
+        T: Handler,
+    {
+        Self {
+            inner: Arc::new(Inner {
+                handler: Box::new(handler),
+                cache: Cache::new(),
+                pending: Arc::default(),
+                validator: Mutex::new(None),
+                filter: Mutex::new(None),
+                listeners: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Registers a listener until it is dropped.
+    pub(crate) fn add(&self, listener: &Arc<dyn Listener>) {
+        if let Ok(mut listeners) = self.inner.listeners.lock() {
+            listeners.push(Arc::downgrade(listener));
+        }
+    }
+
+    /// The most recent location delivered by the backend.
+    pub(crate) fn cache(&self) -> &Cache {
+        &self.inner.cache
+    }
+
+    /// The single update requested with `update_once`, if any.
+    pub(crate) fn pending(&self) -> &Arc<Pending> {
+        &self.inner.pending
+    }
+
+    /// The validator dropping or flagging invalid locations, if enabled.
+    pub(crate) fn validator(&self) -> &Mutex<Option<Validator>> {
+        &self.inner.validator
+    }
+
+    /// The filter smoothing locations before they are delivered, if enabled.
+    pub(crate) fn filter(&self) -> &Mutex<Option<KalmanFilter>> {
+        &self.inner.filter
+    }
+
+    /// Checks `location` if validation is enabled, reporting it to the handler if
+    /// it is invalid. Returns whether it is valid, or why it should be dropped.
+    fn validate(&self, location: &LocationSnapshot) -> Result<bool, Rejection> {
+        let (rejection, flag_only) = {
+            let Ok(mut validator) = self.inner.validator.lock() else {
+                return Ok(true);
+            };
+            let Some(validator) = validator.as_mut() else {
+                return Ok(true);
+            };
+            match validator.check(location) {
+                Some(rejection) => (rejection, validator.config().flag_only),
+                None => return Ok(true),
+            }
+        };
+        // The handler is called without holding the lock, so that it can
+        // reconfigure validation.
+        self.inner.handler.rejected(location.clone(), rejection);
+        if flag_only {
+            Ok(false)
+        } else {
+            Err(rejection)
+        }
+    }
+
+    /// Answers the pending request, if any, with an error once a location is
+    /// dropped, as backends stop looking for one after delivering it.
+    fn dropped(&self, rejection: Rejection) {
+        let Some(id) = self.inner.pending.take() else {
+            return;
+        };
+        let error = Error::new(ErrorKind::TemporarilyUnavailable)
+            .with_message(format!("location dropped by validation: {rejection}"))
+            .with_request_id(id);
+        self.inner.handler.error(error.clone());
+        self.listeners()
+            .iter()
+            .for_each(|listener| listener.error(error.clone()));
+    }
+
+    /// Smooths `location` if a filter is enabled.
+    fn smooth(&self, location: &LocationSnapshot) -> Option<LocationSnapshot> {
+        let mut filter = self.inner.filter.lock().ok()?;
+        Some(filter.as_mut()?.update(location))
+    }
+
+    /// Queues `event` until the current thread releases the manager's lock.
+    fn defer(&self, event: impl FnOnce(&Dispatcher) + 'static) {
+        let dispatcher = self.clone();
+        DEFERRED.with(|deferred| {
+            if let Some(deferred) = deferred.borrow_mut().as_mut() {
+                deferred.push(Box::new(move || event(&dispatcher)));
+            }
+        });
+    }
+
+    /// Returns the live listeners, forgetting those that were dropped.
+    fn listeners(&self) -> Vec<Arc<dyn Listener>> {
+        let Ok(mut listeners) = self.inner.listeners.lock() else {
+            return Vec::new();
+        };
+        listeners.retain(|listener| listener.strong_count() > 0);
+        // The listeners are called without holding the lock, so that they can
+        // register or drop other listeners.
+        listeners.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+impl Handler for Dispatcher {
+    fn handle(&self, location: Location<'_>) {
+        let snapshot = location.snapshot();
+        // Locations without coordinates can't be owned, so they are always
+        // delivered straight away.
+        if let Ok(snapshot) = &snapshot {
+            if deferring() {
+                let snapshot = snapshot.clone();
+                self.defer(move |dispatcher| dispatcher.handle(snapshot.into()));
+                return;
+            }
+        }
+
+        let (mut location, snapshot, valid) = match snapshot {
+            Ok(snapshot) => {
+                let valid = match self.validate(&snapshot) {
+                    Ok(valid) => valid,
+                    Err(rejection) => return self.dropped(rejection),
+                };
+                // Flagged locations are delivered as is, so that they don't
+                // disturb the filter.
+                match valid.then(|| self.smooth(&snapshot)).flatten() {
+                    Some(smoothed) => (smoothed.clone().into(), Ok(smoothed), valid),
+                    None => (location, Ok(snapshot), valid),
+                }
+            }
+            Err(error) => (location, Err(error), false),
+        };
+        let request = self.inner.pending.take();
+        location.request = request;
+        let snapshot = snapshot.map(|snapshot| LocationSnapshot {
+            request,
+            ..snapshot
+        });
+        self.inner.handler.handle(location);
+
+        let listeners = self.listeners();
+        match snapshot {
+            Ok(snapshot) => {
+                // Flagged locations aren't remembered, so that they are never
+                // handed out as the last known location.
+                if valid {
+                    self.inner.cache.insert(&snapshot);
+                }
+                listeners
+                    .iter()
+                    .for_each(|listener| listener.location(&snapshot));
+            }
+            Err(error) => listeners
+                .iter()
+                .for_each(|listener| listener.error(error.clone())),
+        }
+    }
+
+    fn error(&self, error: Error) {
+        if deferring() {
+            return self.defer(move |dispatcher| dispatcher.error(error));
+        }
+        let id = error
+            .ends_request()
+            .then(|| self.inner.pending.take())
+            .flatten();
+        let error = match id {
+            Some(id) => error.with_request_id(id),
+            None => error,
+        };
+        self.inner.handler.error(error.clone());
+        self.listeners()
+            .iter()
+            .for_each(|listener| listener.error(error.clone()));
+    }
+
+    fn finished(&self) {
+        if deferring() {
+            return self.defer(|dispatcher| dispatcher.finished());
+        }
+        self.inner.handler.finished();
+        self.listeners()
+            .iter()
+            .for_each(|listener| listener.finished());
+    }
+
+    fn authorization_changed(&self, status: AuthorizationStatus) {
+        if deferring() {
+            return self.defer(move |dispatcher| dispatcher.authorization_changed(status));
+        }
+        self.inner.handler.authorization_changed(status);
+        self.listeners()
+            .iter()
+            .for_each(|listener| listener.authorization_changed(status));
+    }
+}
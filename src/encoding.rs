@@ -0,0 +1,981 @@
+//! Text encodings of [`Coordinates`] used to share locations: [geohashes],
+//! [Open Location Codes] (Plus Codes) and [Maidenhead locators].
+//!
+//! Each code names a cell rather than a point, which decodes to an [`Area`]. Longer
+//! codes name smaller cells. The `LocationSnapshot` methods, such as
+//! [`LocationSnapshot::geohash`], choose the length from the reported accuracy.
+//!
+//! ```
+//! # use robius_location::{encoding::Area, Coordinates};
+//! let coordinates = Coordinates { latitude: 47.365590, longitude: 8.524997 };
+//!
+//! assert_eq!(coordinates.to_geohash(9).unwrap(), "u0qj3yxsw");
+//! assert_eq!(coordinates.to_plus_code(10).unwrap(), "8FVC9G8F+6X");
+//! assert_eq!(coordinates.to_maidenhead(6).unwrap(), "JN47gi");
+//!
+//! let area = Area::from_plus_code("8FVC9G8F+6X").unwrap();
+//! assert!(area.contains(coordinates));
+//! ```
+//!
+//! [geohashes]: https://en.wikipedia.org/wiki/Geohash
+//! [Open Location Codes]: https://github.com/google/open-location-code
+//! [Maidenhead locators]: https://en.wikipedia.org/wiki/Maidenhead_Locator_System
+
+// `is_multiple_of` is only stable since Rust 1.87.
+#![allow(clippy::manual_is_multiple_of)]
+
+use crate::{Coordinates, Error, ErrorKind, LocationSnapshot, Result};
+
+/// The length of a degree of latitude, in meters, which is also the length of a
+/// degree of longitude at the equator.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// A rectangular cell named by a code, in degrees.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Area {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl Area {
+    pub fn center(&self) -> Coordinates {
+        Coordinates {
+            latitude: (self.south + self.north) / 2.0,
+            longitude: (self.west + self.east) / 2.0,
+        }
+    }
+
+    /// Returns whether `coordinates` are inside the cell, including its southern
+    /// and western edges but not its northern and eastern ones.
+    pub fn contains(&self, coordinates: Coordinates) -> bool {
+        (self.south..self.north).contains(&coordinates.latitude)
+            && (self.west..self.east).contains(&coordinates.longitude)
+    }
+
+    /// Returns whether the cell is no larger than `meters` in either direction.
+    fn fits(&self, meters: f64) -> bool {
+        let scale = self.center().latitude.to_radians().cos();
+        (self.north - self.south) * METERS_PER_DEGREE <= meters
+            && (self.east - self.west) * METERS_PER_DEGREE * scale <= meters
+    }
+}
+
+fn check(coordinates: Coordinates) -> Result<()> {
+    if !(-90.0..=90.0).contains(&coordinates.latitude) || !coordinates.longitude.is_finite() {
+        return Err(Error::new(ErrorKind::OutOfRange)
+            .with_message(format!("invalid coordinates {coordinates:?}")));
+    }
+    Ok(())
+}
+
+/// Wraps a longitude into `[-180, 180)`.
+fn normalize_longitude(longitude: f64) -> f64 {
+    (longitude + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn parse_error(kind: &str, code: &str) -> Error {
+    Error::new(ErrorKind::Parse).with_message(format!("invalid {kind} {code:?}"))
+}
+
+// Geohash
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// The longest geohash that `f64` coordinates can fill.
+const MAX_GEOHASH_LENGTH: usize = 20;
+const DEFAULT_GEOHASH_LENGTH: usize = 9;
+
+/// The cells around a geohash, of the same length.
+///
+/// There is nothing north of cells touching the north pole, or south of cells
+/// touching the south pole.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Neighbors {
+    pub north: Option<String>,
+    pub north_east: Option<String>,
+    pub east: String,
+    pub south_east: Option<String>,
+    pub south: Option<String>,
+    pub south_west: Option<String>,
+    pub west: String,
+    pub north_west: Option<String>,
+}
+
+impl Area {
+    /// Decodes a geohash of any length.
+    ///
+    /// Fails with [`ErrorKind::Parse`] if the geohash is empty or contains a
+    /// character outside of its alphabet.
+    pub fn from_geohash(geohash: &str) -> Result<Area> {
+        if geohash.is_empty() {
+            return Err(parse_error("geohash", geohash));
+        }
+        let mut area = Area {
+            south: -90.0,
+            west: -180.0,
+            north: 90.0,
+            east: 180.0,
+        };
+        // Bits alternate between longitude and latitude, starting with longitude.
+        let mut longitude = true;
+        for c in geohash.bytes() {
+            let value = GEOHASH_ALPHABET
+                .iter()
+                .position(|&d| d == c.to_ascii_lowercase())
+                .ok_or_else(|| parse_error("geohash", geohash))?;
+            for bit in (0..5).rev() {
+                let upper = value & (1 << bit) != 0;
+                let (low, high) = if longitude {
+                    (&mut area.west, &mut area.east)
+                } else {
+                    (&mut area.south, &mut area.north)
+                };
+                let middle = (*low + *high) / 2.0;
+                if upper {
+                    *low = middle;
+                } else {
+                    *high = middle;
+                }
+                longitude = !longitude;
+            }
+        }
+        Ok(area)
+    }
+}
+
+impl Coordinates {
+    /// Encodes the coordinates as a geohash of `length` characters, from 1 to 20.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] if the coordinates are invalid or the
+    /// length is out of range.
+    ///
+    /// ```
+    /// # use robius_location::Coordinates;
+    /// let coordinates = Coordinates { latitude: 57.64911, longitude: 10.40744 };
+    /// assert_eq!(coordinates.to_geohash(11).unwrap(), "u4pruydqqvj");
+    /// ```
+    pub fn to_geohash(self, length: usize) -> Result<String> {
+        check(self)?;
+        if !(1..=MAX_GEOHASH_LENGTH).contains(&length) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("geohash length {length} is out of range")));
+        }
+        let target = [normalize_longitude(self.longitude), self.latitude];
+        let mut bounds = [(-180.0, 180.0), (-90.0, 90.0)];
+        let mut geohash = String::with_capacity(length);
+        let mut bit = 0;
+        for _ in 0..length {
+            let mut value = 0;
+            for _ in 0..5 {
+                let axis = bit % 2;
+                let (low, high) = &mut bounds[axis];
+                let middle = (*low + *high) / 2.0;
+                value <<= 1;
+                if target[axis] >= middle {
+                    value |= 1;
+                    *low = middle;
+                } else {
+                    *high = middle;
+                }
+                bit += 1;
+            }
+            geohash.push(GEOHASH_ALPHABET[value] as char);
+        }
+        Ok(geohash)
+    }
+}
+
+/// Returns the eight cells around `geohash`.
+///
+/// Fails with [`ErrorKind::Parse`] if the geohash is invalid.
+///
+/// ```
+/// # use robius_location::encoding::geohash_neighbors;
+/// let neighbors = geohash_neighbors("u0qj").unwrap();
+/// assert_eq!(neighbors.north.as_deref(), Some("u0qn"));
+/// assert_eq!(neighbors.east, "u0qm");
+/// ```
+pub fn geohash_neighbors(geohash: &str) -> Result<Neighbors> {
+    let area = Area::from_geohash(geohash)?;
+    let length = geohash.len();
+    let center = area.center();
+    let (height, width) = (area.north - area.south, area.east - area.west);
+
+    let neighbor = |rows: f64, columns: f64| {
+        let latitude = center.latitude + rows * height;
+        if !(-90.0..=90.0).contains(&latitude) {
+            return None;
+        }
+        Coordinates {
+            latitude,
+            longitude: normalize_longitude(center.longitude + columns * width),
+        }
+        .to_geohash(length)
+        .ok()
+    };
+    // East and west always exist, as longitudes wrap around.
+    let beside = |columns| neighbor(0.0, columns).ok_or_else(|| parse_error("geohash", geohash));
+    Ok(Neighbors {
+        north: neighbor(1.0, 0.0),
+        north_east: neighbor(1.0, 1.0),
+        east: beside(1.0)?,
+        south_east: neighbor(-1.0, 1.0),
+        south: neighbor(-1.0, 0.0),
+        south_west: neighbor(-1.0, -1.0),
+        west: beside(-1.0)?,
+        north_west: neighbor(1.0, -1.0),
+    })
+}
+
+// Open Location Code
+
+const OLC_ALPHABET: &[u8; 20] = b"23456789CFGHJMPQRVWX";
+const OLC_SEPARATOR: char = '+';
+const OLC_SEPARATOR_POSITION: usize = 8;
+const OLC_PADDING: char = '0';
+/// The number of digits encoded as pairs of latitude and longitude digits.
+const OLC_PAIR_LENGTH: usize = 10;
+const OLC_MAX_LENGTH: usize = 15;
+const OLC_GRID_ROWS: i64 = 5;
+const OLC_GRID_COLUMNS: i64 = 4;
+/// Coordinates are encoded as integers in units of the smallest cell.
+const OLC_LATITUDE_UNITS: i64 = 8000 * 3125;
+const OLC_LONGITUDE_UNITS: i64 = 8000 * 1024;
+const DEFAULT_PLUS_CODE_LENGTH: usize = 10;
+
+impl Coordinates {
+    /// Encodes the coordinates as a full Open Location Code of `length` digits,
+    /// which is 2, 4, 6, 8, or 10 to 15.
+    ///
+    /// Latitudes are clipped to the poles. Fails with [`ErrorKind::OutOfRange`] if
+    /// the coordinates are invalid or the length isn't allowed.
+    pub fn to_plus_code(self, length: usize) -> Result<String> {
+        check(self)?;
+        if !(2..=OLC_MAX_LENGTH).contains(&length) || (length < OLC_PAIR_LENGTH && length % 2 != 0)
+        {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("plus code length {length} is not allowed")));
+        }
+        // Rounding first removes the floating point error of the multiplication.
+        let units = |degrees: f64, units: i64| {
+            ((degrees * units as f64 * 1e6).round() / 1e6).floor() as i64
+        };
+        let mut latitude = units(self.latitude + 90.0, OLC_LATITUDE_UNITS);
+        let mut longitude = units(self.longitude + 180.0, OLC_LONGITUDE_UNITS)
+            .rem_euclid(360 * OLC_LONGITUDE_UNITS);
+        // The north pole is in the northernmost row of cells.
+        latitude = latitude.min(180 * OLC_LATITUDE_UNITS - 1);
+
+        // Digits are produced from the least significant one.
+        let mut digits = Vec::with_capacity(OLC_MAX_LENGTH);
+        for _ in OLC_PAIR_LENGTH..OLC_MAX_LENGTH {
+            let row = latitude % OLC_GRID_ROWS;
+            let column = longitude % OLC_GRID_COLUMNS;
+            digits.push(OLC_ALPHABET[(row * OLC_GRID_COLUMNS + column) as usize]);
+            latitude /= OLC_GRID_ROWS;
+            longitude /= OLC_GRID_COLUMNS;
+        }
+        for _ in 0..OLC_PAIR_LENGTH / 2 {
+            digits.push(OLC_ALPHABET[(longitude % 20) as usize]);
+            digits.push(OLC_ALPHABET[(latitude % 20) as usize]);
+            latitude /= 20;
+            longitude /= 20;
+        }
+        digits.reverse();
+        digits.truncate(length);
+
+        let mut code: String = digits.into_iter().map(char::from).collect();
+        while code.len() < OLC_SEPARATOR_POSITION {
+            code.push(OLC_PADDING);
+        }
+        code.insert(OLC_SEPARATOR_POSITION, OLC_SEPARATOR);
+        Ok(code)
+    }
+
+    /// Encodes the coordinates as a short Open Location Code of `length` digits,
+    /// omitting the leading digits that can be recovered from `reference`, a point
+    /// nearby such as the center of a town.
+    ///
+    /// Four, six or eight digits are omitted. Fails like
+    /// [`to_plus_code`](Self::to_plus_code), and with [`ErrorKind::OutOfRange`] if
+    /// the length is under 10, as such codes can't be shortened. The full code is
+    /// returned if `reference` is too far away.
+    ///
+    /// ```
+    /// # use robius_location::{encoding::Area, Coordinates};
+    /// let coordinates = Coordinates { latitude: 47.365590, longitude: 8.524997 };
+    /// let zurich = Coordinates { latitude: 47.37, longitude: 8.54 };
+    ///
+    /// let code = coordinates.to_short_plus_code(10, zurich).unwrap();
+    /// assert_eq!(code, "9G8F+6X");
+    /// assert!(Area::from_short_plus_code(&code, zurich).unwrap().contains(coordinates));
+    /// ```
+    pub fn to_short_plus_code(self, length: usize, reference: Coordinates) -> Result<String> {
+        check(reference)?;
+        if length < OLC_PAIR_LENGTH {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("plus codes of length {length} can't be shortened")));
+        }
+        let code = self.to_plus_code(length)?;
+        let center = Area::from_plus_code(&code)?.center();
+        let range = f64::max(
+            (center.latitude - reference.latitude).abs(),
+            normalize_longitude(center.longitude - reference.longitude).abs(),
+        );
+        // Remove as many leading pairs as the reference pins down, with a safety
+        // margin so that recovery doesn't pick a neighboring cell. The first pair is
+        // never removed on its own.
+        for pairs in (2..=4).rev() {
+            if range < pair_resolution(pairs - 1) * 0.3 {
+                return Ok(code[pairs * 2..].to_string());
+            }
+        }
+        Ok(code)
+    }
+}
+
+/// The size in degrees of the cells named by the first `pairs + 1` pairs of digits.
+fn pair_resolution(pairs: usize) -> f64 {
+    20.0 / 20f64.powi(pairs as i32)
+}
+
+/// A code that passed validation, without separator and padding, uppercase.
+struct PlusCode {
+    digits: Vec<u8>,
+    /// The position of the separator in the original code.
+    separator: usize,
+}
+
+fn parse_plus_code(code: &str) -> Result<PlusCode> {
+    let error = || parse_error("plus code", code);
+    let upper = code.to_ascii_uppercase();
+    let separator = upper.find(OLC_SEPARATOR).ok_or_else(error)?;
+    let (before, after) = (&upper[..separator], &upper[separator + 1..]);
+    if separator > OLC_SEPARATOR_POSITION
+        || separator % 2 != 0
+        || after.contains(OLC_SEPARATOR)
+        || after.len() == 1
+        || separator + after.len() == 0
+    {
+        return Err(error());
+    }
+
+    let unpadded = before.trim_end_matches(OLC_PADDING);
+    if unpadded.len() != before.len() {
+        // Padding replaces whole pairs up to the separator, and ends the code.
+        let padding = before.len() - unpadded.len();
+        if unpadded.is_empty()
+            || separator != OLC_SEPARATOR_POSITION
+            || padding % 2 != 0
+            || !after.is_empty()
+        {
+            return Err(error());
+        }
+    }
+    let digits: Vec<u8> = unpadded.bytes().chain(after.bytes()).collect();
+    if digits.len() > OLC_MAX_LENGTH || !digits.iter().all(|c| OLC_ALPHABET.contains(c)) {
+        return Err(error());
+    }
+    Ok(PlusCode { digits, separator })
+}
+
+fn olc_value(digit: u8) -> i64 {
+    OLC_ALPHABET.iter().position(|&c| c == digit).unwrap_or(0) as i64
+}
+
+impl Area {
+    /// Decodes a full Open Location Code.
+    ///
+    /// Fails with [`ErrorKind::Parse`] if the code is invalid or short, see
+    /// [`from_short_plus_code`](Self::from_short_plus_code).
+    pub fn from_plus_code(code: &str) -> Result<Area> {
+        let PlusCode { digits, separator } = parse_plus_code(code)?;
+        // The first pair can't exceed 180° of latitude or 360° of longitude.
+        if separator != OLC_SEPARATOR_POSITION
+            || olc_value(digits[0]) * 20 >= 180
+            || digits.get(1).is_some_and(|&c| olc_value(c) * 20 >= 360)
+        {
+            return Err(parse_error("full plus code", code));
+        }
+
+        let mut latitude = 0;
+        let mut longitude = 0;
+        let mut latitude_place = 20 * 20 * OLC_LATITUDE_UNITS;
+        let mut longitude_place = 20 * 20 * OLC_LONGITUDE_UNITS;
+        for (i, &digit) in digits.iter().enumerate() {
+            let value = olc_value(digit);
+            if i < OLC_PAIR_LENGTH {
+                if i % 2 == 0 {
+                    latitude_place /= 20;
+                    latitude += value * latitude_place;
+                } else {
+                    longitude_place /= 20;
+                    longitude += value * longitude_place;
+                }
+            } else {
+                latitude_place /= OLC_GRID_ROWS;
+                longitude_place /= OLC_GRID_COLUMNS;
+                latitude += value / OLC_GRID_COLUMNS * latitude_place;
+                longitude += value % OLC_GRID_COLUMNS * longitude_place;
+            }
+        }
+
+        let latitude_units = OLC_LATITUDE_UNITS as f64;
+        let longitude_units = OLC_LONGITUDE_UNITS as f64;
+        Ok(Area {
+            south: latitude as f64 / latitude_units - 90.0,
+            west: longitude as f64 / longitude_units - 180.0,
+            north: (latitude + latitude_place) as f64 / latitude_units - 90.0,
+            east: (longitude + longitude_place) as f64 / longitude_units - 180.0,
+        })
+    }
+
+    /// Decodes an Open Location Code, recovering the digits a short code omits from
+    /// `reference`, which must be within about half the size of the omitted cell.
+    ///
+    /// Full codes are decoded as with [`from_plus_code`](Self::from_plus_code).
+    pub fn from_short_plus_code(code: &str, reference: Coordinates) -> Result<Area> {
+        check(reference)?;
+        let PlusCode { digits, separator } = parse_plus_code(code)?;
+        if separator == OLC_SEPARATOR_POSITION {
+            return Area::from_plus_code(code);
+        }
+
+        let omitted = OLC_SEPARATOR_POSITION - separator;
+        let resolution = pair_resolution(omitted / 2 - 1);
+        let half = resolution / 2.0;
+        let reference = Coordinates {
+            latitude: reference.latitude,
+            longitude: normalize_longitude(reference.longitude),
+        };
+
+        // Take the omitted digits from the reference, then move to the neighboring
+        // cell if that is closer to it.
+        let prefix = reference.to_plus_code(OLC_PAIR_LENGTH)?;
+        let mut full = prefix[..omitted].to_string();
+        full.extend(digits.iter().map(|&c| char::from(c)));
+        let length = full.len();
+        let mut center = Area::from_plus_code(&format_plus_code(full))?.center();
+        if reference.latitude + half < center.latitude && center.latitude - resolution >= -90.0 {
+            center.latitude -= resolution;
+        } else if reference.latitude - half > center.latitude
+            && center.latitude + resolution <= 90.0
+        {
+            center.latitude += resolution;
+        }
+        if reference.longitude + half < center.longitude {
+            center.longitude -= resolution;
+        } else if reference.longitude - half > center.longitude {
+            center.longitude += resolution;
+        }
+        Area::from_plus_code(&center.to_plus_code(length)?)
+    }
+}
+
+/// Inserts the separator into at least eight unpadded digits.
+fn format_plus_code(mut digits: String) -> String {
+    digits.insert(OLC_SEPARATOR_POSITION, OLC_SEPARATOR);
+    digits
+}
+
+// Maidenhead
+
+const MAIDENHEAD_LENGTHS: [usize; 4] = [2, 4, 6, 8];
+const DEFAULT_MAIDENHEAD_LENGTH: usize = 6;
+
+/// The size in degrees of the longitude and latitude steps of each pair of
+/// characters, and the number of steps per pair: fields, squares, subsquares and
+/// extended squares.
+const MAIDENHEAD_PAIRS: [(f64, f64, u32); 4] = [
+    (20.0, 10.0, 18),
+    (2.0, 1.0, 10),
+    (2.0 / 24.0, 1.0 / 24.0, 24),
+    (2.0 / 240.0, 1.0 / 240.0, 10),
+];
+
+impl Coordinates {
+    /// Encodes the coordinates as a Maidenhead locator of 2, 4, 6 or 8 characters,
+    /// e.g. `FN31pr`.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] if the coordinates are invalid or the
+    /// length isn't allowed.
+    ///
+    /// ```
+    /// # use robius_location::Coordinates;
+    /// let coordinates = Coordinates { latitude: 41.714775, longitude: -72.727260 };
+    /// assert_eq!(coordinates.to_maidenhead(6).unwrap(), "FN31pr");
+    /// ```
+    pub fn to_maidenhead(self, length: usize) -> Result<String> {
+        check(self)?;
+        if !MAIDENHEAD_LENGTHS.contains(&length) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("Maidenhead locator length {length} is not allowed")));
+        }
+        let mut longitude = normalize_longitude(self.longitude) + 180.0;
+        let mut latitude = self.latitude + 90.0;
+        let mut locator = String::with_capacity(length);
+        for (i, &(width, height, steps)) in MAIDENHEAD_PAIRS[..length / 2].iter().enumerate() {
+            // The north pole is in the northernmost row of cells.
+            let column = ((longitude / width).floor() as u32).min(steps - 1);
+            let row = ((latitude / height).floor() as u32).min(steps - 1);
+            longitude -= f64::from(column) * width;
+            latitude -= f64::from(row) * height;
+            for step in [column, row] {
+                locator.push(match i {
+                    0 => char::from(b'A' + step as u8),
+                    2 => char::from(b'a' + step as u8),
+                    _ => char::from(b'0' + step as u8),
+                });
+            }
+        }
+        Ok(locator)
+    }
+}
+
+impl Area {
+    /// Decodes a Maidenhead locator of 2, 4, 6 or 8 characters, in any case.
+    ///
+    /// Fails with [`ErrorKind::Parse`] if the locator is invalid.
+    pub fn from_maidenhead(locator: &str) -> Result<Area> {
+        let error = || parse_error("Maidenhead locator", locator);
+        let bytes = locator.as_bytes();
+        if !MAIDENHEAD_LENGTHS.contains(&bytes.len()) {
+            return Err(error());
+        }
+        let (mut west, mut south) = (-180.0, -90.0);
+        let (mut width, mut height) = (360.0, 180.0);
+        for (i, pair) in bytes.chunks(2).enumerate() {
+            let (step_width, step_height, steps) = MAIDENHEAD_PAIRS[i];
+            let mut values = pair.iter().map(|&c| match i {
+                0 | 2 => c.to_ascii_uppercase().checked_sub(b'A'),
+                _ => c.checked_sub(b'0'),
+            });
+            let mut next = || {
+                values
+                    .next()
+                    .flatten()
+                    .map(u32::from)
+                    .filter(|&value| value < steps)
+                    .ok_or_else(error)
+            };
+            let (column, row) = (next()?, next()?);
+            west += f64::from(column) * step_width;
+            south += f64::from(row) * step_height;
+            (width, height) = (step_width, step_height);
+        }
+        Ok(Area {
+            south,
+            west,
+            north: south + height,
+            east: west + width,
+        })
+    }
+}
+
+// Precision from accuracy
+
+impl LocationSnapshot {
+    /// Encodes the location as a geohash whose cells are no larger than the
+    /// horizontal accuracy, or of 9 characters, about 5 m, if it is unknown.
+    pub fn geohash(&self) -> Result<String> {
+        let lengths = (1..=MAX_GEOHASH_LENGTH).collect::<Vec<_>>();
+        self.encode(&lengths, DEFAULT_GEOHASH_LENGTH, |coordinates, length| {
+            let geohash = coordinates.to_geohash(length)?;
+            Ok((Area::from_geohash(&geohash)?, geohash))
+        })
+    }
+
+    /// Encodes the location as a full Open Location Code whose cells are no larger
+    /// than the horizontal accuracy, or of 10 digits, about 14 m, if it is unknown.
+    pub fn plus_code(&self) -> Result<String> {
+        let lengths = [2, 4, 6, 8, 10, 11, 12, 13, 14, 15];
+        self.encode(&lengths, DEFAULT_PLUS_CODE_LENGTH, |coordinates, length| {
+            let code = coordinates.to_plus_code(length)?;
+            Ok((Area::from_plus_code(&code)?, code))
+        })
+    }
+
+    /// Encodes the location as a Maidenhead locator whose cells are no larger than
+    /// the horizontal accuracy, or of 6 characters if it is unknown.
+    pub fn maidenhead(&self) -> Result<String> {
+        self.encode(
+            &MAIDENHEAD_LENGTHS,
+            DEFAULT_MAIDENHEAD_LENGTH,
+            |coordinates, length| {
+                let locator = coordinates.to_maidenhead(length)?;
+                Ok((Area::from_maidenhead(&locator)?, locator))
+            },
+        )
+    }
+
+    /// Encodes the location with the shortest of `lengths` whose cell fits within
+    /// the accuracy, or the longest one if none does.
+    fn encode<F>(&self, lengths: &[usize], default: usize, encode: F) -> Result<String>
+    where
+        F: Fn(Coordinates, usize) -> Result<(Area, String)>,
+    {
+        let Some(accuracy) = self
+            .horizontal_accuracy
+            .filter(|a| a.is_finite() && *a > 0.0)
+        else {
+            return encode(self.coordinates, default).map(|(_, code)| code);
+        };
+        let mut code = String::new();
+        for &length in lengths {
+            let area;
+            (area, code) = encode(self.coordinates, length)?;
+            if area.fits(accuracy) {
+                break;
+            }
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn geohash() {
+        let cases = [
+            (42.6, -5.6, "ezs42"),
+            (57.64911, 10.40744, "u4pruydqqvj"),
+            (47.36559, 8.524997, "u0qj3yxsw"),
+            (-25.382708, -49.265506, "6gkzwgjzn820"),
+            (0.0, 0.0, "s0000"),
+            (-90.0, -180.0, "00000"),
+            (89.99999, 179.99999, "zzzzz"),
+            // 180° is the same meridian as -180°.
+            (0.0, 180.0, "80000"),
+        ];
+        for (latitude, longitude, geohash) in cases {
+            let coordinates = at(latitude, longitude);
+            assert_eq!(coordinates.to_geohash(geohash.len()).unwrap(), geohash);
+            let area = Area::from_geohash(geohash).unwrap();
+            assert!(
+                area.contains(at(latitude, normalize_longitude(longitude))),
+                "{geohash} {area:?}"
+            );
+            // Round trip through the center of the cell.
+            assert_eq!(area.center().to_geohash(geohash.len()).unwrap(), geohash);
+        }
+        // Decoding ignores case.
+        assert_eq!(
+            Area::from_geohash("EZS42").unwrap(),
+            Area::from_geohash("ezs42").unwrap()
+        );
+        // Longitudes wrap around.
+        assert_eq!(
+            at(42.6, 354.4).to_geohash(5).unwrap(),
+            at(42.6, -5.6).to_geohash(5).unwrap()
+        );
+    }
+
+    #[test]
+    fn geohash_errors() {
+        for geohash in ["", "a", "ezs4i", "ezs4l", "ezs4o", "ez 42"] {
+            let error = Area::from_geohash(geohash).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Parse, "{geohash:?}");
+        }
+        for (coordinates, length) in [
+            (at(91.0, 0.0), 5),
+            (at(f64::NAN, 0.0), 5),
+            (at(0.0, f64::INFINITY), 5),
+            (at(0.0, 0.0), 0),
+            (at(0.0, 0.0), 21),
+        ] {
+            let error = coordinates.to_geohash(length).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn geohash_neighbors_around_the_globe() {
+        assert_eq!(
+            geohash_neighbors("u0qj").unwrap(),
+            Neighbors {
+                north: Some("u0qn".into()),
+                north_east: Some("u0qq".into()),
+                east: "u0qm".into(),
+                south_east: Some("u0qk".into()),
+                south: Some("u0qh".into()),
+                south_west: Some("u0mu".into()),
+                west: "u0mv".into(),
+                north_west: Some("u0my".into()),
+            }
+        );
+        // At the north pole, across the antimeridian.
+        assert_eq!(
+            geohash_neighbors("b").unwrap(),
+            Neighbors {
+                north: None,
+                north_east: None,
+                east: "c".into(),
+                south_east: Some("9".into()),
+                south: Some("8".into()),
+                south_west: Some("x".into()),
+                west: "z".into(),
+                north_west: None,
+            }
+        );
+        // At the south pole, across the antimeridian.
+        let neighbors = geohash_neighbors("0").unwrap();
+        assert_eq!(neighbors.south, None);
+        assert_eq!(neighbors.south_west, None);
+        assert_eq!(neighbors.south_east, None);
+        assert_eq!(neighbors.west, "p");
+        assert_eq!(neighbors.north.as_deref(), Some("2"));
+        // Along the antimeridian at a finer scale.
+        let neighbors = geohash_neighbors("rzzz").unwrap();
+        assert_eq!(neighbors.east, "2pbp");
+        assert_eq!(neighbors.west, "rzzx");
+        assert_eq!(neighbors.north_east.as_deref(), Some("8000"));
+    }
+
+    #[test]
+    fn plus_code() {
+        let cases = [
+            (20.375, 2.775, 6, "7FG49Q00+"),
+            (20.3700625, 2.7821875, 10, "7FG49QCJ+2V"),
+            (20.3701125, 2.782234375, 11, "7FG49QCJ+2VX"),
+            (20.3701135, 2.78223535156, 13, "7FG49QCJ+2VXGJ"),
+            (47.0000625, 8.0000625, 10, "8FVC2222+22"),
+            (-41.2730625, 174.7859375, 10, "4VCPPQGP+Q9"),
+            (0.5, -179.5, 4, "62G20000+"),
+            (-89.5, -179.5, 4, "22220000+"),
+            (20.5, 2.5, 4, "7FG40000+"),
+            (-89.9999375, -179.9999375, 10, "22222222+22"),
+            (0.5, 179.5, 4, "6VGX0000+"),
+            (1.0, 1.0, 11, "6FH32222+222"),
+            // Latitudes are clipped to the poles, and longitudes wrap around.
+            (90.0, 1.0, 4, "CFX30000+"),
+            (90.0, 1.0, 10, "CFX3X2X2+X2"),
+            (1.0, 180.0, 4, "62H20000+"),
+            (1.0, 181.0, 4, "62H30000+"),
+        ];
+        for (latitude, longitude, length, code) in cases {
+            assert_eq!(
+                at(latitude, longitude).to_plus_code(length).unwrap(),
+                code,
+                "{latitude}, {longitude}"
+            );
+            let area = Area::from_plus_code(code).unwrap();
+            assert_eq!(area.center().to_plus_code(length).unwrap(), code);
+        }
+        assert_eq!(
+            Area::from_plus_code("7fg49qcj+2v").unwrap(),
+            Area::from_plus_code("7FG49QCJ+2V").unwrap()
+        );
+    }
+
+    #[test]
+    fn plus_codes_at_cell_edges() {
+        // A cell includes its southern and western edges, and the points just
+        // inside its northern and eastern ones.
+        let area = Area::from_plus_code("8FVC9G8F+").unwrap();
+        let epsilon = 1e-9;
+        for coordinates in [
+            at(area.south, area.west),
+            at(area.south, area.east - epsilon),
+            at(area.north - epsilon, area.west),
+            at(area.north - epsilon, area.east - epsilon),
+        ] {
+            assert_eq!(coordinates.to_plus_code(8).unwrap(), "8FVC9G8F+");
+            let full = coordinates.to_plus_code(10).unwrap();
+            assert!(full.starts_with("8FVC9G8F+"), "{full}");
+            assert!(Area::from_plus_code(&full).unwrap().contains(coordinates));
+        }
+        // The edges themselves belong to the next cells.
+        assert_eq!(
+            at(area.north, area.west).to_plus_code(8).unwrap(),
+            "8FVC9G9F+"
+        );
+        assert_eq!(
+            at(area.south, area.east).to_plus_code(8).unwrap(),
+            "8FVC9G8G+"
+        );
+    }
+
+    #[test]
+    fn plus_code_errors() {
+        let invalid = [
+            "",
+            "+",
+            "8FVC9G8F",
+            "8FVC9G8F6X",
+            "8FVC9G8F+6X+",
+            "8FVC9G8F+6",
+            "8FVC9G8+6X",
+            "8FVC9G8F1+6X",
+            "8FVC9G8F+6XA",
+            "8FVC0000+6X",
+            "8FV00000+",
+            "80000000+",
+            "8FVC9G8F+6X2222222",
+        ];
+        for code in invalid {
+            let error = Area::from_plus_code(code).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Parse, "{code:?}");
+        }
+        // The first digits can't exceed 90° of latitude or 180° of longitude.
+        for code in ["F2222222+", "2X222222+", "9C3W9QCJ+"] {
+            assert_eq!(
+                Area::from_plus_code(code).is_ok(),
+                code == "9C3W9QCJ+",
+                "{code:?}"
+            );
+        }
+        // Short codes aren't full codes.
+        assert!(Area::from_plus_code("9G8F+6X").is_err());
+        for length in [0, 1, 3, 5, 7, 9, 16] {
+            let error = at(0.0, 0.0).to_plus_code(length).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn short_plus_code() {
+        // The full code, the reference, the short code, and whether the full code
+        // shortens to it rather than just being recovered from it.
+        let cases = [
+            ("9C3W9QCJ+2VX", 51.3701125, -1.217765625, "+2VX", true),
+            // The reference is too far to omit eight digits.
+            ("9C3W9QCJ+2VX", 51.3708675, -1.217765625, "CJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3693575, -1.217765625, "CJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3701125, -1.218520625, "CJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3701125, -1.217010625, "CJ+2VX", true),
+            // The reference is too far to omit six digits.
+            ("9C3W9QCJ+2VX", 51.3852125, -1.217765625, "9QCJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3550125, -1.217765625, "9QCJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3701125, -1.232865625, "9QCJ+2VX", true),
+            ("9C3W9QCJ+2VX", 51.3701125, -1.202665625, "9QCJ+2VX", true),
+            // The reference is in a neighboring cell.
+            ("8FJFW222+", 42.899, 9.012, "22+", false),
+            ("796RXG22+", 14.95125, -23.5001, "22+", false),
+            ("8FVC2GGG+GG", 46.976, 8.526, "2GGG+GG", true),
+            ("8FRCXGGG+GG", 47.026, 8.526, "XGGG+GG", true),
+            ("8FR9GXGG+GG", 46.526, 8.026, "GXGG+GG", true),
+            ("8FRCG2GG+GG", 46.526, 7.976, "G2GG+GG", true),
+            // Near the poles.
+            ("CFX22222+22", 89.6, 0.0, "2222+22", false),
+            ("2CXXXXXX+XX", -81.0, 0.0, "XXXXXX+XX", false),
+        ];
+        for (full, latitude, longitude, short, shortens) in cases {
+            let reference = at(latitude, longitude);
+            let recovered = Area::from_short_plus_code(short, reference).unwrap();
+            assert_eq!(recovered, Area::from_plus_code(full).unwrap(), "{short}");
+            if shortens {
+                let length = full.len() - 1;
+                assert_eq!(
+                    recovered
+                        .center()
+                        .to_short_plus_code(length, reference)
+                        .unwrap(),
+                    short,
+                    "{full}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn short_plus_codes_omit_whole_pairs() {
+        let coordinates = Area::from_plus_code("9C3W9QCJ+2VX").unwrap().center();
+        // 1.5° away pins down the first pair, but not the second, and removing the
+        // first pair on its own isn't allowed.
+        let reference = at(coordinates.latitude + 1.5, coordinates.longitude);
+        assert_eq!(
+            coordinates.to_short_plus_code(11, reference).unwrap(),
+            "9C3W9QCJ+2VX"
+        );
+        // Full codes are decoded as is.
+        assert_eq!(
+            Area::from_short_plus_code("9C3W9QCJ+2VX", reference).unwrap(),
+            Area::from_plus_code("9C3W9QCJ+2VX").unwrap()
+        );
+        let error = coordinates.to_short_plus_code(8, reference).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::OutOfRange);
+    }
+
+    #[test]
+    fn maidenhead() {
+        let cases = [
+            (41.714775, -72.72726, "FN31pr"),
+            (37.413708, -122.1073236, "CM87wj"),
+            (48.14666, 11.60833, "JN58td"),
+            (-34.91, -56.21166, "GF15vc"),
+            (38.92, -77.065, "FM18lw"),
+            (-41.2833, 174.745, "RE78ir"),
+            (89.99, 179.99, "RR99xx"),
+            (90.0, 180.0, "AR09ax"),
+            (-90.0, -180.0, "AA00aa"),
+        ];
+        for (latitude, longitude, locator) in cases {
+            let coordinates = at(latitude, longitude);
+            assert_eq!(coordinates.to_maidenhead(6).unwrap(), locator);
+            let area = Area::from_maidenhead(locator).unwrap();
+            assert_eq!(area.center().to_maidenhead(6).unwrap(), locator);
+        }
+        assert_eq!(at(48.14666, 11.60833).to_maidenhead(2).unwrap(), "JN");
+        assert_eq!(at(48.14666, 11.60833).to_maidenhead(8).unwrap(), "JN58td25");
+        assert_eq!(
+            Area::from_maidenhead("jn58TD").unwrap(),
+            Area::from_maidenhead("JN58td").unwrap()
+        );
+    }
+
+    #[test]
+    fn maidenhead_errors() {
+        let invalid = [
+            "",
+            "J",
+            "JN5",
+            "JN58t",
+            "JN58td2",
+            "JN58td255",
+            "SA",
+            "AS",
+            "J5",
+            "JNA8",
+            "JN5A",
+            "JN58yd",
+            "JN58ty",
+            "JN58tdA2",
+            "JN58td2A",
+            "JN58td\u{e9}",
+            "JN 8td",
+        ];
+        for locator in invalid {
+            let error = Area::from_maidenhead(locator).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Parse, "{locator:?}");
+        }
+        for length in [0, 1, 3, 10] {
+            let error = at(0.0, 0.0).to_maidenhead(length).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn precision_from_accuracy() {
+        let location = |accuracy| {
+            crate::LocationBuilder::new(at(47.36559, 8.524997))
+                .horizontal_accuracy(accuracy)
+                .build()
+                .snapshot()
+                .unwrap()
+        };
+        assert_eq!(location(5.0).geohash().unwrap(), "u0qj3yxsw");
+        assert_eq!(location(5000.0).geohash().unwrap(), "u0qj3");
+        assert_eq!(location(14.0).plus_code().unwrap(), "8FVC9G8F+6X");
+        assert_eq!(location(10_000.0).maidenhead().unwrap(), "JN47gi");
+    }
+}
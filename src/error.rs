@@ -1,8 +1,62 @@
+use std::{fmt, sync::Arc};
+
+use crate::RequestId;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// An error that can occur when fetching the location.
-#[derive(Copy, Clone, Debug)]
+///
+/// Every variant carries [`Details`]: the platform's error code, a message and the
+/// underlying error where available, the latter through
+/// [`std::error::Error::source`]. Errors are matched on their variant, e.g.
+/// `Error::AuthorizationDenied(_)`, or on their [kind](Self::kind).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum Error {
+    /// An error occured with the Android Java environment.
+    AndroidEnvironment(Details),
+    /// The user denied authorization.
+    AuthorizationDenied(Details),
+    /// A network error occured.
+    Network(Details),
+    /// The function was not called from the main thread.
+    NotMainThread(Details),
+    /// Location data is temporarily unavailable.
+    TemporarilyUnavailable(Details),
+    /// This device does not support location data.
+    PermanentlyUnavailable(Details),
+    /// No location was delivered before the deadline passed.
+    Timeout(Details),
+    /// The request was cancelled before it completed.
+    Cancelled(Details),
+    /// The service providing location data, such as a daemon, isn't running or
+    /// can't be reached.
+    ServiceUnavailable(Details),
+    /// The manager or backend was given an invalid configuration.
+    InvalidConfiguration(Details),
+    /// Location services are turned off in the system settings.
+    ProviderDisabled(Details),
+    /// Location data could not be parsed.
+    Parse(Details),
+    /// A value was outside of the range it must be in, e.g. a latitude beyond
+    /// ±90°.
+    OutOfRange(Details),
+    /// An unknown error occured.
+    Unknown(Details),
+}
+
+/// The details carried by an [`Error`].
+#[derive(Clone, Debug, Default)]
+pub struct Details {
+    code: Option<i64>,
+    message: Option<String>,
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+    request: Option<RequestId>,
+}
+
+/// The kind of an [`Error`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
     /// An error occured with the Android Java environment.
     AndroidEnvironment,
     /// The user denied authorization.
@@ -15,6 +69,236 @@ pub enum Error {
     TemporarilyUnavailable,
     /// This device does not support location data.
     PermanentlyUnavailable,
+    /// No location was delivered before the deadline passed.
+    Timeout,
+    /// The request was cancelled before it completed.
+    Cancelled,
+    /// The service providing location data, such as a daemon, isn't running or
+    /// can't be reached.
+    ServiceUnavailable,
+    /// The manager or backend was given an invalid configuration.
+    InvalidConfiguration,
+    /// Location services are turned off in the system settings.
+    ProviderDisabled,
+    /// Location data could not be parsed.
+    Parse,
+    /// A value was outside of the range it must be in, e.g. a latitude beyond
+    /// ±90°.
+    OutOfRange,
     /// An unknown error occured.
     Unknown,
 }
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        let details = Details::default();
+        match kind {
+            ErrorKind::AndroidEnvironment => Error::AndroidEnvironment(details),
+            ErrorKind::AuthorizationDenied => Error::AuthorizationDenied(details),
+            ErrorKind::Network => Error::Network(details),
+            ErrorKind::NotMainThread => Error::NotMainThread(details),
+            ErrorKind::TemporarilyUnavailable => Error::TemporarilyUnavailable(details),
+            ErrorKind::PermanentlyUnavailable => Error::PermanentlyUnavailable(details),
+            ErrorKind::Timeout => Error::Timeout(details),
+            ErrorKind::Cancelled => Error::Cancelled(details),
+            ErrorKind::ServiceUnavailable => Error::ServiceUnavailable(details),
+            ErrorKind::InvalidConfiguration => Error::InvalidConfiguration(details),
+            ErrorKind::ProviderDisabled => Error::ProviderDisabled(details),
+            ErrorKind::Parse => Error::Parse(details),
+            ErrorKind::OutOfRange => Error::OutOfRange(details),
+            ErrorKind::Unknown => Error::Unknown(details),
+        }
+    }
+
+    /// Sets the platform's error code, e.g. an `HRESULT` or `NSError` code.
+    pub fn with_code(mut self, code: i64) -> Self {
+        self.details_mut().code = Some(code);
+        self
+    }
+
+    /// Sets a message describing the error in more detail.
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.details_mut().message = Some(message.into());
+        self
+    }
+
+    /// Sets the underlying error that caused this one.
+    pub fn with_source<E>(mut self, source: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.details_mut().source = Some(Arc::new(source));
+        self
+    }
+
+    pub(crate) fn with_request_id(mut self, id: RequestId) -> Self {
+        self.details_mut().request = Some(id);
+        self
+    }
+
+    /// Whether the error answers a pending request made with
+    /// [`Manager::update_once`](crate::Manager::update_once). Backends skip
+    /// malformed data and keep looking for a location.
+    pub(crate) fn ends_request(&self) -> bool {
+        self.kind() != ErrorKind::Parse
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::AndroidEnvironment(_) => ErrorKind::AndroidEnvironment,
+            Error::AuthorizationDenied(_) => ErrorKind::AuthorizationDenied,
+            Error::Network(_) => ErrorKind::Network,
+            Error::NotMainThread(_) => ErrorKind::NotMainThread,
+            Error::TemporarilyUnavailable(_) => ErrorKind::TemporarilyUnavailable,
+            Error::PermanentlyUnavailable(_) => ErrorKind::PermanentlyUnavailable,
+            Error::Timeout(_) => ErrorKind::Timeout,
+            Error::Cancelled(_) => ErrorKind::Cancelled,
+            Error::ServiceUnavailable(_) => ErrorKind::ServiceUnavailable,
+            Error::InvalidConfiguration(_) => ErrorKind::InvalidConfiguration,
+            Error::ProviderDisabled(_) => ErrorKind::ProviderDisabled,
+            Error::Parse(_) => ErrorKind::Parse,
+            Error::OutOfRange(_) => ErrorKind::OutOfRange,
+            Error::Unknown(_) => ErrorKind::Unknown,
+        }
+    }
+
+    /// The details carried by this error.
+    pub fn details(&self) -> &Details {
+        match self {
+            Error::AndroidEnvironment(details) => details,
+            Error::AuthorizationDenied(details) => details,
+            Error::Network(details) => details,
+            Error::NotMainThread(details) => details,
+            Error::TemporarilyUnavailable(details) => details,
+            Error::PermanentlyUnavailable(details) => details,
+            Error::Timeout(details) => details,
+            Error::Cancelled(details) => details,
+            Error::ServiceUnavailable(details) => details,
+            Error::InvalidConfiguration(details) => details,
+            Error::ProviderDisabled(details) => details,
+            Error::Parse(details) => details,
+            Error::OutOfRange(details) => details,
+            Error::Unknown(details) => details,
+        }
+    }
+
+    fn details_mut(&mut self) -> &mut Details {
+        match self {
+            Error::AndroidEnvironment(details) => details,
+            Error::AuthorizationDenied(details) => details,
+            Error::Network(details) => details,
+            Error::NotMainThread(details) => details,
+            Error::TemporarilyUnavailable(details) => details,
+            Error::PermanentlyUnavailable(details) => details,
+            Error::Timeout(details) => details,
+            Error::Cancelled(details) => details,
+            Error::ServiceUnavailable(details) => details,
+            Error::InvalidConfiguration(details) => details,
+            Error::ProviderDisabled(details) => details,
+            Error::Parse(details) => details,
+            Error::OutOfRange(details) => details,
+            Error::Unknown(details) => details,
+        }
+    }
+
+    /// The platform's error code, if any.
+    pub fn code(&self) -> Option<i64> {
+        self.details().code
+    }
+
+    /// A message describing the error in more detail, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.details().message.as_deref()
+    }
+
+    /// The ID of the request made with [`Manager::update_once`] that this error
+    /// answers, if any.
+    ///
+    /// [`Manager::update_once`]: crate::Manager::update_once
+    pub fn request_id(&self) -> Option<RequestId> {
+        self.details().request
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind)
+    }
+}
+
+impl PartialEq<ErrorKind> for Error {
+    fn eq(&self, kind: &ErrorKind) -> bool {
+        self.kind() == *kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details = self.details();
+        write!(f, "{}", self.kind())?;
+        match (&details.message, &details.source) {
+            (Some(message), _) => write!(f, ": {message}")?,
+            // The source usually describes the code itself.
+            (None, Some(source)) => return write!(f, ": {source}"),
+            (None, None) => {}
+        }
+        if let Some(code) = details.code {
+            write!(f, " (code {code})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.details()
+            .source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::AndroidEnvironment => "error in the Android Java environment",
+            ErrorKind::AuthorizationDenied => "authorization denied",
+            ErrorKind::Network => "network error",
+            ErrorKind::NotMainThread => "not called from the main thread",
+            ErrorKind::TemporarilyUnavailable => "location temporarily unavailable",
+            ErrorKind::PermanentlyUnavailable => "location not supported on this device",
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::ServiceUnavailable => "location service unavailable",
+            ErrorKind::InvalidConfiguration => "invalid configuration",
+            ErrorKind::ProviderDisabled => "location services disabled",
+            ErrorKind::Parse => "failed to parse location data",
+            ErrorKind::OutOfRange => "value out of range",
+            ErrorKind::Unknown => "unknown error",
+        })
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind as IoKind;
+
+        let kind = match e.kind() {
+            IoKind::NotFound => ErrorKind::PermanentlyUnavailable,
+            IoKind::ConnectionRefused => ErrorKind::ServiceUnavailable,
+            IoKind::PermissionDenied => ErrorKind::AuthorizationDenied,
+            IoKind::ConnectionReset
+            | IoKind::ConnectionAborted
+            | IoKind::BrokenPipe
+            | IoKind::UnexpectedEof => ErrorKind::TemporarilyUnavailable,
+            IoKind::TimedOut => ErrorKind::Timeout,
+            IoKind::InvalidData => ErrorKind::Parse,
+            _ => ErrorKind::Unknown,
+        };
+        let mut error = Error::new(kind);
+        if let Some(code) = e.raw_os_error() {
+            error = error.with_code(code.into());
+        }
+        error.with_source(e)
+    }
+}
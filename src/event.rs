@@ -0,0 +1,177 @@
+//! Ready-made handlers that deliver owned events to a closure or a channel, see
+//! [`Manager::from_fn`] and [`Manager::channel`].
+//!
+//! [`Manager::from_fn`]: crate::Manager::from_fn
+//! [`Manager::channel`]: crate::Manager::channel
+
+use std::sync::mpsc::{self, Receiver};
+
+use crate::{
+    validation::Rejection, AuthorizationStatus, Error, Handler, Location, LocationSnapshot,
+};
+
+/// An owned copy of a call to a [`Handler`].
+#[derive(Clone, Debug)]
+pub enum LocationEvent {
+    /// A location was delivered, see [`Handler::handle`].
+    Location(LocationSnapshot),
+    /// An error occured, see [`Handler::error`].
+    ///
+    /// This is also delivered if the coordinates of a location could not be read.
+    Error(Error),
+    /// The backend has no more updates to deliver, see [`Handler::finished`].
+    Finished,
+    /// The authorization status changed, see [`Handler::authorization_changed`].
+    AuthorizationChanged(AuthorizationStatus),
+    /// A location failed validation, see [`Handler::rejected`].
+    Rejected(LocationSnapshot, Rejection),
+}
+
+/// Returns a handler that sends every event to the returned receiver, dropping
+/// them once the receiver is gone.
+pub(crate) fn channel() -> (impl Handler, Receiver<LocationEvent>) {
+    let (sender, receiver) = mpsc::channel();
+    let handler = FnHandler(move |event| {
+        let _ = sender.send(event);
+    });
+    (handler, receiver)
+}
+
+/// A handler that passes every event to a closure.
+pub(crate) struct FnHandler<F>(pub(crate) F);
+
+impl<F> Handler for FnHandler<F>
+where
+    F: Fn(LocationEvent) + 'static + Send + Sync,
+{
+    fn handle(&self, location: Location<'_>) {
+        (self.0)(match location.snapshot() {
+            Ok(location) => LocationEvent::Location(location),
+            Err(e) => LocationEvent::Error(e),
+        });
+    }
+
+    fn error(&self, error: Error) {
+        (self.0)(LocationEvent::Error(error));
+    }
+
+    fn finished(&self) {
+        (self.0)(LocationEvent::Finished);
+    }
+
+    fn authorization_changed(&self, status: AuthorizationStatus) {
+        (self.0)(LocationEvent::AuthorizationChanged(status));
+    }
+
+    fn rejected(&self, location: LocationSnapshot, rejection: Rejection) {
+        (self.0)(LocationEvent::Rejected(location, rejection));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{Coordinates, ErrorKind, LocationBuilder};
+
+    fn location(latitude: f64) -> LocationBuilder {
+        LocationBuilder::new(Coordinates {
+            latitude,
+            longitude: 0.0,
+        })
+    }
+
+    #[test]
+    fn from_fn() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let events = events.clone();
+            FnHandler(move |event| events.lock().unwrap().push(event))
+        };
+
+        handler.handle(location(1.0).build());
+        handler.error(ErrorKind::Timeout.into());
+        handler.authorization_changed(AuthorizationStatus::Denied);
+        let rejected = location(0.0).build().snapshot().unwrap();
+        handler.rejected(rejected.clone(), Rejection::NullIsland);
+        handler.finished();
+
+        let events = std::mem::take(&mut *events.lock().unwrap());
+        assert!(
+            matches!(
+                &events[..],
+                [
+                    LocationEvent::Location(first),
+                    LocationEvent::Error(error),
+                    LocationEvent::AuthorizationChanged(AuthorizationStatus::Denied),
+                    LocationEvent::Rejected(location, Rejection::NullIsland),
+                    LocationEvent::Finished,
+                ] if first.coordinates.latitude == 1.0
+                    && error.kind() == ErrorKind::Timeout
+                    && *location == rejected
+            ),
+            "{events:?}"
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn channel() {
+        use crate::{mock::Mock, Access, Accuracy, Manager};
+
+        let mock = Mock::new();
+        let (handler, events) = super::channel();
+        let manager = Manager::with_backend(handler, mock.backend()).unwrap();
+
+        // Events are received in the order they were delivered.
+        for latitude in 1..=3 {
+            mock.push(location(latitude.into()).build());
+        }
+        mock.push_error(ErrorKind::TemporarilyUnavailable);
+        manager
+            .request_authorization(Access::Foreground, Accuracy::Approximate)
+            .unwrap();
+        mock.push(location(4.0).build());
+
+        let events: Vec<_> = events.try_iter().collect();
+        let latitudes: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                LocationEvent::Location(location) => Some(location.coordinates.latitude),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(latitudes, [1.0, 2.0, 3.0, 4.0]);
+        assert!(matches!(events[3], LocationEvent::Error(_)));
+        assert!(matches!(
+            events[4],
+            LocationEvent::AuthorizationChanged(AuthorizationStatus::ForegroundOnly(
+                Accuracy::Approximate
+            ))
+        ));
+        assert!(matches!(events[5], LocationEvent::Location(_)));
+        assert_eq!(events.len(), 6);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn dropped_receiver() {
+        use crate::{mock::Mock, Manager};
+
+        let mock = Mock::new();
+        let (handler, events) = super::channel();
+        let mut manager = Manager::with_backend(handler, mock.backend()).unwrap();
+        drop(events);
+
+        // Events are dropped, and the manager keeps working.
+        mock.push(location(1.0).build());
+        mock.push_error(ErrorKind::Unknown);
+        manager.start_updates().unwrap();
+        assert!(mock.is_updating());
+        manager.update_once().unwrap();
+        mock.push(location(2.0).build());
+        let last = manager.last_known_location().unwrap();
+        assert_eq!(last.location.coordinates.latitude, 2.0);
+    }
+}
@@ -0,0 +1,364 @@
+//! Smoothing of noisy fixes with a Kalman filter, which can be applied to all the
+//! locations delivered to a manager with [`Manager::set_filter_config`], or used
+//! on its own.
+//!
+//! The filter models the device as moving at a constant velocity, disturbed by
+//! random accelerations, in a plane tangent to the Earth at its last position.
+//! Fixes are weighted by their horizontal accuracy, so a precise fix moves the
+//! estimate more than a vague one.
+//!
+//! ```
+//! # use std::time::{Duration, SystemTime};
+//! # use robius_location::{filter::*, Coordinates, EarthModel, LocationBuilder};
+//! let start = Coordinates { latitude: 51.5, longitude: -0.1 };
+//! let mut filter = KalmanFilter::new(FilterConfig::default()).unwrap();
+//!
+//! // A walk due east at 1.5 m/s, with fixes scattered up to 20 m around it.
+//! let mut seed = 1u64;
+//! let mut noise = move || {
+//!     seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+//!     (seed >> 11) as f64 / (1u64 << 53) as f64 * 40.0 - 20.0
+//! };
+//! let (mut raw_error, mut filtered_error) = (0.0, 0.0);
+//! for second in 0..120 {
+//!     let truth = start.destination(90.0, 1.5 * second as f64, EarthModel::Wgs84);
+//!     let fix = truth
+//!         .destination(0.0, noise(), EarthModel::Wgs84)
+//!         .destination(90.0, noise(), EarthModel::Wgs84);
+//!     let location = LocationBuilder::new(fix)
+//!         .time(SystemTime::UNIX_EPOCH + Duration::from_secs(second))
+//!         .horizontal_accuracy(12.0)
+//!         .build()
+//!         .snapshot()
+//!         .unwrap();
+//!     let smoothed = filter.update(&location);
+//!     if second >= 20 {
+//!         raw_error += truth.distance_to(fix, EarthModel::Wgs84);
+//!         filtered_error += truth.distance_to(smoothed.coordinates, EarthModel::Wgs84);
+//!     }
+//! }
+//! assert!(filtered_error < raw_error / 2.0);
+//! ```
+//!
+//! [`Manager::set_filter_config`]: crate::Manager::set_filter_config
+
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    conversion::{Enu, LocalFrame},
+    Coordinates, Error, ErrorKind, LocationSnapshot, Result,
+};
+
+/// The uncertainty of the velocity of a new track whose fix has no speed, in
+/// meters per second.
+const INITIAL_SPEED_UNCERTAINTY: f64 = 30.0;
+
+/// The parameters of a [`KalmanFilter`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterConfig {
+    /// The standard deviation of the device's acceleration, in meters per second
+    /// squared. Lower values give smoother tracks that are slower to follow turns.
+    pub acceleration_noise: f64,
+    /// The time between fixes after which the filter restarts from the next fix
+    /// rather than predicting where the device went.
+    pub max_gap: Duration,
+    /// The horizontal accuracy, in meters, assumed for fixes that don't report
+    /// one.
+    pub default_accuracy: f64,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            acceleration_noise: 1.0,
+            max_gap: Duration::from_secs(30),
+            default_accuracy: 30.0,
+        }
+    }
+}
+
+/// A constant-velocity Kalman filter over a sequence of fixes.
+#[derive(Clone, Debug)]
+pub struct KalmanFilter {
+    config: FilterConfig,
+    track: Option<Track>,
+}
+
+/// The estimated state of the device.
+///
+/// Both axes share a covariance, as they have the same dynamics and fixes have
+/// the same uncertainty along either.
+#[derive(Clone, Debug)]
+struct Track {
+    position: Coordinates,
+    /// The east and north velocities, in meters per second.
+    velocity: [f64; 2],
+    /// The variance of the position, the covariance of position and velocity, and
+    /// the variance of the velocity, along each axis.
+    covariance: [f64; 3],
+    time: SystemTime,
+    /// Whether the velocity has been measured, either reported with a fix or
+    /// derived from two of them, rather than assumed to be zero.
+    velocity_known: bool,
+}
+
+impl KalmanFilter {
+    /// Creates a filter.
+    ///
+    /// Fails with [`ErrorKind::InvalidConfiguration`] if a parameter isn't
+    /// positive.
+    pub fn new(config: FilterConfig) -> Result<Self> {
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+        if !positive(config.acceleration_noise) || !positive(config.default_accuracy) {
+            return Err(Error::new(ErrorKind::InvalidConfiguration)
+                .with_message("filter noise parameters must be positive"));
+        }
+        Ok(Self {
+            config,
+            track: None,
+        })
+    }
+
+    pub fn config(&self) -> &FilterConfig {
+        &self.config
+    }
+
+    /// Forgets the track, so that the next fix is taken as is.
+    pub fn reset(&mut self) {
+        self.track = None;
+    }
+
+    /// Adds a fix to the track, returning the smoothed location.
+    ///
+    /// The coordinates, speed and bearing of the result are estimated, with their
+    /// accuracies updated accordingly. The speed and bearing are only estimated
+    /// once the track has a velocity, from the second fix or one that reports its
+    /// speed and bearing. Other fields are those of `location`. Fixes
+    /// without a time are assumed to be current, and fixes older than the previous
+    /// one are treated as simultaneous with it.
+    pub fn update(&mut self, location: &LocationSnapshot) -> LocationSnapshot {
+        let time = location.time.unwrap_or_else(SystemTime::now);
+        let accuracy = location
+            .horizontal_accuracy
+            .filter(|a| *a > 0.0 && a.is_finite())
+            .unwrap_or(self.config.default_accuracy);
+        // The velocity reported with the fix, if any, and its uncertainty.
+        let velocity = match (location.speed, location.bearing) {
+            (Some(speed), Some(bearing)) if speed.is_finite() && bearing.is_finite() => {
+                let (sin, cos) = bearing.to_radians().sin_cos();
+                let uncertainty = location
+                    .speed_accuracy
+                    .filter(|a| *a > 0.0 && a.is_finite())
+                    .unwrap_or(INITIAL_SPEED_UNCERTAINTY);
+                Some(([speed * sin, speed * cos], uncertainty))
+            }
+            _ => None,
+        };
+
+        let gap = self
+            .track
+            .as_ref()
+            .map(|track| time.duration_since(track.time).unwrap_or_default());
+        let track = match (self.track.take(), gap) {
+            (Some(track), Some(gap)) if gap <= self.config.max_gap => {
+                let mut track = self.predict(track, gap.as_secs_f64());
+                track.correct(location.coordinates, accuracy, velocity);
+                track.time = track.time.max(time);
+                track.velocity_known = true;
+                track
+            }
+            _ => Track {
+                position: location.coordinates,
+                velocity: velocity.map_or([0.0; 2], |(velocity, _)| velocity),
+                covariance: [
+                    accuracy * accuracy,
+                    0.0,
+                    velocity
+                        .map_or(INITIAL_SPEED_UNCERTAINTY, |(_, u)| u)
+                        .powi(2),
+                ],
+                time,
+                velocity_known: velocity.is_some(),
+            },
+        };
+
+        let mut smoothed = LocationSnapshot {
+            coordinates: track.position,
+            horizontal_accuracy: Some(track.covariance[0].sqrt()),
+            ..location.clone()
+        };
+        // The speed and bearing of the fix, if any, are kept until the track has a
+        // velocity.
+        if track.velocity_known {
+            let [east, north] = track.velocity;
+            let speed = east.hypot(north);
+            let speed_accuracy = track.covariance[2].sqrt();
+            smoothed.speed = Some(speed);
+            smoothed.speed_accuracy = Some(speed_accuracy);
+            // The direction of a velocity smaller than its uncertainty is
+            // meaningless, so the bearing of the fix is kept instead.
+            if speed > speed_accuracy {
+                smoothed.bearing = Some(east.atan2(north).to_degrees().rem_euclid(360.0));
+                smoothed.bearing_accuracy = Some((speed_accuracy / speed).asin().to_degrees());
+            }
+        }
+        self.track = Some(track);
+        smoothed
+    }
+
+    /// Moves the track forward by `dt` seconds.
+    fn predict(&self, mut track: Track, dt: f64) -> Track {
+        let frame = LocalFrame::new(track.position, 0.0);
+        let [east, north] = track.velocity;
+        track.position = frame
+            .to_coordinates(Enu {
+                east: east * dt,
+                north: north * dt,
+                up: 0.0,
+            })
+            .0;
+
+        // The process noise of accelerations that are constant between fixes.
+        let q = self.config.acceleration_noise.powi(2);
+        let [p00, p01, p11] = track.covariance;
+        track.covariance = [
+            p00 + 2.0 * dt * p01 + dt * dt * p11 + q * dt.powi(4) / 4.0,
+            p01 + dt * p11 + q * dt.powi(3) / 2.0,
+            p11 + q * dt * dt,
+        ];
+        track
+    }
+}
+
+impl Track {
+    /// Corrects the track with a fix at `coordinates`, and its velocity if known.
+    fn correct(
+        &mut self,
+        coordinates: Coordinates,
+        accuracy: f64,
+        velocity: Option<([f64; 2], f64)>,
+    ) {
+        // The track is centered on its position, so the fix is the innovation.
+        let frame = LocalFrame::new(self.position, 0.0);
+        let offset = frame.to_enu(coordinates, 0.0);
+        let mut position = [0.0; 2];
+        let measured = [offset.east, offset.north];
+
+        // The measurements are independent, so they are applied one after the
+        // other.
+        let [p00, p01, p11] = self.covariance;
+        let s = p00 + accuracy * accuracy;
+        for axis in 0..2 {
+            let innovation = measured[axis];
+            position[axis] += p00 / s * innovation;
+            self.velocity[axis] += p01 / s * innovation;
+        }
+        self.covariance = [
+            p00 - p00 * p00 / s,
+            p01 - p00 * p01 / s,
+            p11 - p01 * p01 / s,
+        ];
+
+        if let Some((measured, uncertainty)) = velocity {
+            let [p00, p01, p11] = self.covariance;
+            let s = p11 + uncertainty * uncertainty;
+            for axis in 0..2 {
+                let innovation = measured[axis] - self.velocity[axis];
+                position[axis] += p01 / s * innovation;
+                self.velocity[axis] += p11 / s * innovation;
+            }
+            self.covariance = [
+                p00 - p01 * p01 / s,
+                p01 - p01 * p11 / s,
+                p11 - p11 * p11 / s,
+            ];
+        }
+
+        self.position = frame
+            .to_coordinates(Enu {
+                east: position[0],
+                north: position[1],
+                up: 0.0,
+            })
+            .0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EarthModel, LocationBuilder};
+
+    const START: Coordinates = Coordinates {
+        latitude: 51.5,
+        longitude: -0.1,
+    };
+
+    fn fix(coordinates: Coordinates, secs: u64) -> LocationBuilder {
+        LocationBuilder::new(coordinates)
+            .time(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .horizontal_accuracy(10.0)
+    }
+
+    fn update(filter: &mut KalmanFilter, fix: LocationBuilder) -> LocationSnapshot {
+        filter.update(&fix.build().snapshot().unwrap())
+    }
+
+    #[test]
+    fn restarts_after_max_gap() {
+        let mut filter = KalmanFilter::new(FilterConfig::default()).unwrap();
+        for secs in 0..10 {
+            let position = START.destination(90.0, 2.0 * secs as f64, EarthModel::Wgs84);
+            update(&mut filter, fix(position, secs));
+        }
+
+        // Far from where the track predicts, but after a long gap.
+        let position = START.destination(0.0, 5_000.0, EarthModel::Wgs84);
+        let smoothed = update(&mut filter, fix(position, 9 + 31));
+        assert_eq!(smoothed.coordinates, position);
+        assert_eq!(smoothed.horizontal_accuracy, Some(10.0));
+        // The velocity of the old track is forgotten.
+        assert_eq!(smoothed.speed, None);
+        assert_eq!(smoothed.bearing, None);
+
+        // Within the gap, the fix is smoothed.
+        let mut filter = KalmanFilter::new(FilterConfig::default()).unwrap();
+        update(&mut filter, fix(START, 0));
+        let smoothed = update(&mut filter, fix(position, 30));
+        assert_ne!(smoothed.coordinates, position);
+    }
+
+    #[test]
+    fn keeps_the_bearing_of_a_slow_track() {
+        let mut filter = KalmanFilter::new(FilterConfig::default()).unwrap();
+        let smoothed = update(&mut filter, fix(START, 0));
+        // Nothing is known about the velocity of a single fix.
+        assert_eq!(smoothed.speed, None);
+        assert_eq!(smoothed.speed_accuracy, None);
+
+        // The track barely moves, so its speed is below its uncertainty and its
+        // direction is noise.
+        let position = START.destination(0.0, 0.5, EarthModel::Wgs84);
+        let smoothed = update(
+            &mut filter,
+            fix(position, 1).bearing(123.0).bearing_accuracy(5.0),
+        );
+        let (speed, speed_accuracy) = (smoothed.speed.unwrap(), smoothed.speed_accuracy.unwrap());
+        assert!(speed < speed_accuracy, "{speed} ≥ {speed_accuracy}");
+        assert_eq!(smoothed.bearing, Some(123.0));
+        assert_eq!(smoothed.bearing_accuracy, Some(5.0));
+    }
+
+    #[test]
+    fn estimates_the_bearing_of_a_fast_track() {
+        let mut filter = KalmanFilter::new(FilterConfig::default()).unwrap();
+        let mut smoothed = None;
+        for secs in 0..20 {
+            let position = START.destination(45.0, 10.0 * secs as f64, EarthModel::Wgs84);
+            smoothed = Some(update(&mut filter, fix(position, secs).bearing(270.0)));
+        }
+        let smoothed = smoothed.unwrap();
+        assert!((smoothed.speed.unwrap() - 10.0).abs() < 1.0);
+        assert!((smoothed.bearing.unwrap() - 45.0).abs() < 5.0);
+    }
+}
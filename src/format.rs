@@ -0,0 +1,799 @@
+//! Formatting and parsing of [`Coordinates`] as text, and [`geo` URIs] as defined
+//! by RFC 5870.
+//!
+//! [`Coordinates`] are displayed in decimal degrees, or in another [`Style`] with
+//! [`Coordinates::display`]. They can be parsed from most formats people write:
+//!
+//! ```
+//! # use robius_location::{format::Style, Coordinates};
+//! let coordinates: Coordinates = "48°51'29.6\"N 2°17'40.2\"E".parse().unwrap();
+//!
+//! assert_eq!(coordinates.to_string(), "48.858222, 2.294500");
+//! assert_eq!(format!("{:.2}", coordinates), "48.86, 2.29");
+//! assert_eq!(
+//!     coordinates.display(Style::DegreesDecimalMinutes).to_string(),
+//!     "48°51.493'N 2°17.670'E"
+//! );
+//! ```
+//!
+//! [`geo` URIs]: https://www.rfc-editor.org/rfc/rfc5870
+
+use std::{fmt, str::FromStr};
+
+use crate::{Coordinates, Error, ErrorKind, LocationSnapshot, Result};
+
+/// The largest number of decimals displayed, beyond which `f64` has no precision
+/// left.
+const MAX_PRECISION: usize = 9;
+
+impl Coordinates {
+    /// Checks that the latitude is between -90 and 90 and the longitude between
+    /// -180 and 180.
+    ///
+    /// Fails with [`ErrorKind::OutOfRange`] otherwise, including if either is NaN.
+    pub fn validate(self) -> Result<Coordinates> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("latitude {} is out of range", self.latitude)));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(Error::new(ErrorKind::OutOfRange)
+                .with_message(format!("longitude {} is out of range", self.longitude)));
+        }
+        Ok(self)
+    }
+
+    /// Returns a value that displays the coordinates in `style`.
+    pub fn display(self, style: Style) -> DisplayCoordinates {
+        DisplayCoordinates {
+            coordinates: self,
+            style,
+        }
+    }
+}
+
+/// The style coordinates are displayed in.
+///
+/// The precision of the formatter sets the number of decimals of the last
+/// component, which defaults to 6 for degrees, 3 for minutes and 1 for seconds.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Style {
+    /// Signed decimal degrees, e.g. `48.858222, 2.294500`.
+    #[default]
+    DecimalDegrees,
+    /// Degrees, minutes and seconds, e.g. `48°51'29.6"N 2°17'40.2"E`.
+    DegreesMinutesSeconds,
+    /// Degrees and decimal minutes, e.g. `48°51.493'N 2°17.670'E`.
+    DegreesDecimalMinutes,
+}
+
+/// Displays [`Coordinates`] in a [`Style`], see [`Coordinates::display`].
+#[derive(Copy, Clone, Debug)]
+pub struct DisplayCoordinates {
+    coordinates: Coordinates,
+    style: Style,
+}
+
+impl fmt::Display for DisplayCoordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Coordinates {
+            latitude,
+            longitude,
+        } = self.coordinates;
+        let (components, precision) = match self.style {
+            Style::DecimalDegrees => {
+                let precision = f.precision().unwrap_or(6);
+                return write!(f, "{latitude:.precision$}, {longitude:.precision$}");
+            }
+            Style::DegreesMinutesSeconds => (3, 1),
+            Style::DegreesDecimalMinutes => (2, 3),
+        };
+        let precision = f.precision().unwrap_or(precision).min(MAX_PRECISION);
+        write_sexagesimal(f, latitude, [b'N', b'S'], components, precision)?;
+        f.write_str(" ")?;
+        write_sexagesimal(f, longitude, [b'E', b'W'], components, precision)
+    }
+}
+
+/// Writes the absolute value of `degrees` split into `components` of degrees,
+/// minutes and seconds, followed by its hemisphere.
+fn write_sexagesimal(
+    f: &mut fmt::Formatter<'_>,
+    degrees: f64,
+    [positive, negative]: [u8; 2],
+    components: usize,
+    precision: usize,
+) -> fmt::Result {
+    if !degrees.is_finite() {
+        return write!(f, "{degrees}");
+    }
+    // Rounding the total avoids displaying 60 seconds or minutes.
+    let scale = 10u64.pow(precision as u32);
+    let total = (degrees.abs() * 60f64.powi(components as i32 - 1) * scale as f64).round() as u64;
+    let last = total % (60 * scale);
+    let mut whole = total / (60 * scale);
+    let hemisphere = if degrees < 0.0 && total != 0 {
+        negative
+    } else {
+        positive
+    };
+
+    let width = if precision == 0 { 2 } else { precision + 3 };
+    let last = last as f64 / scale as f64;
+    if components == 3 {
+        let minutes = whole % 60;
+        whole /= 60;
+        write!(f, "{whole}°{minutes:02}'{last:0width$.precision$}\"")?;
+    } else {
+        write!(f, "{whole}°{last:0width$.precision$}'")?;
+    }
+    write!(f, "{}", char::from(hemisphere))
+}
+
+impl fmt::Display for Coordinates {
+    /// Formats the coordinates in decimal degrees, e.g. `48.858222, 2.294500`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.display(Style::DecimalDegrees), f)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Token {
+    Number {
+        value: f64,
+        negative: bool,
+        /// Whether the number is marked as degrees (0), minutes (1) or seconds
+        /// (2).
+        unit: Option<usize>,
+    },
+    Hemisphere(u8),
+    Separator,
+}
+
+fn tokenize(s: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            ',' | ';' => {
+                chars.next();
+                tokens.push(Token::Separator);
+            }
+            'N' | 'n' | 'S' | 's' | 'E' | 'e' | 'W' | 'w' => {
+                chars.next();
+                tokens.push(Token::Hemisphere(c.to_ascii_uppercase() as u8));
+            }
+            '+' | '-' | '.' | '0'..='9' => {
+                let negative = c == '-';
+                if matches!(c, '+' | '-') {
+                    chars.next();
+                }
+                let mut number = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                    number.push(c);
+                    chars.next();
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                let unit = match chars.peek() {
+                    Some('°' | 'º' | '˚' | 'd' | 'D') => Some(0),
+                    Some('\'' | '′' | '’') => Some(1),
+                    Some('"' | '″' | '”') => Some(2),
+                    _ => None,
+                };
+                if unit.is_some() {
+                    chars.next();
+                }
+                // Two apostrophes mark seconds.
+                let unit = match unit {
+                    Some(1) if chars.next_if(|&c| matches!(c, '\'' | '′' | '’')).is_some() => {
+                        Some(2)
+                    }
+                    unit => unit,
+                };
+                tokens.push(Token::Number {
+                    value: number.parse().ok()?,
+                    negative,
+                    unit,
+                });
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+/// The numbers and hemisphere making up one of the two coordinates.
+#[derive(Default)]
+struct Part {
+    numbers: Vec<(f64, Option<usize>)>,
+    negative: bool,
+    hemisphere: Option<u8>,
+    /// Whether the hemisphere came before the numbers.
+    prefixed: bool,
+}
+
+impl Part {
+    fn degrees(&self) -> Option<f64> {
+        let mut degrees = 0.0;
+        let mut position = None;
+        for &(value, unit) in &self.numbers {
+            let next = unit.unwrap_or(position.map_or(0, |p| p + 1));
+            // Components must be degrees, minutes then seconds, and only degrees
+            // can exceed 60.
+            if position.map_or(next != 0, |p| next <= p) || next > 2 || (next > 0 && value >= 60.0)
+            {
+                return None;
+            }
+            degrees += value / 60f64.powi(next as i32);
+            position = Some(next);
+        }
+        match (self.hemisphere, self.negative) {
+            (Some(_), true) => None,
+            (Some(b'S' | b'W'), false) | (None, true) => Some(-degrees),
+            _ => Some(degrees),
+        }
+    }
+}
+
+/// Splits tokens into the latitude and longitude parts.
+fn split(tokens: Vec<Token>) -> Option<Vec<Part>> {
+    // Without any separator, unit or hemisphere, the numbers are split in half.
+    if tokens
+        .iter()
+        .all(|t| matches!(t, Token::Number { unit: None, .. }))
+    {
+        if ![2, 4, 6].contains(&tokens.len()) {
+            return None;
+        }
+        let half = tokens.len() / 2;
+        let tokens = tokens.split_at(half);
+        return [tokens.0, tokens.1]
+            .into_iter()
+            .map(|tokens| {
+                let mut part = Part::default();
+                for (i, token) in tokens.iter().enumerate() {
+                    let &Token::Number {
+                        value, negative, ..
+                    } = token
+                    else {
+                        unreachable!()
+                    };
+                    if i == 0 {
+                        part.negative = negative;
+                    } else if negative {
+                        return None;
+                    }
+                    part.numbers.push((value, None));
+                }
+                Some(part)
+            })
+            .collect();
+    }
+
+    let mut parts = Vec::new();
+    let mut part = Part::default();
+    for token in tokens {
+        match token {
+            Token::Separator => {
+                if !part.numbers.is_empty() {
+                    parts.push(std::mem::take(&mut part));
+                } else if part.hemisphere.is_some() {
+                    return None;
+                }
+            }
+            Token::Hemisphere(hemisphere) => {
+                if part.numbers.is_empty() {
+                    if part.hemisphere.is_some() {
+                        return None;
+                    }
+                } else if part.prefixed {
+                    // This is the prefix of the next part.
+                    parts.push(std::mem::take(&mut part));
+                } else {
+                    part.hemisphere = Some(hemisphere);
+                    parts.push(std::mem::take(&mut part));
+                    continue;
+                }
+                part.hemisphere = Some(hemisphere);
+                part.prefixed = true;
+            }
+            Token::Number {
+                value,
+                negative,
+                unit,
+            } => {
+                let last_unit = part.numbers.last().and_then(|(_, unit)| *unit);
+                let starts_part = !part.numbers.is_empty()
+                    && (unit == Some(0)
+                        || part.numbers.len() == 3
+                        || matches!((last_unit, unit), (Some(a), Some(b)) if b <= a));
+                if starts_part {
+                    parts.push(std::mem::take(&mut part));
+                }
+                if part.numbers.is_empty() {
+                    part.negative = negative;
+                } else if negative {
+                    return None;
+                }
+                part.numbers.push((value, unit));
+            }
+        }
+    }
+    if !part.numbers.is_empty() {
+        parts.push(part);
+    } else if part.hemisphere.is_some() {
+        return None;
+    }
+    Some(parts)
+}
+
+fn parse_coordinates(s: &str) -> Option<Coordinates> {
+    let parts = split(tokenize(s)?)?;
+    let [first, second] = parts.as_slice() else {
+        return None;
+    };
+    let is_latitude = |part: &Part| part.hemisphere.map(|h| matches!(h, b'N' | b'S'));
+    let (latitude, longitude) = match (is_latitude(first), is_latitude(second)) {
+        (Some(false), None | Some(true)) | (None, Some(true)) => (second, first),
+        (Some(a), Some(b)) if a == b => return None,
+        _ => (first, second),
+    };
+    Some(Coordinates {
+        latitude: latitude.degrees()?,
+        longitude: longitude.degrees()?,
+    })
+}
+
+impl FromStr for Coordinates {
+    type Err = Error;
+
+    /// Parses coordinates written in decimal degrees, degrees and decimal minutes,
+    /// or degrees, minutes and seconds, or as a `geo` URI.
+    ///
+    /// Latitude comes first unless hemispheres say otherwise. Hemispheres may be
+    /// written before or after each coordinate, or replaced by a sign, and the
+    /// components may be marked with `°`, `'` and `"` or separated by spaces:
+    ///
+    /// ```
+    /// # use robius_location::Coordinates;
+    /// let eiffel_tower = Coordinates { latitude: 48.8582, longitude: 2.2945 };
+    /// for s in [
+    ///     "48.8582, 2.2945",
+    ///     "48.8582 2.2945",
+    ///     "48.8582°N 2.2945°E",
+    ///     "E 2.2945 N 48.8582",
+    ///     "48°51.492'N, 2°17.67'E",
+    ///     "48 51 29.52 N 2 17 40.2 E",
+    ///     "geo:48.8582,2.2945",
+    /// ] {
+    ///     let coordinates: Coordinates = s.parse().unwrap();
+    ///     assert!(coordinates.distance_to(eiffel_tower, Default::default()) < 0.01, "{s}");
+    /// }
+    /// ```
+    ///
+    /// Fails with [`ErrorKind::Parse`] if the text isn't recognized, and with
+    /// [`ErrorKind::OutOfRange`] if the coordinates are invalid, see
+    /// [`validate`](Coordinates::validate).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.get(..4)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("geo:"))
+        {
+            let uri: GeoUri = s.parse()?;
+            if uri.crs.as_deref().is_some_and(|crs| crs != WGS84) {
+                return Err(Error::new(ErrorKind::Parse)
+                    .with_message(format!("unsupported coordinate reference system in {s:?}")));
+            }
+            return Ok(uri.coordinates);
+        }
+        parse_coordinates(s)
+            .ok_or_else(|| {
+                Error::new(ErrorKind::Parse).with_message(format!("invalid coordinates {s:?}"))
+            })?
+            .validate()
+    }
+}
+
+/// The label of the default coordinate reference system of `geo` URIs.
+const WGS84: &str = "wgs84";
+
+/// A [`geo` URI] as defined by RFC 5870, e.g. `geo:48.198634,16.371648;u=40`.
+///
+/// ```
+/// # use robius_location::format::GeoUri;
+/// let uri: GeoUri = "geo:48.2010,16.3695,183;crs=wgs84;u=40".parse().unwrap();
+/// assert_eq!(uri.coordinates.latitude, 48.201);
+/// assert_eq!(uri.altitude, Some(183.0));
+/// assert_eq!(uri.uncertainty, Some(40.0));
+/// assert_eq!(uri.to_string(), "geo:48.201,16.3695,183;crs=wgs84;u=40");
+/// ```
+///
+/// [`geo` URI]: https://www.rfc-editor.org/rfc/rfc5870
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoUri {
+    pub coordinates: Coordinates,
+    /// The altitude, measured in meters.
+    pub altitude: Option<f64>,
+    /// The coordinate reference system, in lowercase. `None` and `wgs84` both mean
+    /// WGS-84; the coordinates are in another system otherwise.
+    pub crs: Option<String>,
+    /// The radius of uncertainty of the location, measured in meters.
+    pub uncertainty: Option<f64>,
+    /// Other parameters, with names in lowercase and values as written, including
+    /// percent-encoding.
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+impl From<Coordinates> for GeoUri {
+    fn from(coordinates: Coordinates) -> Self {
+        Self {
+            coordinates,
+            altitude: None,
+            crs: None,
+            uncertainty: None,
+            parameters: Vec::new(),
+        }
+    }
+}
+
+impl From<&LocationSnapshot> for GeoUri {
+    fn from(location: &LocationSnapshot) -> Self {
+        Self {
+            altitude: location.altitude,
+            uncertainty: location.horizontal_accuracy,
+            ..location.coordinates.into()
+        }
+    }
+}
+
+impl fmt::Display for GeoUri {
+    /// Writes the URI with the fields as they are, so that a URI whose fields
+    /// weren't parsed may not be valid, e.g. if a coordinate is NaN. Check them
+    /// with [`Coordinates::validate`] first.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Coordinates {
+            latitude,
+            longitude,
+        } = self.coordinates;
+        write!(f, "geo:{latitude},{longitude}")?;
+        if let Some(altitude) = self.altitude {
+            write!(f, ",{altitude}")?;
+        }
+        if let Some(crs) = &self.crs {
+            write!(f, ";crs={crs}")?;
+        }
+        if let Some(uncertainty) = self.uncertainty {
+            write!(f, ";u={uncertainty}")?;
+        }
+        for (name, value) in &self.parameters {
+            write!(f, ";{name}")?;
+            if let Some(value) = value {
+                write!(f, "={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a number as allowed in `geo` URIs, without exponent or `+` sign.
+fn parse_number(s: &str, signed: bool) -> Option<f64> {
+    let digits = s.strip_prefix('-').filter(|_| signed).unwrap_or(s);
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+    (is_digits(integer) && is_digits(fraction))
+        .then(|| s.parse().ok())
+        .flatten()
+}
+
+fn is_label(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-')
+}
+
+/// Returns whether `s` is a valid parameter value, with well-formed
+/// percent-encoding.
+fn is_value(s: &str) -> bool {
+    let mut bytes = s.bytes();
+    while let Some(c) = bytes.next() {
+        let valid = match c {
+            b'%' => (0..2).all(|_| bytes.next().is_some_and(|c| c.is_ascii_hexdigit())),
+            c => c.is_ascii_alphanumeric() || b"[]:&+$-_.!~*'()".contains(&c),
+        };
+        if !valid {
+            return false;
+        }
+    }
+    !s.is_empty()
+}
+
+impl FromStr for GeoUri {
+    type Err = Error;
+
+    /// Parses a `geo` URI.
+    ///
+    /// Fails with [`ErrorKind::Parse`] if the URI isn't valid, and with
+    /// [`ErrorKind::OutOfRange`] if its WGS-84 coordinates are invalid.
+    fn from_str(s: &str) -> Result<Self> {
+        let error = || Error::new(ErrorKind::Parse).with_message(format!("invalid geo URI {s:?}"));
+        let rest = s
+            .get(..4)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("geo:"))
+            .map(|_| &s[4..])
+            .ok_or_else(error)?;
+        let mut segments = rest.split(';');
+
+        let numbers = segments
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|n| parse_number(n, true))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(error)?;
+        let mut uri = match numbers[..] {
+            [latitude, longitude] | [latitude, longitude, _] => GeoUri {
+                altitude: numbers.get(2).copied(),
+                ..Coordinates {
+                    latitude,
+                    longitude,
+                }
+                .into()
+            },
+            _ => return Err(error()),
+        };
+
+        for (i, segment) in segments.enumerate() {
+            let (name, value) = match segment.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (segment, None),
+            };
+            let name = name.to_ascii_lowercase();
+            match (name.as_str(), value) {
+                // The CRS comes first, followed by the uncertainty.
+                ("crs", Some(crs)) if i == 0 && is_label(crs) => {
+                    uri.crs = Some(crs.to_ascii_lowercase());
+                }
+                ("u", Some(u))
+                    if uri.uncertainty.is_none() && i == usize::from(uri.crs.is_some()) =>
+                {
+                    uri.uncertainty = Some(parse_number(u, false).ok_or_else(error)?);
+                }
+                ("crs" | "u", _) => return Err(error()),
+                (name, value) if is_label(name) && value.is_none_or(is_value) => {
+                    uri.parameters
+                        .push((name.to_string(), value.map(str::to_string)));
+                }
+                _ => return Err(error()),
+            }
+        }
+
+        if uri.crs.as_deref().is_none_or(|crs| crs == WGS84) {
+            uri.coordinates.validate()?;
+        }
+        Ok(uri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EIFFEL_TOWER: Coordinates = Coordinates {
+        latitude: 48.858222,
+        longitude: 2.2945,
+    };
+
+    fn at(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn display() {
+        let dms = |c: Coordinates| c.display(Style::DegreesMinutesSeconds).to_string();
+        let ddm = |c: Coordinates| c.display(Style::DegreesDecimalMinutes).to_string();
+        assert_eq!(EIFFEL_TOWER.to_string(), "48.858222, 2.294500");
+        assert_eq!(dms(EIFFEL_TOWER), "48°51'29.6\"N 2°17'40.2\"E");
+        assert_eq!(ddm(EIFFEL_TOWER), "48°51.493'N 2°17.670'E");
+        assert_eq!(dms(at(-33.8568, -151.2153)), "33°51'24.5\"S 151°12'55.1\"W");
+        assert_eq!(
+            format!("{:.0}", EIFFEL_TOWER.display(Style::DegreesMinutesSeconds)),
+            "48°51'30\"N 2°17'40\"E"
+        );
+        // The precision is capped where `f64` runs out of digits.
+        assert_eq!(
+            format!("{:.12}", at(0.5, 0.0).display(Style::DegreesDecimalMinutes)),
+            "0°30.000000000'N 0°00.000000000'E"
+        );
+    }
+
+    #[test]
+    fn display_carries_rounding() {
+        let dms = |c: Coordinates| c.display(Style::DegreesMinutesSeconds).to_string();
+        let ddm = |c: Coordinates| c.display(Style::DegreesDecimalMinutes).to_string();
+        // 10°59'59.99" rounds up to the next minute and degree.
+        let degrees = 10.0 + 59.0 / 60.0 + 59.99 / 3600.0;
+        assert_eq!(dms(at(degrees, -degrees)), "11°00'00.0\"N 11°00'00.0\"W");
+        // 10°20'59.99" only rounds up to the next minute.
+        let degrees = 10.0 + 20.0 / 60.0 + 59.99 / 3600.0;
+        assert_eq!(dms(at(degrees, 0.0)), "10°21'00.0\"N 0°00'00.0\"E");
+        assert_eq!(ddm(at(89.9999999, 179.9999999)), "90°00.000'N 180°00.000'E");
+        // Values that round to zero have no southern or western hemisphere.
+        assert_eq!(dms(at(-1e-9, -1e-9)), "0°00'00.0\"N 0°00'00.0\"E");
+    }
+
+    #[test]
+    fn parse() {
+        let cases = [
+            "48.858222, 2.2945",
+            "48.858222 2.2945",
+            "48.858222;2.2945",
+            "+48.858222, +2.2945",
+            "48.858222N 2.2945E",
+            "48.858222°N, 2.2945°E",
+            "48.858222 n 2.2945 e",
+            "N 48.858222 E 2.2945",
+            "N48.858222, E2.2945",
+            "2.2945E 48.858222N",
+            "E 2.2945 N 48.858222",
+            "48°51.49332'N 2°17.67'E",
+            "48d 51.49332' N, 2d 17.67' E",
+            "48 51.49332 2 17.67",
+            "48°51'29.5992\"N 2°17'40.2\"E",
+            "48º51′29.5992″N 2˚17’40.2”E",
+            "48°51'29.5992''N 2°17'40.2''E",
+            "48 51 29.5992 N 2 17 40.2 E",
+            "48 51 29.5992 2 17 40.2",
+            "geo:48.858222,2.2945",
+            "GEO:48.858222,2.2945;u=10",
+        ];
+        for s in cases {
+            let coordinates: Coordinates = s.parse().unwrap();
+            assert!(
+                coordinates.distance_to(EIFFEL_TOWER, Default::default()) < 0.01,
+                "{s}: {coordinates:?}"
+            );
+        }
+        let southern: Coordinates = "33°51'24.5\"S 151°12'55.1\"W".parse().unwrap();
+        assert!(southern.latitude < 0.0 && southern.longitude < 0.0);
+        let signed: Coordinates = "-33.8568, -151.2153".parse().unwrap();
+        assert_eq!(signed, at(-33.8568, -151.2153));
+        let separated: Coordinates = "-33 51 24.5, -151 12 55.1".parse().unwrap();
+        assert!((separated.latitude + 33.8568).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse_errors = [
+            "",
+            "48.858222",
+            "48.858222, 2.2945, 3",
+            "48.858222N 2.2945N",
+            "2.2945E 48.858222W",
+            "-48.858222N 2.2945E",
+            "N, 48.858222 2.2945",
+            "48.858222 2.2945 N",
+            "48°60'N 2°17'E",
+            "48°51'60\"N 2°17'40\"E",
+            "48'51° 2'17°",
+            "48 -51 2 17",
+            "48.858222, 2.2945 meters",
+            "48..8, 2.2",
+            "1 2 3 4 5",
+            "NaN, 0",
+            "inf, 0",
+            "geo:48.858222,2.2945;crs=utm",
+        ];
+        for s in parse_errors {
+            let error = s.parse::<Coordinates>().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Parse, "{s:?}");
+        }
+        for s in [
+            "91, 0",
+            "0, 181",
+            "90.0001N 0E",
+            "0 0 0 181 0 0",
+            "geo:91,0",
+        ] {
+            let error = s.parse::<Coordinates>().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange, "{s:?}");
+        }
+        for coordinates in [at(f64::NAN, 0.0), at(0.0, f64::NAN), at(-90.1, 0.0)] {
+            let error = coordinates.validate().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+        assert!(at(-90.0, 180.0).validate().is_ok());
+    }
+
+    #[test]
+    fn geo_uri() {
+        let uri: GeoUri = "geo:48.2010,16.3695,183;CRS=WGS84;u=40;foo=b%20r;flag"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            uri,
+            GeoUri {
+                coordinates: at(48.201, 16.3695),
+                altitude: Some(183.0),
+                crs: Some("wgs84".into()),
+                uncertainty: Some(40.0),
+                parameters: vec![("foo".into(), Some("b%20r".into())), ("flag".into(), None)],
+            }
+        );
+        assert_eq!(
+            uri.to_string(),
+            "geo:48.201,16.3695,183;crs=wgs84;u=40;foo=b%20r;flag"
+        );
+        let uri: GeoUri = "geo:-48.2,16.3;u=0.5".parse().unwrap();
+        assert_eq!((uri.crs, uri.uncertainty), (None, Some(0.5)));
+
+        // Coordinates in other systems aren't checked.
+        let uri: GeoUri = "geo:500000,4649776;crs=utm-33n".parse().unwrap();
+        assert_eq!(uri.coordinates, at(500000.0, 4649776.0));
+
+        let location = crate::LocationBuilder::new(EIFFEL_TOWER)
+            .altitude(35.0)
+            .horizontal_accuracy(12.5)
+            .build()
+            .snapshot()
+            .unwrap();
+        assert_eq!(
+            GeoUri::from(&location).to_string(),
+            "geo:48.858222,2.2945,35;u=12.5"
+        );
+        // Invalid fields are written as is.
+        assert_eq!(GeoUri::from(at(f64::NAN, 0.0)).to_string(), "geo:NaN,0");
+    }
+
+    #[test]
+    fn geo_uri_errors() {
+        let parse_errors = [
+            "",
+            "geo",
+            "geo:",
+            "geom:1,2",
+            "http:1,2",
+            "geo:1",
+            "geo:1,2,3,4",
+            "geo:1,,2",
+            "geo:+1,2",
+            "geo:1e1,2",
+            "geo:1.,2",
+            "geo:.5,2",
+            "geo:1,2;",
+            // The uncertainty must follow the CRS, which must come first.
+            "geo:1,2;u=3;crs=wgs84",
+            "geo:1,2;foo=bar;u=3",
+            "geo:1,2;foo=bar;crs=wgs84",
+            "geo:1,2;u=3;u=4",
+            "geo:1,2;crs=wgs84;crs=wgs84",
+            "geo:1,2;crs",
+            "geo:1,2;crs=",
+            "geo:1,2;crs=w_s",
+            "geo:1,2;u",
+            "geo:1,2;u=-3",
+            "geo:1,2;u=x",
+            "geo:1,2;f o=bar",
+            "geo:1,2;foo=",
+            "geo:1,2;foo=b r",
+            "geo:1,2;foo=%2",
+            "geo:1,2;foo=%zz",
+        ];
+        for s in parse_errors {
+            let error = s.parse::<GeoUri>().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Parse, "{s:?}");
+        }
+        for s in ["geo:91,0", "geo:0,-181", "geo:0,0;crs=wgs84;u=1;crs=x"] {
+            let kind = s.parse::<GeoUri>().unwrap_err().kind();
+            assert!(
+                matches!(kind, ErrorKind::OutOfRange | ErrorKind::Parse),
+                "{s:?}"
+            );
+        }
+        let error = "geo:91,0".parse::<GeoUri>().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::OutOfRange);
+    }
+}
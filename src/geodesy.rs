@@ -0,0 +1,547 @@
+//! Geodesic calculations on [`Coordinates`], on a spherical Earth or the WGS-84
+//! ellipsoid.
+//!
+//! The ellipsoidal calculations use [Vincenty's formulae], which are accurate to
+//! well under a millimeter. For nearly antipodal points, where Vincenty's inverse
+//! formula doesn't converge, the initial bearing is searched for as in
+//! [Karney's method] instead.
+//!
+//! [Vincenty's formulae]: https://en.wikipedia.org/wiki/Vincenty%27s_formulae
+//! [Karney's method]: https://doi.org/10.1007/s00190-012-0578-z
+
+use std::f64::consts::PI;
+
+use crate::Coordinates;
+
+/// The mean radius of the Earth, in meters, as defined by the IUGG.
+pub(crate) const MEAN_EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The semi-major axis of the WGS-84 ellipsoid, in meters.
+pub(crate) const WGS84_A: f64 = 6_378_137.0;
+/// The flattening of the WGS-84 ellipsoid.
+pub(crate) const WGS84_F: f64 = 1.0 / 298.257_223_563;
+/// The semi-minor axis of the WGS-84 ellipsoid, in meters.
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+
+/// Vincenty's formulae are iterated until the result changes by less than this,
+/// which is about 0.006 mm on the ground.
+const CONVERGENCE: f64 = 1e-12;
+const MAX_ITERATIONS: usize = 200;
+
+/// The shape of the Earth assumed by geodesic calculations.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EarthModel {
+    /// A sphere with the mean radius of the Earth.
+    ///
+    /// This is faster, but distances may be off by up to 0.5%.
+    Sphere,
+    /// The WGS-84 ellipsoid, which GNSS coordinates refer to.
+    #[default]
+    Wgs84,
+}
+
+impl Coordinates {
+    /// The length of the shortest path to `other`, in meters.
+    ///
+    /// On the sphere this is the haversine distance.
+    ///
+    /// ```
+    /// # use robius_location::{Coordinates, EarthModel};
+    /// // Flinders Peak to Buninyong, from Vincenty's worked example.
+    /// let flinders_peak = Coordinates { latitude: -37.9510334167, longitude: 144.4248678889 };
+    /// let buninyong = Coordinates { latitude: -37.6528211389, longitude: 143.9264955278 };
+    ///
+    /// let distance = flinders_peak.distance_to(buninyong, EarthModel::Wgs84);
+    /// assert!((distance - 54_972.271).abs() < 0.001);
+    ///
+    /// // Nearly antipodal points, from Karney's "Algorithms for geodesics".
+    /// let a = Coordinates { latitude: -30.0, longitude: 0.0 };
+    /// let b = Coordinates { latitude: 29.9, longitude: 179.8 };
+    /// let distance = a.distance_to(b, EarthModel::Wgs84);
+    /// assert!((distance - 19_989_832.827_610).abs() < 0.001);
+    /// ```
+    pub fn distance_to(self, other: Coordinates, model: EarthModel) -> f64 {
+        inverse(self, other, model).distance
+    }
+
+    /// The direction in which to set off towards `other` along the shortest path,
+    /// in degrees clockwise from due north.
+    ///
+    /// ```
+    /// # use robius_location::{Coordinates, EarthModel};
+    /// # let flinders_peak = Coordinates { latitude: -37.9510334167, longitude: 144.4248678889 };
+    /// # let buninyong = Coordinates { latitude: -37.6528211389, longitude: 143.9264955278 };
+    /// let bearing = flinders_peak.initial_bearing_to(buninyong, EarthModel::Wgs84);
+    /// assert!((bearing - (306.0 + 52.0 / 60.0 + 5.37 / 3600.0)).abs() < 0.01 / 3600.0);
+    /// ```
+    pub fn initial_bearing_to(self, other: Coordinates, model: EarthModel) -> f64 {
+        normalize_bearing(inverse(self, other, model).initial_bearing)
+    }
+
+    /// The direction of travel on arriving at `other` along the shortest path, in
+    /// degrees clockwise from due north.
+    ///
+    /// ```
+    /// # use robius_location::{Coordinates, EarthModel};
+    /// # let flinders_peak = Coordinates { latitude: -37.9510334167, longitude: 144.4248678889 };
+    /// # let buninyong = Coordinates { latitude: -37.6528211389, longitude: 143.9264955278 };
+    /// let bearing = flinders_peak.final_bearing_to(buninyong, EarthModel::Wgs84);
+    /// assert!((bearing - (307.0 + 10.0 / 60.0 + 25.07 / 3600.0)).abs() < 0.01 / 3600.0);
+    /// ```
+    pub fn final_bearing_to(self, other: Coordinates, model: EarthModel) -> f64 {
+        normalize_bearing(inverse(self, other, model).final_bearing)
+    }
+
+    /// The point reached by travelling `distance` meters along the shortest path
+    /// that sets off in the direction of `bearing`, in degrees clockwise from due
+    /// north.
+    ///
+    /// ```
+    /// # use robius_location::{Coordinates, EarthModel};
+    /// # let flinders_peak = Coordinates { latitude: -37.9510334167, longitude: 144.4248678889 };
+    /// # let buninyong = Coordinates { latitude: -37.6528211389, longitude: 143.9264955278 };
+    /// let bearing = 306.0 + 52.0 / 60.0 + 5.37 / 3600.0;
+    /// let end = flinders_peak.destination(bearing, 54_972.271, EarthModel::Wgs84);
+    /// assert!((end.latitude - buninyong.latitude).abs() < 1e-7);
+    /// assert!((end.longitude - buninyong.longitude).abs() < 1e-7);
+    /// ```
+    pub fn destination(self, bearing: f64, distance: f64, model: EarthModel) -> Coordinates {
+        let bearing = bearing.to_radians();
+        match model {
+            EarthModel::Sphere => spherical_direct(self, bearing, distance),
+            EarthModel::Wgs84 => vincenty_direct(self, bearing, distance),
+        }
+    }
+
+    /// The point halfway along the shortest path to `other`.
+    pub fn midpoint(self, other: Coordinates, model: EarthModel) -> Coordinates {
+        self.interpolate(other, 0.5, model)
+    }
+
+    /// The point at `fraction` of the way along the shortest path to `other`, where
+    /// 0 is `self` and 1 is `other`.
+    ///
+    /// Fractions outside of that range extrapolate along the same path.
+    pub fn interpolate(self, other: Coordinates, fraction: f64, model: EarthModel) -> Coordinates {
+        let inverse = inverse(self, other, model);
+        self.destination(
+            inverse.initial_bearing.to_degrees(),
+            inverse.distance * fraction,
+            model,
+        )
+    }
+}
+
+/// The solution of the inverse geodesic problem, with bearings in radians.
+struct Inverse {
+    distance: f64,
+    initial_bearing: f64,
+    final_bearing: f64,
+}
+
+fn inverse(a: Coordinates, b: Coordinates, model: EarthModel) -> Inverse {
+    match model {
+        EarthModel::Sphere => spherical_inverse(a, b),
+        EarthModel::Wgs84 => vincenty_inverse(a, b).unwrap_or_else(|| antipodal_inverse(a, b)),
+    }
+}
+
+fn spherical_inverse(a: Coordinates, b: Coordinates) -> Inverse {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+
+    let bearing = |lat_a: f64, lat_b: f64, d_lon: f64| {
+        f64::atan2(
+            d_lon.sin() * lat_b.cos(),
+            lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos(),
+        )
+    };
+    Inverse {
+        distance: 2.0 * MEAN_EARTH_RADIUS * h.sqrt().min(1.0).asin(),
+        initial_bearing: bearing(lat_a, lat_b, d_lon),
+        // The final bearing is the reverse of the initial bearing of the way back.
+        final_bearing: bearing(lat_b, lat_a, -d_lon) + PI,
+    }
+}
+
+fn spherical_direct(start: Coordinates, bearing: f64, distance: f64) -> Coordinates {
+    let lat = start.latitude.to_radians();
+    let angle = distance / MEAN_EARTH_RADIUS;
+
+    let end_lat = (lat.sin() * angle.cos() + lat.cos() * angle.sin() * bearing.cos()).asin();
+    let d_lon = f64::atan2(
+        bearing.sin() * angle.sin() * lat.cos(),
+        angle.cos() - lat.sin() * end_lat.sin(),
+    );
+    Coordinates {
+        latitude: end_lat.to_degrees(),
+        longitude: normalize_longitude(start.longitude + d_lon.to_degrees()),
+    }
+}
+
+/// Solves the inverse problem on the ellipsoid, returning `None` if the iteration
+/// doesn't converge, which happens for nearly antipodal points.
+fn vincenty_inverse(a: Coordinates, b: Coordinates) -> Option<Inverse> {
+    let l = normalize_longitude(b.longitude - a.longitude).to_radians();
+    let (sin_u1, cos_u1) = reduced_latitude(a.latitude).sin_cos();
+    let (sin_u2, cos_u2) = reduced_latitude(b.latitude).sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = (cos_u2 * sin_lambda).hypot(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda);
+        if sin_sigma == 0.0 {
+            // The points coincide.
+            return Some(Inverse {
+                distance: 0.0,
+                initial_bearing: 0.0,
+                final_bearing: 0.0,
+            });
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // Both points are on the equator if `cos2_alpha` is zero.
+        let cos_2sigma_m = if cos2_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+        } else {
+            0.0
+        };
+        let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+        let previous = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (2.0 * cos_2sigma_m.powi(2) - 1.0)));
+        if lambda.abs() > PI {
+            return None;
+        }
+        if (lambda - previous).abs() < CONVERGENCE {
+            let (a_coefficient, b_coefficient) = series_coefficients(cos2_alpha);
+            let delta_sigma = delta_sigma(b_coefficient, sin_sigma, cos_sigma, cos_2sigma_m);
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            return Some(Inverse {
+                distance: WGS84_B * a_coefficient * (sigma - delta_sigma),
+                initial_bearing: f64::atan2(
+                    cos_u2 * sin_lambda,
+                    cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda,
+                ),
+                final_bearing: f64::atan2(
+                    cos_u1 * sin_lambda,
+                    cos_u1 * sin_u2 * cos_lambda - sin_u1 * cos_u2,
+                ),
+            });
+        }
+    }
+    None
+}
+
+/// Solves the inverse problem on the ellipsoid for nearly antipodal points.
+///
+/// For each initial bearing, the geodesic reaches the latitude of `b` at some
+/// longitude, which increases with the bearing once the problem is reduced to
+/// `a` being in the southern hemisphere, at least as far from the equator as `b`,
+/// and west of it. The bearing that reaches the longitude of `b` is found by
+/// bisection.
+fn antipodal_inverse(a: Coordinates, b: Coordinates) -> Inverse {
+    let swap = a.latitude.abs() < b.latitude.abs();
+    let (a, b) = if swap { (b, a) } else { (a, b) };
+    let flip_latitude = a.latitude > 0.0;
+    // A zero latitude must be negative zero, so that bearings towards the south
+    // start out below the equator.
+    let latitude1 = -a.latitude.abs();
+    let latitude2 = if flip_latitude {
+        -b.latitude
+    } else {
+        b.latitude
+    };
+    let l = normalize_longitude(b.longitude - a.longitude).to_radians();
+    let flip_longitude = l < 0.0;
+    let l = l.abs();
+
+    let (sin_u1, cos_u1) = reduced_latitude(latitude1).sin_cos();
+    let (sin_u2, cos_u2) = reduced_latitude(latitude2).sin_cos();
+
+    // Follows the geodesic setting off at `alpha1` to its first crossing of the
+    // latitude of `b`, returning the longitude there and the geodesic's
+    // parameters.
+    let solve = |alpha1: f64| {
+        let (sin_alpha1, cos_alpha1) = alpha1.sin_cos();
+        let sin_alpha = sin_alpha1 * cos_u1;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // Clairaut's relation gives the bearing at the crossing.
+        let cos_alpha2_cos_u2 = ((cos_alpha1 * cos_u1).powi(2) + (cos_u2.powi(2) - cos_u1.powi(2)))
+            .max(0.0)
+            .sqrt();
+        let sigma1 = sin_u1.atan2(cos_alpha1 * cos_u1);
+        let sigma2 = sin_u2.atan2(cos_alpha2_cos_u2);
+        let omega1 = (sin_alpha * sin_u1).atan2(cos_alpha1 * cos_u1);
+        let omega2 = (sin_alpha * sin_u2).atan2(cos_alpha2_cos_u2);
+        let sigma = sigma2 - sigma1;
+        let cos_2sigma_m = (sigma1 + sigma2).cos();
+        let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+        let lambda = omega2
+            - omega1
+            - (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sigma.sin()
+                        * (cos_2sigma_m + c * sigma.cos() * (2.0 * cos_2sigma_m.powi(2) - 1.0)));
+        (
+            lambda,
+            sigma,
+            cos_2sigma_m,
+            cos2_alpha,
+            sin_alpha,
+            cos_alpha2_cos_u2,
+        )
+    };
+
+    let (mut low, mut high) = (0.0, PI);
+    for _ in 0..MAX_ITERATIONS {
+        let middle = (low + high) / 2.0;
+        if middle == low || middle == high {
+            break;
+        }
+        if solve(middle).0 < l {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let alpha1 = (low + high) / 2.0;
+    let (_, sigma, cos_2sigma_m, cos2_alpha, sin_alpha, cos_alpha2_cos_u2) = solve(alpha1);
+    let (a_coefficient, b_coefficient) = series_coefficients(cos2_alpha);
+    let distance = WGS84_B
+        * a_coefficient
+        * (sigma - delta_sigma(b_coefficient, sigma.sin(), sigma.cos(), cos_2sigma_m));
+    let alpha2 = sin_alpha.atan2(cos_alpha2_cos_u2);
+
+    // Undo the reduction of the problem.
+    let unreduce = |mut alpha: f64| {
+        if flip_longitude {
+            alpha = -alpha;
+        }
+        if flip_latitude {
+            alpha = PI - alpha;
+        }
+        alpha
+    };
+    let (initial_bearing, final_bearing) = (unreduce(alpha1), unreduce(alpha2));
+    if swap {
+        // Travelling the other way reverses both bearings.
+        Inverse {
+            distance,
+            initial_bearing: final_bearing + PI,
+            final_bearing: initial_bearing + PI,
+        }
+    } else {
+        Inverse {
+            distance,
+            initial_bearing,
+            final_bearing,
+        }
+    }
+}
+
+fn vincenty_direct(start: Coordinates, bearing: f64, distance: f64) -> Coordinates {
+    let (sin_alpha1, cos_alpha1) = bearing.sin_cos();
+    let u1 = reduced_latitude(start.latitude);
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let sigma1 = u1.tan().atan2(cos_alpha1);
+    let sin_alpha = cos_u1 * sin_alpha1;
+    let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+    let (a_coefficient, b_coefficient) = series_coefficients(cos2_alpha);
+
+    let first_sigma = distance / (WGS84_B * a_coefficient);
+    let mut sigma = first_sigma;
+    let mut cos_2sigma_m;
+    let mut iterations = 0;
+    loop {
+        cos_2sigma_m = (2.0 * sigma1 + sigma).cos();
+        let delta = delta_sigma(b_coefficient, sigma.sin(), sigma.cos(), cos_2sigma_m);
+        let previous = sigma;
+        sigma = first_sigma + delta;
+        iterations += 1;
+        if (sigma - previous).abs() < CONVERGENCE || iterations == MAX_ITERATIONS {
+            break;
+        }
+    }
+
+    let (sin_sigma, cos_sigma) = sigma.sin_cos();
+    let x = sin_u1 * sin_sigma - cos_u1 * cos_sigma * cos_alpha1;
+    let latitude = f64::atan2(
+        sin_u1 * cos_sigma + cos_u1 * sin_sigma * cos_alpha1,
+        (1.0 - WGS84_F) * sin_alpha.hypot(x),
+    );
+    let lambda = f64::atan2(
+        sin_sigma * sin_alpha1,
+        cos_u1 * cos_sigma - sin_u1 * sin_sigma * cos_alpha1,
+    );
+    let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+    let l = lambda
+        - (1.0 - c)
+            * WGS84_F
+            * sin_alpha
+            * (sigma
+                + c * sin_sigma
+                    * (cos_2sigma_m + c * cos_sigma * (2.0 * cos_2sigma_m.powi(2) - 1.0)));
+
+    Coordinates {
+        latitude: latitude.to_degrees(),
+        longitude: normalize_longitude(start.longitude + l.to_degrees()),
+    }
+}
+
+/// The latitude on the auxiliary sphere, in radians.
+fn reduced_latitude(latitude: f64) -> f64 {
+    ((1.0 - WGS84_F) * latitude.to_radians().tan()).atan()
+}
+
+/// Vincenty's `A` and `B` coefficients.
+fn series_coefficients(cos2_alpha: f64) -> (f64, f64) {
+    let u2 = cos2_alpha * (WGS84_A * WGS84_A - WGS84_B * WGS84_B) / (WGS84_B * WGS84_B);
+    let a = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+    let b = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+    (a, b)
+}
+
+fn delta_sigma(b: f64, sin_sigma: f64, cos_sigma: f64, cos_2sigma_m: f64) -> f64 {
+    let cos2_2sigma_m = cos_2sigma_m * cos_2sigma_m;
+    b * sin_sigma
+        * (cos_2sigma_m
+            + b / 4.0
+                * (cos_sigma * (2.0 * cos2_2sigma_m - 1.0)
+                    - b / 6.0
+                        * cos_2sigma_m
+                        * (4.0 * sin_sigma * sin_sigma - 3.0)
+                        * (4.0 * cos2_2sigma_m - 3.0)))
+}
+
+/// Converts a bearing in radians to degrees in `[0, 360)`.
+fn normalize_bearing(bearing: f64) -> f64 {
+    let degrees = bearing.to_degrees().rem_euclid(360.0);
+    // Rounding can push tiny negative values up to 360.
+    if degrees >= 360.0 {
+        0.0
+    } else {
+        degrees
+    }
+}
+
+/// Wraps a longitude in degrees into `[-180, 180]`.
+fn normalize_longitude(longitude: f64) -> f64 {
+    if (-180.0..=180.0).contains(&longitude) {
+        longitude
+    } else {
+        (longitude + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Geodesics on the WGS-84 ellipsoid, with their lengths in meters and initial
+    /// bearings where they are unique, from published solutions.
+    const CASES: &[(&str, Coordinates, Coordinates, f64, Option<f64>)] = &[
+        (
+            "short",
+            point(-37.9510334167, 144.4248678889),
+            point(-37.6528211389, 143.9264955278),
+            54_972.271,
+            Some(306.868_158_3),
+        ),
+        (
+            "long",
+            point(40.6, -73.8),
+            point(51.6, -0.5),
+            5_551_759.400_318_679,
+            Some(51.198_882_845_579_824),
+        ),
+        (
+            "equatorial",
+            point(0.0, 0.0),
+            point(0.0, 1.0),
+            111_319.490_793,
+            Some(90.0),
+        ),
+        (
+            "meridional",
+            point(0.0, 0.0),
+            point(90.0, 0.0),
+            10_001_965.729,
+            Some(0.0),
+        ),
+        (
+            "coincident",
+            point(51.5, -0.1),
+            point(51.5, -0.1),
+            0.0,
+            None,
+        ),
+        (
+            "nearly antipodal",
+            point(-41.32, 174.81),
+            point(40.96, -5.5),
+            19_959_679.267_353,
+            Some(161.067_669_986),
+        ),
+        (
+            "nearly antipodal on the equator",
+            point(0.0, 0.0),
+            point(0.5, 179.7),
+            19_944_127.42,
+            None,
+        ),
+    ];
+
+    #[test]
+    fn wgs84_inverse() {
+        for &(name, a, b, distance, bearing) in CASES {
+            let error = (a.distance_to(b, EarthModel::Wgs84) - distance).abs();
+            assert!(error < 0.01, "{name}: distance off by {error} m");
+            if let Some(bearing) = bearing {
+                let error = (a.initial_bearing_to(b, EarthModel::Wgs84) - bearing).abs();
+                assert!(error < 1e-6, "{name}: bearing off by {error}°");
+            }
+        }
+    }
+
+    #[test]
+    fn wgs84_direct() {
+        for &(name, a, b, distance, _) in CASES {
+            let bearing = a.initial_bearing_to(b, EarthModel::Wgs84);
+            let end = a.destination(bearing, distance, EarthModel::Wgs84);
+            let error = end.distance_to(b, EarthModel::Wgs84);
+            assert!(error < 0.01, "{name}: destination off by {error} m");
+        }
+    }
+
+    #[test]
+    fn sphere() {
+        let quarter = MEAN_EARTH_RADIUS * PI / 2.0;
+        for (a, b, distance) in [
+            (point(0.0, 0.0), point(0.0, 90.0), quarter),
+            (point(0.0, 0.0), point(90.0, 0.0), quarter),
+            (point(0.0, 0.0), point(0.0, 180.0), 2.0 * quarter),
+            (point(10.0, 20.0), point(10.0, 20.0), 0.0),
+        ] {
+            assert!((a.distance_to(b, EarthModel::Sphere) - distance).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,569 @@
+//! Geofencing on top of the location updates of a [`Manager`], which works the same
+//! on every platform.
+//!
+//! A [`Geofencer`] tracks whether the device is inside a set of [`Geofence`]s and
+//! reports [`GeofenceEvent`]s as locations are fed to it. It can be driven by a
+//! manager with [`Manager::monitor_geofences`], or by hand, e.g. with a synthetic
+//! track:
+//!
+//! ```
+//! # use robius_location::{geofence::*, *};
+//! # use std::time::{Duration, SystemTime};
+//! let home = Coordinates { latitude: 52.52, longitude: 13.405 };
+//! let mut geofencer = Geofencer::new();
+//! geofencer
+//!     .add(Geofence::circle("home", home, 100.0).dwell(Duration::from_secs(60)))
+//!     .unwrap();
+//!
+//! let start = SystemTime::UNIX_EPOCH;
+//! let fix = |latitude: f64, secs: u64| {
+//!     LocationBuilder::new(Coordinates { latitude, longitude: 13.405 })
+//!         .time(start + Duration::from_secs(secs))
+//!         .horizontal_accuracy(10.0)
+//!         .build()
+//!         .snapshot()
+//!         .unwrap()
+//! };
+//!
+//! assert!(geofencer.process(&fix(52.53, 0)).is_empty());
+//! assert!(matches!(geofencer.process(&fix(52.52, 10))[..], [GeofenceEvent::Enter { .. }]));
+//! assert!(matches!(geofencer.process(&fix(52.52, 70))[..], [GeofenceEvent::Dwell { .. }]));
+//! assert!(matches!(geofencer.process(&fix(52.53, 80))[..], [GeofenceEvent::Exit { .. }]));
+//! ```
+//!
+//! [`Manager`]: crate::Manager
+//! [`Manager::monitor_geofences`]: crate::Manager::monitor_geofences
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    geodesy::MEAN_EARTH_RADIUS, Coordinates, EarthModel, Error, ErrorKind, Handler, Location,
+    LocationSnapshot, Result, Subscription,
+};
+
+const METERS_PER_DEGREE: f64 = MEAN_EARTH_RADIUS * std::f64::consts::PI / 180.0;
+
+/// An area whose boundary crossings are reported by a [`Geofencer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Geofence {
+    id: String,
+    region: Region,
+    hysteresis: f64,
+    dwell: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Region {
+    Circle { center: Coordinates, radius: f64 },
+    Polygon(Vec<Coordinates>),
+}
+
+impl Geofence {
+    /// Creates a fence covering the points within `radius` meters of `center`.
+    pub fn circle(id: impl Into<String>, center: Coordinates, radius: f64) -> Self {
+        Self::new(id.into(), Region::Circle { center, radius })
+    }
+
+    /// Creates a fence covering the area enclosed by `vertices`, in order.
+    ///
+    /// The polygon is closed implicitly, and its edges are straight on a local map
+    /// projection, so it should span no more than a few tens of kilometers.
+    pub fn polygon(id: impl Into<String>, vertices: Vec<Coordinates>) -> Self {
+        Self::new(id.into(), Region::Polygon(vertices))
+    }
+
+    fn new(id: String, region: Region) -> Self {
+        Self {
+            id,
+            region,
+            hysteresis: 0.0,
+            dwell: None,
+        }
+    }
+
+    /// Sets how far past the boundary, in meters, a location must be to count as a
+    /// crossing. Defaults to zero.
+    ///
+    /// This keeps a device moving along the boundary from entering and exiting
+    /// repeatedly.
+    pub fn hysteresis(mut self, meters: f64) -> Self {
+        self.hysteresis = meters;
+        self
+    }
+
+    /// Reports a [`GeofenceEvent::Dwell`] once the device has stayed inside the
+    /// fence for `duration`.
+    pub fn dwell(mut self, duration: Duration) -> Self {
+        self.dwell = Some(duration);
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn validate(&self) -> Result<()> {
+        let valid = match &self.region {
+            Region::Circle { center, radius } => {
+                is_valid(*center) && radius.is_finite() && *radius > 0.0
+            }
+            Region::Polygon(vertices) => {
+                vertices.len() >= 3 && vertices.iter().copied().all(is_valid)
+            }
+        };
+        if !valid {
+            return Err(Error::new(ErrorKind::InvalidConfiguration)
+                .with_message(format!("invalid region for geofence {:?}", self.id)));
+        }
+        if !(self.hysteresis.is_finite() && self.hysteresis >= 0.0) {
+            return Err(Error::new(ErrorKind::InvalidConfiguration)
+                .with_message(format!("invalid hysteresis for geofence {:?}", self.id)));
+        }
+        Ok(())
+    }
+
+    /// The distance from the boundary to `point`, in meters, which is negative
+    /// inside the fence.
+    fn signed_distance(&self, point: Coordinates) -> f64 {
+        match &self.region {
+            Region::Circle { center, radius } => {
+                center.distance_to(point, EarthModel::Wgs84) - radius
+            }
+            Region::Polygon(vertices) => {
+                // Longitudes are unwrapped around the first vertex, so that the
+                // polygon stays in one piece if it crosses the antimeridian.
+                let reference = vertices[0].longitude;
+                let unwrap = |longitude: f64| (longitude - reference + 540.0) % 360.0 - 180.0;
+                let longitude = unwrap(point.longitude);
+
+                // Work on a flat projection centered on the point, so the point is
+                // at the origin.
+                let scale = point.latitude.to_radians().cos();
+                let projected: Vec<(f64, f64)> = vertices
+                    .iter()
+                    .map(|vertex| {
+                        let d_lon = unwrap(vertex.longitude) - longitude;
+                        (
+                            d_lon * scale * METERS_PER_DEGREE,
+                            (vertex.latitude - point.latitude) * METERS_PER_DEGREE,
+                        )
+                    })
+                    .collect();
+
+                let mut inside = false;
+                let mut nearest = f64::INFINITY;
+                for (i, &a) in projected.iter().enumerate() {
+                    let b = projected[(i + 1) % projected.len()];
+                    if (a.1 > 0.0) != (b.1 > 0.0) && a.0 + (b.0 - a.0) * -a.1 / (b.1 - a.1) > 0.0 {
+                        inside = !inside;
+                    }
+                    nearest = nearest.min(distance_to_segment(a, b));
+                }
+                if inside {
+                    -nearest
+                } else {
+                    nearest
+                }
+            }
+        }
+    }
+}
+
+/// The distance from the origin to the segment between `a` and `b`.
+fn distance_to_segment(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        ((-a.0 * dx - a.1 * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx).hypot(a.1 + t * dy)
+}
+
+fn is_valid(coordinates: Coordinates) -> bool {
+    (-90.0..=90.0).contains(&coordinates.latitude)
+        && (-180.0..=180.0).contains(&coordinates.longitude)
+}
+
+/// A boundary crossing reported by a [`Geofencer`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeofenceEvent {
+    /// The device entered the fence, or was inside it when first located.
+    Enter {
+        id: String,
+        location: LocationSnapshot,
+    },
+    /// The device left the fence.
+    Exit {
+        id: String,
+        location: LocationSnapshot,
+    },
+    /// The device has stayed inside the fence for its [dwell time](Geofence::dwell).
+    Dwell {
+        id: String,
+        location: LocationSnapshot,
+    },
+}
+
+impl GeofenceEvent {
+    /// The ID of the fence the event is about.
+    pub fn id(&self) -> &str {
+        match self {
+            GeofenceEvent::Enter { id, .. }
+            | GeofenceEvent::Exit { id, .. }
+            | GeofenceEvent::Dwell { id, .. } => id,
+        }
+    }
+
+    /// The location that triggered the event.
+    pub fn location(&self) -> &LocationSnapshot {
+        match self {
+            GeofenceEvent::Enter { location, .. }
+            | GeofenceEvent::Exit { location, .. }
+            | GeofenceEvent::Dwell { location, .. } => location,
+        }
+    }
+}
+
+/// A handler for the events of geofences monitored with
+/// [`Manager::monitor_geofences`](crate::Manager::monitor_geofences).
+pub trait GeofenceHandler: 'static + Send + Sync {
+    fn event(&self, event: GeofenceEvent);
+
+    /// Called when an error occurs while fetching the location.
+    fn error(&self, _error: Error) {}
+}
+
+/// Tracks whether the device is inside a set of geofences.
+///
+/// Locations are treated conservatively: a location only moves the device in or
+/// out of a fence if its whole accuracy circle, widened by the fence's
+/// [hysteresis](Geofence::hysteresis), is on one side of the boundary. Locations
+/// that straddle the boundary leave the state unchanged, so fences should be
+/// larger than the typical accuracy of the locations.
+#[derive(Default)]
+pub struct Geofencer {
+    fences: Vec<Tracked>,
+}
+
+struct Tracked {
+    fence: Geofence,
+    /// Whether the device is inside the fence, if known.
+    inside: Option<bool>,
+    /// When the device entered the fence.
+    entered: Option<SystemTime>,
+    dwelled: bool,
+}
+
+impl Geofencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a fence, replacing the one with the same ID if any.
+    ///
+    /// Fails with [`ErrorKind::InvalidConfiguration`] if the fence has invalid
+    /// coordinates, a non-positive radius, fewer than three vertices or a negative
+    /// hysteresis.
+    pub fn add(&mut self, fence: Geofence) -> Result<()> {
+        fence.validate()?;
+        self.remove(&fence.id);
+        self.fences.push(Tracked {
+            fence,
+            inside: None,
+            entered: None,
+            dwelled: false,
+        });
+        Ok(())
+    }
+
+    /// Removes the fence with the given ID, returning whether there was one.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let len = self.fences.len();
+        self.fences.retain(|tracked| tracked.fence.id != id);
+        self.fences.len() != len
+    }
+
+    /// Returns whether the device is inside the fence with the given ID, or `None`
+    /// if there is no such fence or no location has been decisive yet.
+    pub fn is_inside(&self, id: &str) -> Option<bool> {
+        self.fences
+            .iter()
+            .find(|tracked| tracked.fence.id == id)
+            .and_then(|tracked| tracked.inside)
+    }
+
+    /// Updates the state of every fence with a new location, returning the
+    /// resulting events.
+    ///
+    /// Dwell times are measured with the time of the locations, or the current time
+    /// for locations without one, and are only checked when a location is
+    /// processed.
+    pub fn process(&mut self, location: &LocationSnapshot) -> Vec<GeofenceEvent> {
+        let time = location.time.unwrap_or_else(SystemTime::now);
+        let accuracy = location
+            .horizontal_accuracy
+            .filter(|accuracy| accuracy.is_finite())
+            .unwrap_or(0.0);
+
+        let mut events = Vec::new();
+        for tracked in &mut self.fences {
+            let fence = &tracked.fence;
+            let margin = accuracy + fence.hysteresis;
+            let distance = fence.signed_distance(location.coordinates);
+            let inside = if distance <= -margin {
+                Some(true)
+            } else if distance >= margin {
+                Some(false)
+            } else {
+                None
+            };
+
+            let event = |event: fn(String, LocationSnapshot) -> GeofenceEvent| {
+                event(fence.id.clone(), location.clone())
+            };
+            match (tracked.inside, inside) {
+                (Some(false) | None, Some(true)) => {
+                    events.push(event(|id, location| GeofenceEvent::Enter { id, location }));
+                    tracked.inside = Some(true);
+                    tracked.entered = Some(time);
+                    tracked.dwelled = false;
+                }
+                (Some(true), Some(false)) => {
+                    events.push(event(|id, location| GeofenceEvent::Exit { id, location }));
+                    tracked.inside = Some(false);
+                    tracked.entered = None;
+                }
+                (None, Some(false)) => tracked.inside = Some(false),
+                _ => {}
+            }
+
+            if let (Some(dwell), Some(entered), false) =
+                (fence.dwell, tracked.entered, tracked.dwelled)
+            {
+                if time
+                    .duration_since(entered)
+                    .is_ok_and(|stayed| stayed >= dwell)
+                {
+                    events.push(event(|id, location| GeofenceEvent::Dwell { id, location }));
+                    tracked.dwelled = true;
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Geofences monitored with
+/// [`Manager::monitor_geofences`](crate::Manager::monitor_geofences).
+///
+/// Monitoring stops when the monitor is dropped.
+pub struct GeofenceMonitor {
+    geofencer: Arc<Mutex<Geofencer>>,
+    subscription: Subscription,
+}
+
+impl GeofenceMonitor {
+    pub(crate) fn new(geofencer: Arc<Mutex<Geofencer>>, subscription: Subscription) -> Self {
+        Self {
+            geofencer,
+            subscription,
+        }
+    }
+
+    /// Adds a fence, see [`Geofencer::add`].
+    pub fn add(&self, fence: Geofence) -> Result<()> {
+        self.lock()?.add(fence)
+    }
+
+    /// Removes the fence with the given ID, returning whether there was one.
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        Ok(self.lock()?.remove(id))
+    }
+
+    /// Returns whether the device is inside the fence with the given ID, see
+    /// [`Geofencer::is_inside`].
+    pub fn is_inside(&self, id: &str) -> Result<Option<bool>> {
+        Ok(self.lock()?.is_inside(id))
+    }
+
+    /// Stops monitoring the fences.
+    pub fn stop(self) -> Result<()> {
+        self.subscription.unsubscribe()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Geofencer>> {
+        self.geofencer
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))
+    }
+}
+
+/// Feeds the updates of a subscription to a geofencer.
+pub(crate) struct Monitor<T> {
+    geofencer: Arc<Mutex<Geofencer>>,
+    handler: T,
+}
+
+impl<T> Monitor<T> {
+    pub(crate) fn new(geofencer: Arc<Mutex<Geofencer>>, handler: T) -> Self {
+        Self { geofencer, handler }
+    }
+}
+
+impl<T> Handler for Monitor<T>
+where
+    T: GeofenceHandler,
+{
+    fn handle(&self, location: Location<'_>) {
+        let snapshot = match location.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(e) => return self.handler.error(e),
+        };
+        let Ok(mut geofencer) = self.geofencer.lock() else {
+            return;
+        };
+        let events = geofencer.process(&snapshot);
+        // The handler is called without holding the lock, so that it can change
+        // the fences.
+        drop(geofencer);
+        events
+            .into_iter()
+            .for_each(|event| self.handler.event(event));
+    }
+
+    fn error(&self, error: Error) {
+        self.handler.error(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocationBuilder;
+
+    const CENTER: Coordinates = Coordinates {
+        latitude: 52.52,
+        longitude: 13.405,
+    };
+
+    /// A fix `distance` meters north of `CENTER`, `secs` seconds into the track.
+    fn fix(distance: f64, accuracy: f64, secs: u64) -> LocationSnapshot {
+        at(
+            CENTER.destination(0.0, distance, EarthModel::Wgs84),
+            accuracy,
+            secs,
+        )
+    }
+
+    fn at(coordinates: Coordinates, accuracy: f64, secs: u64) -> LocationSnapshot {
+        LocationBuilder::new(coordinates)
+            .time(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .horizontal_accuracy(accuracy)
+            .build()
+            .snapshot()
+            .unwrap()
+    }
+
+    fn kinds(events: &[GeofenceEvent]) -> Vec<&'static str> {
+        events
+            .iter()
+            .map(|event| match event {
+                GeofenceEvent::Enter { .. } => "enter",
+                GeofenceEvent::Exit { .. } => "exit",
+                GeofenceEvent::Dwell { .. } => "dwell",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn straddling_fixes_dont_flap() {
+        let mut geofencer = Geofencer::new();
+        geofencer
+            .add(Geofence::circle("fence", CENTER, 100.0))
+            .unwrap();
+
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 0))), ["enter"]);
+        // The accuracy circle of every fix covers the boundary.
+        for (distance, secs) in [95.0, 105.0, 90.0, 110.0, 99.0, 101.0].into_iter().zip(1..) {
+            assert!(geofencer.process(&fix(distance, 20.0, secs)).is_empty());
+        }
+        assert_eq!(geofencer.is_inside("fence"), Some(true));
+        assert_eq!(kinds(&geofencer.process(&fix(130.0, 20.0, 10))), ["exit"]);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut geofencer = Geofencer::new();
+        geofencer
+            .add(Geofence::circle("fence", CENTER, 100.0).hysteresis(10.0))
+            .unwrap();
+
+        assert_eq!(kinds(&geofencer.process(&fix(50.0, 0.0, 0))), ["enter"]);
+        for (distance, secs) in [95.0, 105.0, 92.0, 108.0].into_iter().zip(1..) {
+            assert!(geofencer.process(&fix(distance, 0.0, secs)).is_empty());
+        }
+        assert_eq!(kinds(&geofencer.process(&fix(111.0, 0.0, 5))), ["exit"]);
+        assert!(geofencer.process(&fix(95.0, 0.0, 6)).is_empty());
+        assert_eq!(kinds(&geofencer.process(&fix(89.0, 0.0, 7))), ["enter"]);
+    }
+
+    #[test]
+    fn polygon_across_the_antimeridian() {
+        let corner = |latitude, longitude| Coordinates {
+            latitude,
+            longitude,
+        };
+        let mut geofencer = Geofencer::new();
+        geofencer
+            .add(Geofence::polygon(
+                "fence",
+                vec![
+                    corner(-0.1, 179.9),
+                    corner(-0.1, -179.9),
+                    corner(0.1, -179.9),
+                    corner(0.1, 179.9),
+                ],
+            ))
+            .unwrap();
+
+        for (longitude, inside) in [
+            (179.95, true),
+            (180.0, true),
+            (-180.0, true),
+            (-179.95, true),
+            (179.8, false),
+            (-179.8, false),
+            (0.0, false),
+        ] {
+            geofencer.process(&at(corner(0.0, longitude), 10.0, 0));
+            assert_eq!(geofencer.is_inside("fence"), Some(inside), "{longitude}");
+        }
+    }
+
+    #[test]
+    fn dwell_restarts_on_exit() {
+        let mut geofencer = Geofencer::new();
+        geofencer
+            .add(Geofence::circle("fence", CENTER, 100.0).dwell(Duration::from_secs(60)))
+            .unwrap();
+
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 0))), ["enter"]);
+        assert!(geofencer.process(&fix(0.0, 10.0, 50)).is_empty());
+        assert_eq!(kinds(&geofencer.process(&fix(200.0, 10.0, 55))), ["exit"]);
+        // A minute after the first entry, but not after the second.
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 58))), ["enter"]);
+        assert!(geofencer.process(&fix(0.0, 10.0, 100)).is_empty());
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 118))), ["dwell"]);
+        assert!(geofencer.process(&fix(0.0, 10.0, 200)).is_empty());
+
+        // Dwelling is reported again after leaving and coming back.
+        assert_eq!(kinds(&geofencer.process(&fix(200.0, 10.0, 210))), ["exit"]);
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 220))), ["enter"]);
+        assert_eq!(kinds(&geofencer.process(&fix(0.0, 10.0, 280))), ["dwell"]);
+    }
+}
@@ -15,13 +15,66 @@
 //! As specified in the [Android documentation][android-docs].
 //!
 //! [android-docs]: https://developer.android.com/develop/sensors-and-location/location/permissions
+//!
+//! ## Linux
+//!
+//! On Linux location data is provided by [GeoClue][geoclue] over the system D-Bus.
+//! GeoClue identifies applications by their desktop ID, which is taken from the name
+//! of the current executable, so a matching `.desktop` file should be installed.
+//!
+//! Inside a Flatpak or Snap sandbox the [location portal][portal] is used instead,
+//! which asks the user for permission when updates are first started.
+//!
+//! [geoclue]: https://www.freedesktop.org/software/geoclue/docs/
+//! [portal]: https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Location.html
 
+mod cache;
+pub mod conversion;
+mod current;
+mod dispatch;
+pub mod encoding;
 mod error;
+mod event;
+pub mod filter;
+pub mod format;
+mod geodesy;
+pub mod geofence;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod nmea;
+#[cfg(feature = "replay")]
+pub mod replay;
+mod request;
+#[cfg(feature = "async")]
+mod stream;
+mod subscription;
 mod sys;
+mod throttle;
+mod time;
+pub mod validation;
 
-use std::time::SystemTime;
+use std::{
+    future::Future,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    time::{Duration, Instant, SystemTime},
+};
 
-pub use crate::error::{Error, Result};
+pub use crate::cache::{CacheConfig, CachedLocation};
+pub use crate::error::{Details, Error, ErrorKind, Result};
+pub use crate::event::LocationEvent;
+pub use crate::geodesy::EarthModel;
+pub use crate::request::{RequestHandle, RequestId};
+#[cfg(feature = "async")]
+pub use crate::stream::{Overflow, Updates};
+pub use crate::subscription::Subscription;
+use crate::{
+    current::{Current, CurrentLocation, Request, Waiter},
+    dispatch::{Deferral, Dispatcher, Listener},
+    event::FnHandler,
+    geofence::{GeofenceHandler, GeofenceMonitor, Geofencer, Monitor},
+    subscription::Subscriber,
+};
 
 /// A manager for dealing with location data and handling location updates.
 ///
@@ -31,7 +84,45 @@ pub use crate::error::{Error, Result};
 /// even if `update_once` or `start_updates` are not called.
 /// When the manager is dropped, the handler is no longer guaranteed to receive updates.
 pub struct Manager {
-    inner: sys::Manager,
+    shared: Arc<Shared>,
+}
+
+// The manager and the handles created from it can be moved to other threads, e.g.
+// into async tasks, on every platform.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Manager>();
+    assert_send::<RequestHandle>();
+    assert_send::<Subscription>();
+    #[cfg(feature = "async")]
+    assert_send::<Updates>();
+};
+
+/// The parts of a manager that are shared with the streams and subscriptions
+/// created from it.
+struct Shared {
+    state: Mutex<State>,
+    dispatcher: Dispatcher,
+}
+
+struct State {
+    backend: ManagerInner,
+    /// The request continuous updates were started with by the user, if any.
+    request: Option<UpdateRequest>,
+    /// The number of update streams that have started updates.
+    streams: usize,
+    /// The requests of the active subscriptions, by ID.
+    subscriptions: Vec<(u64, UpdateRequest)>,
+    next_subscription: u64,
+    /// The request the backend is currently running continuous updates with.
+    running: Option<UpdateRequest>,
+    /// The single update that callers of `current_location` are waiting for.
+    current: Weak<Request>,
+}
+
+enum ManagerInner {
+    Sys(sys::Manager),
+    Custom(Box<dyn Backend>),
 }
 
 impl Manager {
@@ -42,41 +133,582 @@ impl Manager {
     where
         T: Handler,
     {
-        Ok(Manager {
-            inner: sys::Manager::new(handler)?,
-        })
+        let dispatcher = Dispatcher::new(handler);
+        let backend = ManagerInner::Sys(sys::Manager::new(dispatcher.clone())?);
+        Ok(Manager::from_parts(dispatcher, backend))
+    }
+
+    /// Creates a new location manager that passes every event to `f`.
+    ///
+    /// This **must** be called from the main thread due to platform restrictions.
+    pub fn from_fn<F>(f: F) -> Result<Self>
+    where
+        F: Fn(LocationEvent) + 'static + Send + Sync,
+    {
+        Manager::new(FnHandler(f))
+    }
+
+    /// Creates a new location manager that sends every event to the returned
+    /// receiver.
+    ///
+    /// Events are dropped once the receiver is dropped.
+    ///
+    /// This **must** be called from the main thread due to platform restrictions.
+    pub fn channel() -> Result<(Self, std::sync::mpsc::Receiver<LocationEvent>)> {
+        let (handler, receiver) = event::channel();
+        Ok((Manager::new(handler)?, receiver))
+    }
+
+    /// Like [`channel`](Self::channel), but returns a [`crossbeam_channel`]
+    /// receiver.
+    #[cfg(feature = "crossbeam")]
+    pub fn crossbeam_channel() -> Result<(Self, crossbeam_channel::Receiver<LocationEvent>)> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let manager = Manager::from_fn(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok((manager, receiver))
+    }
+
+    /// Like [`channel`](Self::channel), but returns a [`flume`] receiver.
+    #[cfg(feature = "flume")]
+    pub fn flume_channel() -> Result<(Self, flume::Receiver<LocationEvent>)> {
+        let (sender, receiver) = flume::unbounded();
+        let manager = Manager::from_fn(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok((manager, receiver))
+    }
+
+    /// Creates a new location manager that receives updates from a [gpsd] daemon
+    /// rather than GeoClue.
+    ///
+    /// `address` is either a `host:port` pair, such as gpsd's default of
+    /// `localhost:2947`, or the path of a Unix domain socket.
+    ///
+    /// [gpsd]: https://gpsd.io/
+    #[cfg(target_os = "linux")]
+    pub fn with_gpsd<T>(handler: T, address: &str) -> Result<Self>
+    where
+        T: Handler,
+    {
+        let dispatcher = Dispatcher::new(handler);
+        let backend = ManagerInner::Sys(sys::Manager::with_gpsd(dispatcher.clone(), address)?);
+        Ok(Manager::from_parts(dispatcher, backend))
+    }
+
+    /// Creates a new location manager that reads NMEA 0183 sentences from a serial
+    /// device, such as `/dev/ttyACM0`, or from a file or pipe.
+    ///
+    /// The device should already be configured (e.g. with `stty`) to deliver
+    /// sentences line by line.
+    #[cfg(target_os = "linux")]
+    pub fn with_nmea<T, P>(handler: T, path: P) -> Result<Self>
+    where
+        T: Handler,
+        P: AsRef<std::path::Path>,
+    {
+        let dispatcher = Dispatcher::new(handler);
+        let backend =
+            ManagerInner::Sys(sys::Manager::with_nmea(dispatcher.clone(), path.as_ref())?);
+        Ok(Manager::from_parts(dispatcher, backend))
+    }
+
+    /// Creates a new location manager that receives updates from a custom backend.
+    ///
+    /// `backend` is called with the handler that the backend should deliver
+    /// locations and errors to, which can be constructed using
+    /// [`LocationBuilder`].
+    pub fn with_backend<T, B, F>(handler: T, backend: F) -> Result<Self>
+    where
+        T: Handler,
+        B: Backend,
+        F: FnOnce(Arc<dyn Handler>) -> Result<B>,
+    {
+        let dispatcher = Dispatcher::new(handler);
+        let backend = ManagerInner::Custom(Box::new(backend(Arc::new(dispatcher.clone()))?));
+        Ok(Manager::from_parts(dispatcher, backend))
+    }
+
+    fn from_parts(dispatcher: Dispatcher, backend: ManagerInner) -> Self {
+        Manager {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    backend,
+                    request: None,
+                    streams: 0,
+                    subscriptions: Vec::new(),
+                    next_subscription: 0,
+                    running: None,
+                    current: Weak::new(),
+                }),
+                dispatcher,
+            }),
+        }
     }
 
     /// Requests authorization to access location data.
     ///
     /// This will return immediately and request authorization in the background.
     pub fn request_authorization(&self, access: Access, accuracy: Accuracy) -> Result<()> {
-        self.inner.request_authorization(access, accuracy)
+        match &self.shared.lock()?.backend {
+            ManagerInner::Sys(inner) => inner.request_authorization(access, accuracy),
+            ManagerInner::Custom(inner) => inner.request_authorization(access, accuracy),
+        }
+    }
+
+    /// Returns the current authorization status.
+    ///
+    /// Changes are also reported to [`Handler::authorization_changed`].
+    pub fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        match &self.shared.lock()?.backend {
+            ManagerInner::Sys(inner) => inner.authorization_status(),
+            ManagerInner::Custom(inner) => inner.authorization_status(),
+        }
     }
 
     /// Delivers a single update to the handler.
-    pub fn update_once(&self) -> Result<()> {
-        self.inner.update_once()
+    ///
+    /// The location or error that answers the request carries the ID of the
+    /// returned handle, which can also be used to cancel the request. Requests
+    /// made while another one is pending share its ID.
+    pub fn update_once(&self) -> Result<RequestHandle> {
+        let state = self.shared.lock()?;
+        self.shared
+            .begin_once(&state, |id| RequestHandle::new(self.shared.clone(), id))
     }
 
-    /// Begins delivering continuous updates to the handler.
+    /// Returns an owned copy of the current location.
+    ///
+    /// If a location was delivered within `max_age` it is returned immediately,
+    /// otherwise a single update is requested from the backend. Concurrent calls
+    /// share the same request. The future resolves to [`ErrorKind::Timeout`] if no
+    /// location is delivered within `timeout`. Once every caller has timed out or
+    /// dropped its future, the request is cancelled, so that the location is no
+    /// longer attributed to it.
+    ///
+    /// The update is requested when this is called rather than when the future
+    /// is first polled, so the future itself can be awaited on any thread.
+    pub fn current_location(
+        &self,
+        timeout: Duration,
+        max_age: Duration,
+    ) -> impl Future<Output = Result<LocationSnapshot>> + Send {
+        CurrentLocation::new(self.current(max_age), timeout)
+    }
+
+    /// Blocks until the current location is known, see
+    /// [`current_location`](Self::current_location).
+    ///
+    /// On platforms where updates are delivered on the main thread, such as macOS
+    /// and iOS, blocking the main thread prevents the update from being delivered,
+    /// so this will always time out unless a cached location is returned.
+    pub fn current_location_blocking(
+        &self,
+        timeout: Duration,
+        max_age: Duration,
+    ) -> Result<LocationSnapshot> {
+        let waiter = match self.current(max_age)? {
+            Current::Cached(location) => return Ok(location),
+            Current::Requested(waiter) => waiter,
+        };
+        let result = waiter.wait(Instant::now().checked_add(timeout));
+        if result.is_err() {
+            // Unlike a dropped future, this is on the manager's thread, so the
+            // backend can be told to stop looking. The manager stays locked so that
+            // no other request begins in the meantime.
+            let state = self.shared.lock()?;
+            if waiter.give_up() {
+                state.cancel_update_once()?;
+            }
+        }
+        result
+    }
+
+    /// Returns the most recent location delivered to the manager, or restored from
+    /// the file set with [`set_cache_config`](Self::set_cache_config), unless it is
+    /// no longer valid under the cache configuration.
+    pub fn last_known_location(&self) -> Option<CachedLocation> {
+        self.shared.dispatcher.cache().get()
+    }
+
+    /// Configures which locations are remembered as the
+    /// [last known location](Self::last_known_location).
+    ///
+    /// If `config` has a path, the location saved there by a previous manager is
+    /// restored, and the last known location is saved there from then on.
+    pub fn set_cache_config(&self, config: CacheConfig) -> Result<()> {
+        self.shared.dispatcher.cache().configure(config)
+    }
+
+    /// Enables validation of the locations delivered to the manager with a
+    /// [`Validator`](validation::Validator) configured by `config`, or disables it
+    /// if `None`.
+    ///
+    /// Invalid locations are reported to [`Handler::rejected`], and dropped unless
+    /// [`flag_only`](validation::ValidationConfig::flag_only) is set. Validation
+    /// happens before [filtering](Self::set_filter_config), which only sees valid
+    /// locations.
+    pub fn set_validation_config(
+        &self,
+        config: Option<validation::ValidationConfig>,
+    ) -> Result<()> {
+        let validator = config.map(validation::Validator::new).transpose()?;
+        *self
+            .shared
+            .dispatcher
+            .validator()
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))? = validator;
+        Ok(())
+    }
+
+    /// Enables smoothing of the locations delivered to the manager with a
+    /// [`KalmanFilter`](filter::KalmanFilter) configured by `config`, or disables it
+    /// if `None`.
+    ///
+    /// The filter sits between the backend and all consumers, including the
+    /// handler, subscriptions and the last known location. Changing the
+    /// configuration restarts the track.
+    pub fn set_filter_config(&self, config: Option<filter::FilterConfig>) -> Result<()> {
+        let filter = config.map(filter::KalmanFilter::new).transpose()?;
+        *self
+            .shared
+            .dispatcher
+            .filter()
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))? = filter;
+        Ok(())
+    }
+
+    fn current(&self, max_age: Duration) -> Result<Current> {
+        let mut state = self.shared.lock()?;
+        let cached = self.shared.dispatcher.cache().get();
+        if let Some(cached) = cached.filter(|cached| cached.age() <= max_age) {
+            return Ok(Current::Cached(cached.location));
+        }
+        if let Some(waiter) = state.current.upgrade().and_then(Waiter::join) {
+            return Ok(Current::Requested(waiter));
+        }
+
+        let request = self.shared.begin_once(&state, |id| {
+            let pending = Arc::downgrade(self.shared.dispatcher.pending());
+            let request = Arc::new(Request::new(id, pending));
+            let listener: Arc<dyn Listener> = request.clone();
+            self.shared.dispatcher.add(&listener);
+            request
+        })?;
+        state.current = Arc::downgrade(&request);
+        Ok(Current::Requested(Waiter::new(request)))
+    }
+
+    /// Begins delivering continuous updates to the handler, using the default
+    /// [`UpdateRequest`].
     pub fn start_updates(&mut self) -> Result<()> {
-        self.inner.start_updates()
+        self.start_updates_with(UpdateRequest::default())
+    }
+
+    /// Begins delivering continuous updates to the handler, configured by `request`.
+    ///
+    /// Backends honor as much of the request as the platform supports. Calling this
+    /// while updates are running replaces the previous request.
+    pub fn start_updates_with(&mut self, request: UpdateRequest) -> Result<()> {
+        let mut state = self.shared.lock()?;
+        let previous = state.request.replace(request);
+        state.apply().inspect_err(|_| state.request = previous)
     }
 
     /// Stops delivering continuous updates to the handler.
+    ///
+    /// Updates keep running while there are [update streams](Self::updates) or
+    /// [subscriptions](Self::subscribe) that need them.
     pub fn stop_updates(&mut self) -> Result<()> {
-        self.inner.stop_updates()
+        let mut state = self.shared.lock()?;
+        state.request = None;
+        state.apply()
+    }
+
+    /// Delivers continuous updates to `handler` until the returned subscription is
+    /// dropped.
+    ///
+    /// The backend runs at the most demanding request of the subscriptions, update
+    /// streams and [`start_updates`](Self::start_updates), and is stopped once none
+    /// of them need updates. Each subscriber only receives updates as often and as
+    /// far apart as its own `request` asks for.
+    ///
+    /// The handler the manager was created with keeps receiving every update.
+    pub fn subscribe<T>(&self, handler: T, request: UpdateRequest) -> Result<Subscription>
+    where
+        T: Handler,
+    {
+        let subscriber = Arc::new(Subscriber::new(handler, &request));
+        let listener: Arc<dyn Listener> = subscriber.clone();
+
+        let mut state = self.shared.lock()?;
+        let id = state.next_subscription;
+        state.next_subscription += 1;
+        state.subscriptions.push((id, request));
+        if let Err(e) = state.apply() {
+            state.subscriptions.pop();
+            return Err(e);
+        }
+        self.shared.dispatcher.add(&listener);
+        Ok(Subscription::new(self.shared.clone(), id, subscriber))
+    }
+
+    /// Monitors the fences of `geofencer`, delivering their events to `handler`
+    /// until the returned monitor is dropped.
+    ///
+    /// The fences are fed the updates of a [subscription](Self::subscribe) made with
+    /// `request`, so its interval and distance bound how quickly crossings are
+    /// noticed.
+    pub fn monitor_geofences<T>(
+        &self,
+        geofencer: Geofencer,
+        handler: T,
+        request: UpdateRequest,
+    ) -> Result<GeofenceMonitor>
+    where
+        T: GeofenceHandler,
+    {
+        let geofencer = Arc::new(Mutex::new(geofencer));
+        let subscription = self.subscribe(Monitor::new(geofencer.clone(), handler), request)?;
+        Ok(GeofenceMonitor::new(geofencer, subscription))
+    }
+
+    /// Returns a stream of owned copies of the locations and errors delivered to
+    /// the handler.
+    ///
+    /// Updates are started when the stream is first polled, and stopped when it is
+    /// dropped unless they were also started with
+    /// [`start_updates`](Self::start_updates). Up to `capacity` items are buffered
+    /// while the stream isn't being polled, after which `overflow` decides which
+    /// ones are dropped. The stream ends when the backend has no more updates to
+    /// deliver.
+    ///
+    /// On platforms where manager functions must be called from the main thread,
+    /// the stream must be first polled and dropped on the main thread.
+    #[cfg(feature = "async")]
+    pub fn updates(&self, capacity: usize, overflow: Overflow) -> Updates {
+        Updates::new(self.shared.clone(), capacity, overflow)
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> Result<StateGuard<'_>> {
+        let deferral = Deferral::new();
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?;
+        Ok(StateGuard {
+            state,
+            _deferral: deferral,
+        })
+    }
+
+    /// Requests a single update from the backend, which the next location or error
+    /// answers.
+    ///
+    /// `f` is called with the ID of the request before the backend is asked, so
+    /// that nothing answering it can be missed, and its result is returned.
+    fn begin_once<T>(&self, state: &State, f: impl FnOnce(RequestId) -> T) -> Result<T> {
+        let pending = self.dispatcher.pending();
+        let id = pending.begin().ok_or(Error::new(ErrorKind::Unknown))?;
+        let result = f(id);
+        if let Err(e) = state.update_once() {
+            pending.cancel(id);
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    /// Removes a caller from the single update `id`, cancelling it in the backend if
+    /// it was the last one.
+    fn cancel_once(&self, id: RequestId) -> Result<()> {
+        let state = self.lock()?;
+        if self.dispatcher.pending().cancel(id) {
+            state.cancel_update_once()?;
+        }
+        Ok(())
+    }
+}
+
+/// The locked state of a manager.
+///
+/// The events the backend raises on the locking thread are delivered once the
+/// lock is released, so that handlers can call back into the manager.
+struct StateGuard<'a> {
+    // Fields are dropped in order, so the lock is released before the events are
+    // delivered.
+    state: MutexGuard<'a, State>,
+    _deferral: Deferral,
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.state
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut State {
+        &mut self.state
     }
 }
 
+impl State {
+    fn update_once(&self) -> Result<()> {
+        match &self.backend {
+            ManagerInner::Sys(inner) => inner.update_once(),
+            ManagerInner::Custom(inner) => inner.update_once(),
+        }
+    }
+
+    fn cancel_update_once(&self) -> Result<()> {
+        match &self.backend {
+            ManagerInner::Sys(inner) => inner.cancel_update_once(),
+            ManagerInner::Custom(inner) => inner.cancel_update_once(),
+        }
+    }
+
+    fn start(&mut self, request: UpdateRequest) -> Result<()> {
+        match &mut self.backend {
+            ManagerInner::Sys(inner) => inner.start_updates(request),
+            ManagerInner::Custom(inner) => inner.start_updates_with(request),
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        match &mut self.backend {
+            ManagerInner::Sys(inner) => inner.stop_updates(),
+            ManagerInner::Custom(inner) => inner.stop_updates(),
+        }
+    }
+
+    /// Starts or reconfigures continuous updates to satisfy every consumer, or
+    /// stops them if none is left.
+    fn apply(&mut self) -> Result<()> {
+        let streams = (self.streams > 0).then(UpdateRequest::default);
+        let wanted = self
+            .subscriptions
+            .iter()
+            .map(|(_, request)| *request)
+            .chain(self.request)
+            .chain(streams)
+            .reduce(UpdateRequest::most_demanding);
+        if wanted == self.running {
+            return Ok(());
+        }
+
+        match wanted {
+            Some(request) => self.start(request)?,
+            None => self.stop()?,
+        }
+        self.running = wanted;
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, id: u64) -> Result<()> {
+        self.subscriptions.retain(|(other, _)| *other != id);
+        self.apply()
+    }
+
+    /// Starts updates on behalf of a stream, unless they are already running.
+    #[cfg(feature = "async")]
+    fn acquire_stream(&mut self) -> Result<()> {
+        self.streams += 1;
+        self.apply().inspect_err(|_| self.streams -= 1)
+    }
+
+    /// Stops updates once the last stream is gone, unless something else needs
+    /// them.
+    #[cfg(feature = "async")]
+    fn release_stream(&mut self) -> Result<()> {
+        self.streams -= 1;
+        self.apply()
+    }
+}
+
+/// A source of location data.
+///
+/// Implementing this trait allows a [`Manager`] to be driven by a provider that
+/// isn't built into this crate, see [`Manager::with_backend`].
+///
+/// The methods are called with the manager locked. Events delivered to the handler
+/// from within them are held back until the lock is released, but a backend must
+/// not wait on another thread that delivers events, e.g. by joining it, as its
+/// handler may be waiting for the lock.
+pub trait Backend: 'static + Send {
+    /// Requests authorization to access location data.
+    fn request_authorization(&self, access: Access, accuracy: Accuracy) -> Result<()>;
+
+    /// Returns the current authorization status.
+    ///
+    /// Backends that don't track authorization report
+    /// [`AuthorizationStatus::NotDetermined`].
+    fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        Ok(AuthorizationStatus::NotDetermined)
+    }
+
+    /// Delivers a single update to the handler.
+    fn update_once(&self) -> Result<()>;
+
+    /// Cancels the single update requested with [`update_once`](Self::update_once),
+    /// if it hasn't been delivered yet.
+    ///
+    /// Backends that can't cancel a request may rely on the default implementation,
+    /// which does nothing. The update is then still delivered to the handler, but
+    /// isn't attributed to the cancelled request.
+    fn cancel_update_once(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Begins delivering continuous updates to the handler.
+    fn start_updates(&mut self) -> Result<()>;
+
+    /// Begins delivering continuous updates to the handler, configured by `request`.
+    ///
+    /// Backends that can't be configured may rely on the default implementation,
+    /// which ignores the request and calls [`start_updates`](Self::start_updates).
+    fn start_updates_with(&mut self, request: UpdateRequest) -> Result<()> {
+        let _ = request;
+        self.start_updates()
+    }
+
+    /// Stops delivering continuous updates to the handler.
+    fn stop_updates(&mut self) -> Result<()>;
+}
+
 /// A handler that handles location events and errors.
 ///
 /// The handler should be registered with [`Manager::new`].
+///
+/// Handlers may call back into the manager they are registered with.
 pub trait Handler: 'static + Send + Sync {
     fn handle(&self, location: Location<'_>);
 
     fn error(&self, error: Error);
+
+    /// Called when the backend has no more updates to deliver, e.g. when a replayed
+    /// track has ended.
+    fn finished(&self) {}
+
+    /// Called when the authorization status changes, e.g. after the user responds to
+    /// a permission prompt.
+    fn authorization_changed(&self, _status: AuthorizationStatus) {}
+
+    /// Called instead of [`handle`](Self::handle) when a location fails the
+    /// validation enabled with [`Manager::set_validation_config`], or before it if
+    /// the location is only flagged.
+    ///
+    /// A dropped location doesn't answer a request made with
+    /// [`Manager::update_once`]. As the backend stops looking for a location after
+    /// delivering it, the request is answered with an
+    /// [`ErrorKind::TemporarilyUnavailable`] error carrying its ID instead.
+    fn rejected(&self, _location: LocationSnapshot, _rejection: validation::Rejection) {}
 }
 
 /// Data about the device's current whereabouts.
@@ -84,52 +716,276 @@ pub trait Handler: 'static + Send + Sync {
 /// Despite the name, `Location` contains more than just the location of the
 /// device. See the methods for all available information.
 pub struct Location<'a> {
-    inner: sys::Location<'a>,
+    inner: LocationInner<'a>,
+    /// The request made with [`Manager::update_once`] that this location answers.
+    request: Option<RequestId>,
+}
+
+enum LocationInner<'a> {
+    Sys(sys::Location<'a>),
+    Snapshot(LocationSnapshot),
 }
 
 impl Location<'_> {
+    /// The ID of the request made with [`Manager::update_once`] that this location
+    /// answers, if any.
+    pub fn request_id(&self) -> Option<RequestId> {
+        self.request
+    }
+
     pub fn coordinates(&self) -> Result<Coordinates> {
-        self.inner.coordinates()
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.coordinates(),
+            LocationInner::Snapshot(inner) => Ok(inner.coordinates),
+        }
     }
 
     pub fn altitude(&self) -> Result<f64> {
-        self.inner.altitude()
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.altitude(),
+            LocationInner::Snapshot(inner) => inner
+                .altitude
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
     }
 
     /// The direction in which the device is travelling, measured in degrees and
     /// relative to due north.
     pub fn bearing(&self) -> Result<f64> {
-        self.inner.bearing()
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.bearing(),
+            LocationInner::Snapshot(inner) => inner
+                .bearing
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
     }
 
     /// The instantaneous speed of the device measured in meters per second.
     pub fn speed(&self) -> Result<f64> {
-        self.inner.speed()
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.speed(),
+            LocationInner::Snapshot(inner) => inner
+                .speed
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
     }
 
     /// The time at which the location was acquired.
     ///
     /// This is not currently supported on Windows.
     pub fn time(&self) -> Result<SystemTime> {
-        self.inner.time()
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.time(),
+            LocationInner::Snapshot(inner) => inner
+                .time
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
+    }
+
+    /// The radius of uncertainty of the coordinates, measured in meters.
+    pub fn horizontal_accuracy(&self) -> Result<f64> {
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.horizontal_accuracy(),
+            LocationInner::Snapshot(inner) => inner
+                .horizontal_accuracy
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
+    }
+
+    /// The uncertainty of the altitude, measured in meters.
+    pub fn vertical_accuracy(&self) -> Result<f64> {
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.vertical_accuracy(),
+            LocationInner::Snapshot(inner) => inner
+                .vertical_accuracy
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
+    }
+
+    /// The uncertainty of the speed, measured in meters per second.
+    ///
+    /// This is not currently supported on Windows.
+    pub fn speed_accuracy(&self) -> Result<f64> {
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.speed_accuracy(),
+            LocationInner::Snapshot(inner) => inner
+                .speed_accuracy
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
+    }
+
+    /// The uncertainty of the bearing, measured in degrees.
+    ///
+    /// This is not currently supported on Windows.
+    pub fn bearing_accuracy(&self) -> Result<f64> {
+        match &self.inner {
+            LocationInner::Sys(inner) => inner.bearing_accuracy(),
+            LocationInner::Snapshot(inner) => inner
+                .bearing_accuracy
+                .ok_or(Error::new(ErrorKind::TemporarilyUnavailable)),
+        }
+    }
+
+    /// Captures all available data into an owned [`LocationSnapshot`], which can be
+    /// stored, compared and sent across threads.
+    ///
+    /// Fails only if the coordinates are unavailable.
+    pub fn snapshot(&self) -> Result<LocationSnapshot> {
+        if let LocationInner::Snapshot(inner) = &self.inner {
+            return Ok(LocationSnapshot {
+                request: self.request,
+                ..inner.clone()
+            });
+        }
+        Ok(LocationSnapshot {
+            coordinates: self.coordinates()?,
+            altitude: self.altitude().ok(),
+            bearing: self.bearing().ok(),
+            speed: self.speed().ok(),
+            time: self.time().ok(),
+            horizontal_accuracy: self.horizontal_accuracy().ok(),
+            vertical_accuracy: self.vertical_accuracy().ok(),
+            speed_accuracy: self.speed_accuracy().ok(),
+            bearing_accuracy: self.bearing_accuracy().ok(),
+            request: self.request,
+        })
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+impl From<LocationSnapshot> for Location<'static> {
+    fn from(snapshot: LocationSnapshot) -> Self {
+        Location {
+            request: snapshot.request,
+            inner: LocationInner::Snapshot(snapshot),
+        }
+    }
+}
+
+/// An owned copy of the data in a [`Location`], created by
+/// [`Location::snapshot`].
+///
+/// Fields that weren't available when the snapshot was taken are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocationSnapshot {
+    pub coordinates: Coordinates,
+    /// The altitude, measured in meters.
+    pub altitude: Option<f64>,
+    /// The bearing, measured in degrees and relative to due north.
+    pub bearing: Option<f64>,
+    /// The speed, measured in meters per second.
+    pub speed: Option<f64>,
+    /// The time at which the location was acquired.
+    pub time: Option<SystemTime>,
+    /// The radius of uncertainty of the coordinates, measured in meters.
+    pub horizontal_accuracy: Option<f64>,
+    /// The uncertainty of the altitude, measured in meters.
+    pub vertical_accuracy: Option<f64>,
+    /// The uncertainty of the speed, measured in meters per second.
+    pub speed_accuracy: Option<f64>,
+    /// The uncertainty of the bearing, measured in degrees.
+    pub bearing_accuracy: Option<f64>,
+    /// The request made with [`Manager::update_once`] that the location answers,
+    /// see [`Location::request_id`].
+    pub request: Option<RequestId>,
+}
+
+/// A builder for [`Location`]s that aren't backed by a platform object, intended
+/// for use by custom [`Backend`]s.
+///
+/// Data that isn't set on the builder is reported as
+/// [`ErrorKind::TemporarilyUnavailable`] by the corresponding getter.
+#[derive(Clone, Debug)]
+pub struct LocationBuilder {
+    inner: LocationSnapshot,
+}
+
+impl LocationBuilder {
+    pub fn new(coordinates: Coordinates) -> Self {
+        Self {
+            inner: LocationSnapshot {
+                coordinates,
+                altitude: None,
+                bearing: None,
+                speed: None,
+                time: None,
+                horizontal_accuracy: None,
+                vertical_accuracy: None,
+                speed_accuracy: None,
+                bearing_accuracy: None,
+                request: None,
+            },
+        }
+    }
+
+    pub fn altitude(mut self, altitude: f64) -> Self {
+        self.inner.altitude = Some(altitude);
+        self
+    }
+
+    /// Sets the bearing, measured in degrees and relative to due north.
+    pub fn bearing(mut self, bearing: f64) -> Self {
+        self.inner.bearing = Some(bearing);
+        self
+    }
+
+    /// Sets the speed, measured in meters per second.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.inner.speed = Some(speed);
+        self
+    }
+
+    pub fn time(mut self, time: SystemTime) -> Self {
+        self.inner.time = Some(time);
+        self
+    }
+
+    /// Sets the radius of uncertainty of the coordinates, measured in meters.
+    pub fn horizontal_accuracy(mut self, accuracy: f64) -> Self {
+        self.inner.horizontal_accuracy = Some(accuracy);
+        self
+    }
+
+    /// Sets the uncertainty of the altitude, measured in meters.
+    pub fn vertical_accuracy(mut self, accuracy: f64) -> Self {
+        self.inner.vertical_accuracy = Some(accuracy);
+        self
+    }
+
+    /// Sets the uncertainty of the speed, measured in meters per second.
+    pub fn speed_accuracy(mut self, accuracy: f64) -> Self {
+        self.inner.speed_accuracy = Some(accuracy);
+        self
+    }
+
+    /// Sets the uncertainty of the bearing, measured in degrees.
+    pub fn bearing_accuracy(mut self, accuracy: f64) -> Self {
+        self.inner.bearing_accuracy = Some(accuracy);
+        self
+    }
+
+    pub fn build(self) -> Location<'static> {
+        Location {
+            inner: LocationInner::Snapshot(self.inner),
+            request: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Coordinates {
     pub latitude: f64,
     pub longitude: f64,
 }
 
 /// The kind of location access.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Access {
     Foreground,
     Background,
 }
 
 /// The accuracy of the location data.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Accuracy {
     /// Approximate location accuracy.
     ///
@@ -144,3 +1000,117 @@ pub enum Accuracy {
     /// on Android.
     Precise,
 }
+
+/// The configuration of continuous updates, see [`Manager::start_updates_with`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpdateRequest {
+    /// The desired time between updates.
+    pub interval: Duration,
+    /// The shortest time between updates, which may be shorter than `interval` if
+    /// updates are available sooner, e.g. because another application requested
+    /// them. Defaults to `interval` if `None`.
+    pub min_interval: Option<Duration>,
+    /// The distance the device must move, in meters, before another update is
+    /// delivered.
+    pub min_distance: f64,
+    /// How to trade off accuracy against power usage.
+    pub priority: Priority,
+    /// The longest time updates may be delayed so that they can be delivered in
+    /// batches, which saves power on some platforms.
+    pub max_update_delay: Duration,
+}
+
+impl Default for UpdateRequest {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            min_interval: None,
+            min_distance: 0.0,
+            priority: Priority::HighAccuracy,
+            max_update_delay: Duration::ZERO,
+        }
+    }
+}
+
+impl UpdateRequest {
+    /// Combines two requests into one that satisfies both.
+    fn most_demanding(self, other: Self) -> Self {
+        let min_interval = match (self.min_interval, other.min_interval) {
+            (None, None) => None,
+            _ => Some(
+                self.min_interval
+                    .unwrap_or(self.interval)
+                    .min(other.min_interval.unwrap_or(other.interval)),
+            ),
+        };
+        Self {
+            interval: self.interval.min(other.interval),
+            min_interval,
+            min_distance: self.min_distance.min(other.min_distance),
+            priority: if self.priority.rank() >= other.priority.rank() {
+                self.priority
+            } else {
+                other.priority
+            },
+            max_update_delay: self.max_update_delay.min(other.max_update_delay),
+        }
+    }
+}
+
+/// How a backend should trade off accuracy against power usage.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// The most accurate locations available, regardless of power usage.
+    #[default]
+    HighAccuracy,
+    /// Block-level accuracy, with moderate power usage.
+    Balanced,
+    /// City-level accuracy, with low power usage.
+    LowPower,
+    /// Only locations that were requested by other applications.
+    Passive,
+}
+
+impl Priority {
+    /// Orders priorities from the least to the most demanding.
+    fn rank(self) -> u8 {
+        match self {
+            Priority::Passive => 0,
+            Priority::LowPower => 1,
+            Priority::Balanced => 2,
+            Priority::HighAccuracy => 3,
+        }
+    }
+}
+
+/// Whether the application is allowed to access location data.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AuthorizationStatus {
+    /// The user hasn't been asked for authorization yet.
+    #[default]
+    NotDetermined,
+    /// The user denied authorization.
+    Denied,
+    /// Authorization can't be granted, e.g. because of parental controls or a
+    /// device policy.
+    Restricted,
+    /// Location data may be accessed while the application is in use.
+    ForegroundOnly(Accuracy),
+    /// Location data may be accessed at any time.
+    Background(Accuracy),
+}
+
+impl AuthorizationStatus {
+    /// Returns whether location data may be accessed.
+    pub fn is_authorized(&self) -> bool {
+        matches!(self, Self::ForegroundOnly(_) | Self::Background(_))
+    }
+
+    /// Returns the accuracy of the location data that may be accessed, if any.
+    pub fn accuracy(&self) -> Option<Accuracy> {
+        match self {
+            Self::ForegroundOnly(accuracy) | Self::Background(accuracy) => Some(*accuracy),
+            _ => None,
+        }
+    }
+}
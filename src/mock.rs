@@ -0,0 +1,426 @@
+//! A scriptable backend for testing code that uses a [`Manager`].
+//!
+//! ```
+//! # use robius_location::{mock::{Call, Mock}, *};
+//! # struct MyHandler;
+//! # impl Handler for MyHandler {
+//! #     fn handle(&self, _: Location<'_>) {}
+//! #     fn error(&self, _: Error) {}
+//! # }
+//! let mock = Mock::new();
+//! let mut manager = Manager::with_backend(MyHandler, mock.backend()).unwrap();
+//!
+//! manager.start_updates().unwrap();
+//! mock.push(LocationBuilder::new(Coordinates { latitude: 1.0, longitude: 2.0 }).build());
+//! mock.push_error(ErrorKind::Network);
+//!
+//! assert_eq!(mock.calls(), [Call::StartUpdates(UpdateRequest::default())]);
+//! ```
+//!
+//! [`Manager`]: crate::Manager
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Backend, Error, ErrorKind, Handler, Location, Result,
+    UpdateRequest,
+};
+
+/// A call made by the application to a [`Manager`](crate::Manager) backed by a
+/// [`Mock`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Call {
+    RequestAuthorization(Access, Accuracy),
+    UpdateOnce,
+    /// The single update requested by the last [`Call::UpdateOnce`] was cancelled.
+    CancelUpdateOnce,
+    /// Continuous updates were started with the given request, which is the default
+    /// one for [`Manager::start_updates`](crate::Manager::start_updates).
+    StartUpdates(UpdateRequest),
+    StopUpdates,
+}
+
+#[derive(Default)]
+struct State {
+    handler: Option<Arc<dyn Handler>>,
+    calls: Vec<Call>,
+    denied: bool,
+    status: AuthorizationStatus,
+    updating: bool,
+}
+
+/// The test handle for a mock backend.
+///
+/// Clones of the handle refer to the same backend.
+#[derive(Clone, Default)]
+pub struct Mock {
+    state: Arc<Mutex<State>>,
+}
+
+impl Mock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the backend constructor to pass to
+    /// [`Manager::with_backend`](crate::Manager::with_backend).
+    pub fn backend(&self) -> impl FnOnce(Arc<dyn Handler>) -> Result<MockBackend> {
+        let state = self.state.clone();
+        move |handler| {
+            lock(&state).handler = Some(handler);
+            Ok(MockBackend { state })
+        }
+    }
+
+    /// Delivers a location to the manager's handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the backend isn't attached to a manager.
+    pub fn push(&self, location: Location<'_>) {
+        self.handler().handle(location);
+    }
+
+    /// Delivers an error to the manager's handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the backend isn't attached to a manager.
+    pub fn push_error<E>(&self, error: E)
+    where
+        E: Into<Error>,
+    {
+        self.handler().error(error.into());
+    }
+
+    /// Makes subsequent authorization requests succeed. This is the default.
+    pub fn grant_authorization(&self) {
+        lock(&self.state).denied = false;
+    }
+
+    /// Makes subsequent authorization requests fail with
+    /// [`ErrorKind::AuthorizationDenied`].
+    pub fn deny_authorization(&self) {
+        lock(&self.state).denied = true;
+    }
+
+    /// Changes the authorization status, notifying the manager's handler if it
+    /// differs from the current one.
+    pub fn set_authorization_status(&self, status: AuthorizationStatus) {
+        set_status(&self.state, status);
+    }
+
+    /// Returns the calls made to the backend so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        lock(&self.state).calls.clone()
+    }
+
+    /// Forgets the calls made to the backend so far.
+    pub fn clear_calls(&self) {
+        lock(&self.state).calls.clear();
+    }
+
+    /// Returns whether continuous updates have been started and not stopped.
+    pub fn is_updating(&self) -> bool {
+        lock(&self.state).updating
+    }
+
+    /// Returns whether the backend is attached to a manager that hasn't been dropped.
+    pub fn is_attached(&self) -> bool {
+        lock(&self.state).handler.is_some()
+    }
+
+    fn handler(&self) -> Arc<dyn Handler> {
+        // The lock must not be held while calling the handler, as it may call back
+        // into the manager.
+        lock(&self.state)
+            .handler
+            .clone()
+            .expect("mock backend is not attached to a manager")
+    }
+}
+
+/// The backend half of a [`Mock`].
+pub struct MockBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl Backend for MockBackend {
+    fn request_authorization(&self, access: Access, accuracy: Accuracy) -> Result<()> {
+        let denied = {
+            let mut state = lock(&self.state);
+            state
+                .calls
+                .push(Call::RequestAuthorization(access, accuracy));
+            state.denied
+        };
+        if denied {
+            set_status(&self.state, AuthorizationStatus::Denied);
+            return Err(Error::new(ErrorKind::AuthorizationDenied));
+        }
+        set_status(
+            &self.state,
+            match access {
+                Access::Foreground => AuthorizationStatus::ForegroundOnly(accuracy),
+                Access::Background => AuthorizationStatus::Background(accuracy),
+            },
+        );
+        Ok(())
+    }
+
+    fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        Ok(lock(&self.state).status)
+    }
+
+    fn update_once(&self) -> Result<()> {
+        lock(&self.state).calls.push(Call::UpdateOnce);
+        Ok(())
+    }
+
+    fn cancel_update_once(&self) -> Result<()> {
+        lock(&self.state).calls.push(Call::CancelUpdateOnce);
+        Ok(())
+    }
+
+    fn start_updates(&mut self) -> Result<()> {
+        self.start_updates_with(UpdateRequest::default())
+    }
+
+    fn start_updates_with(&mut self, request: UpdateRequest) -> Result<()> {
+        let mut state = lock(&self.state);
+        state.calls.push(Call::StartUpdates(request));
+        state.updating = true;
+        Ok(())
+    }
+
+    fn stop_updates(&mut self) -> Result<()> {
+        let mut state = lock(&self.state);
+        state.calls.push(Call::StopUpdates);
+        state.updating = false;
+        Ok(())
+    }
+}
+
+impl Drop for MockBackend {
+    fn drop(&mut self) {
+        let mut state = lock(&self.state);
+        state.handler = None;
+        state.updating = false;
+    }
+}
+
+fn set_status(state: &Mutex<State>, status: AuthorizationStatus) {
+    let handler = {
+        let mut state = lock(state);
+        if std::mem::replace(&mut state.status, status) == status {
+            return;
+        }
+        state.handler.clone()
+    };
+    // The lock must not be held while calling the handler, as it may call back into
+    // the manager.
+    if let Some(handler) = handler {
+        handler.authorization_changed(status);
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // A panicking test shouldn't poison every other use of the mock.
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{self, Receiver},
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::{event::FnHandler, Coordinates, LocationBuilder, LocationEvent, Manager};
+
+    fn manager(mock: &Mock) -> (Manager, Receiver<LocationEvent>) {
+        let (sender, events) = mpsc::channel();
+        let handler = FnHandler(move |event| {
+            let _ = sender.send(event);
+        });
+        let manager = Manager::with_backend(handler, mock.backend()).unwrap();
+        (manager, events)
+    }
+
+    fn location(latitude: f64) -> LocationBuilder {
+        LocationBuilder::new(Coordinates {
+            latitude,
+            longitude: 2.0,
+        })
+    }
+
+    #[test]
+    fn locations() {
+        let mock = Mock::new();
+        assert!(!mock.is_attached());
+        let (mut manager, events) = manager(&mock);
+        assert!(mock.is_attached());
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        mock.push(
+            location(1.0)
+                .altitude(100.0)
+                .speed(3.0)
+                .time(time)
+                .horizontal_accuracy(5.0)
+                .build(),
+        );
+        let Ok(LocationEvent::Location(first)) = events.try_recv() else {
+            panic!("expected a location");
+        };
+        assert_eq!(first.coordinates.latitude, 1.0);
+        assert_eq!(first.altitude, Some(100.0));
+        assert_eq!(first.speed, Some(3.0));
+        assert_eq!(first.time, Some(time));
+        assert_eq!(first.horizontal_accuracy, Some(5.0));
+        assert_eq!(first.request, None);
+
+        // Pushed locations answer the pending request.
+        let request = manager.update_once().unwrap().id();
+        manager.start_updates().unwrap();
+        assert!(mock.is_updating());
+        for latitude in [2.0, 3.0] {
+            mock.push(location(latitude).build());
+        }
+        let locations: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                LocationEvent::Location(location) => location,
+                event => panic!("expected a location, got {event:?}"),
+            })
+            .collect();
+        assert_eq!(locations[0].coordinates.latitude, 2.0);
+        assert_eq!(locations[0].request, Some(request));
+        assert_eq!(locations[1].coordinates.latitude, 3.0);
+        assert_eq!(locations[1].request, None);
+
+        manager.stop_updates().unwrap();
+        assert!(!mock.is_updating());
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::UpdateOnce,
+                Call::StartUpdates(UpdateRequest::default()),
+                Call::StopUpdates
+            ]
+        );
+        mock.clear_calls();
+        assert_eq!(mock.calls(), []);
+
+        manager.start_updates().unwrap();
+        drop(manager);
+        assert!(!mock.is_attached());
+        assert!(!mock.is_updating());
+    }
+
+    #[test]
+    #[should_panic = "not attached"]
+    fn push_detached() {
+        Mock::new().push(location(1.0).build());
+    }
+
+    #[test]
+    fn errors() {
+        let mock = Mock::new();
+        let (manager, events) = manager(&mock);
+
+        mock.push_error(ErrorKind::Network);
+        let request = manager.update_once().unwrap().id();
+        mock.push_error(Error::new(ErrorKind::TemporarilyUnavailable).with_message("no fix"));
+
+        let errors: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                LocationEvent::Error(error) => error,
+                event => panic!("expected an error, got {event:?}"),
+            })
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].kind(), ErrorKind::Network);
+        assert_eq!(errors[0].request_id(), None);
+        assert_eq!(errors[1].kind(), ErrorKind::TemporarilyUnavailable);
+        assert_eq!(errors[1].message(), Some("no fix"));
+        // The error ends the pending request.
+        assert_eq!(errors[1].request_id(), Some(request));
+    }
+
+    #[test]
+    fn authorization() {
+        let mock = Mock::new();
+        let (manager, events) = manager(&mock);
+        assert_eq!(
+            manager.authorization_status().unwrap(),
+            AuthorizationStatus::NotDetermined
+        );
+
+        manager
+            .request_authorization(Access::Foreground, Accuracy::Approximate)
+            .unwrap();
+        let status = AuthorizationStatus::ForegroundOnly(Accuracy::Approximate);
+        assert_eq!(manager.authorization_status().unwrap(), status);
+        assert!(matches!(
+            events.try_recv(),
+            Ok(LocationEvent::AuthorizationChanged(changed)) if changed == status
+        ));
+        // Asking again for the same doesn't change the status.
+        manager
+            .request_authorization(Access::Foreground, Accuracy::Approximate)
+            .unwrap();
+        assert!(events.try_recv().is_err());
+
+        mock.deny_authorization();
+        let error = manager
+            .request_authorization(Access::Background, Accuracy::Precise)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AuthorizationDenied);
+        assert_eq!(
+            manager.authorization_status().unwrap(),
+            AuthorizationStatus::Denied
+        );
+        assert!(matches!(
+            events.try_recv(),
+            Ok(LocationEvent::AuthorizationChanged(
+                AuthorizationStatus::Denied
+            ))
+        ));
+
+        mock.grant_authorization();
+        manager
+            .request_authorization(Access::Background, Accuracy::Precise)
+            .unwrap();
+        let status = AuthorizationStatus::Background(Accuracy::Precise);
+        assert_eq!(manager.authorization_status().unwrap(), status);
+        assert!(events.try_recv().is_ok());
+
+        // The status can also change on its own, e.g. in the system settings.
+        mock.set_authorization_status(AuthorizationStatus::Restricted);
+        assert_eq!(
+            manager.authorization_status().unwrap(),
+            AuthorizationStatus::Restricted
+        );
+        assert!(matches!(
+            events.try_recv(),
+            Ok(LocationEvent::AuthorizationChanged(
+                AuthorizationStatus::Restricted
+            ))
+        ));
+        mock.set_authorization_status(AuthorizationStatus::Restricted);
+        assert!(events.try_recv().is_err());
+
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::RequestAuthorization(Access::Foreground, Accuracy::Approximate),
+                Call::RequestAuthorization(Access::Foreground, Accuracy::Approximate),
+                Call::RequestAuthorization(Access::Background, Accuracy::Precise),
+                Call::RequestAuthorization(Access::Background, Accuracy::Precise),
+            ]
+        );
+    }
+}
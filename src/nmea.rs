@@ -0,0 +1,794 @@
+//! A parser for [NMEA 0183] sentences, as emitted by most GNSS receivers.
+//!
+//! Only the sentences relevant to positioning are supported: `GGA`, `RMC`, `VTG`,
+//! `GSA` and `GSV`. Sentences may come from any talker, e.g. `$GPGGA` and `$GNGGA`
+//! are both parsed as [`Sentence::Gga`].
+//!
+//! [NMEA 0183]: https://en.wikipedia.org/wiki/NMEA_0183
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::Coordinates;
+
+const KNOTS_TO_METERS_PER_SECOND: f64 = 1852.0 / 3600.0;
+const KILOMETERS_PER_HOUR_TO_METERS_PER_SECOND: f64 = 1000.0 / 3600.0;
+
+/// An error that can occur when parsing a sentence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The checksum didn't match the contents of the sentence.
+    Checksum,
+    /// The sentence wasn't well-formed.
+    Malformed,
+    /// The sentence is well-formed but of a type this parser doesn't handle.
+    Unsupported,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::Checksum => "checksum mismatch",
+            ParseError::Malformed => "malformed sentence",
+            ParseError::Unsupported => "unsupported sentence",
+        })
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for crate::Error {
+    fn from(e: ParseError) -> Self {
+        crate::Error::new(crate::ErrorKind::Parse).with_source(e)
+    }
+}
+
+/// The source of a sentence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Talker {
+    /// `GP`: GPS.
+    Gps,
+    /// `GL`: GLONASS.
+    Glonass,
+    /// `GA`: Galileo.
+    Galileo,
+    /// `GB` or `BD`: BeiDou.
+    BeiDou,
+    /// `GQ`: QZSS.
+    Qzss,
+    /// `GN`: a combination of constellations.
+    Gnss,
+    /// Any other talker ID.
+    Other([u8; 2]),
+}
+
+impl Talker {
+    fn new(id: [u8; 2]) -> Self {
+        match &id {
+            b"GP" => Talker::Gps,
+            b"GL" => Talker::Glonass,
+            b"GA" => Talker::Galileo,
+            b"GB" | b"BD" => Talker::BeiDou,
+            b"GQ" => Talker::Qzss,
+            b"GN" => Talker::Gnss,
+            _ => Talker::Other(id),
+        }
+    }
+}
+
+/// A parsed sentence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+    Vtg(Vtg),
+    Gsa(Gsa),
+    Gsv(Gsv),
+}
+
+/// A UTC calendar date.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Combines the date with a time of day, measured from midnight UTC.
+    pub fn at(&self, time: Duration) -> Option<SystemTime> {
+        crate::time::from_date(self.year.into(), self.month.into(), self.day.into())
+            .map(|midnight| midnight + time)
+    }
+}
+
+/// Global positioning system fix data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gga {
+    /// The time of the fix, measured from midnight UTC.
+    pub time: Option<Duration>,
+    pub coordinates: Option<Coordinates>,
+    /// The fix quality, where `0` means that the fix is invalid.
+    pub quality: u8,
+    /// The number of satellites used in the fix.
+    pub satellites: Option<u8>,
+    /// The horizontal dilution of precision.
+    pub hdop: Option<f64>,
+    /// The altitude above mean sea level in meters.
+    pub altitude: Option<f64>,
+    /// The height of the geoid above the WGS-84 ellipsoid in meters.
+    pub geoid_separation: Option<f64>,
+}
+
+/// Recommended minimum specific GNSS data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rmc {
+    /// The time of the fix, measured from midnight UTC.
+    pub time: Option<Duration>,
+    /// Whether the receiver considers the data valid.
+    pub valid: bool,
+    pub coordinates: Option<Coordinates>,
+    /// The speed over ground in meters per second.
+    pub speed: Option<f64>,
+    /// The course over ground in degrees relative to true north.
+    pub course: Option<f64>,
+    pub date: Option<Date>,
+}
+
+/// Course over ground and ground speed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vtg {
+    /// The course over ground in degrees relative to true north.
+    pub course: Option<f64>,
+    /// The speed over ground in meters per second.
+    pub speed: Option<f64>,
+}
+
+/// The type of fix reported by a [`Gsa`] sentence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FixMode {
+    NoFix,
+    TwoDimensional,
+    ThreeDimensional,
+}
+
+/// DOP and active satellites.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gsa {
+    pub mode: FixMode,
+    /// The IDs of the satellites used in the fix.
+    pub satellites: Vec<u16>,
+    /// The position dilution of precision.
+    pub pdop: Option<f64>,
+    /// The horizontal dilution of precision.
+    pub hdop: Option<f64>,
+    /// The vertical dilution of precision.
+    pub vdop: Option<f64>,
+}
+
+/// Satellites in view.
+///
+/// The satellites in view are spread across several sentences, so a single `Gsv`
+/// only describes up to four of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gsv {
+    /// The number of sentences in this group.
+    pub total: u8,
+    /// The (one-based) index of this sentence in its group.
+    pub number: u8,
+    /// The total number of satellites in view.
+    pub in_view: u16,
+    pub satellites: Vec<Satellite>,
+}
+
+/// A satellite described by a [`Gsv`] sentence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Satellite {
+    pub id: u16,
+    /// The elevation in degrees.
+    pub elevation: Option<f64>,
+    /// The azimuth in degrees relative to true north.
+    pub azimuth: Option<f64>,
+    /// The signal-to-noise ratio in dB-Hz.
+    pub snr: Option<f64>,
+}
+
+/// Parses a single sentence, e.g.
+/// `$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47`.
+///
+/// Surrounding whitespace, including the trailing `\r\n`, is ignored. If the
+/// sentence has a checksum it must be correct.
+pub fn parse(sentence: &str) -> Result<(Talker, Sentence), ParseError> {
+    let sentence = sentence.trim();
+    let body = sentence.strip_prefix('$').ok_or(ParseError::Malformed)?;
+
+    let body = match body.split_once('*') {
+        Some((body, checksum)) => {
+            let checksum = u8::from_str_radix(checksum, 16).map_err(|_| ParseError::Malformed)?;
+            if body.bytes().fold(0, |acc, b| acc ^ b) != checksum {
+                return Err(ParseError::Checksum);
+            }
+            body
+        }
+        None => body,
+    };
+
+    let mut fields = body.split(',');
+    let address = fields.next().ok_or(ParseError::Malformed)?.as_bytes();
+    if address.first() == Some(&b'P') {
+        // Proprietary sentences use a different address format.
+        return Err(ParseError::Unsupported);
+    }
+    let [a, b, ref kind @ ..] = *address else {
+        return Err(ParseError::Malformed);
+    };
+    let talker = Talker::new([a, b]);
+
+    let mut fields = Fields(fields);
+    let sentence = match kind {
+        b"GGA" => Sentence::Gga(Gga {
+            time: fields.time()?,
+            coordinates: fields.coordinates()?,
+            quality: fields.number()?.unwrap_or(0),
+            satellites: fields.number()?,
+            hdop: fields.number()?,
+            altitude: fields.number_with_unit()?,
+            geoid_separation: fields.number_with_unit()?,
+        }),
+        b"RMC" => Sentence::Rmc(Rmc {
+            time: fields.time()?,
+            valid: fields.next() == "A",
+            coordinates: fields.coordinates()?,
+            speed: fields
+                .number::<f64>()?
+                .map(|knots| knots * KNOTS_TO_METERS_PER_SECOND),
+            course: fields.number()?,
+            date: fields.date()?,
+        }),
+        b"VTG" => {
+            // The first pair is the true course, the second the magnetic course, the
+            // third the speed in knots and the fourth the speed in km/h.
+            let course = fields.number_with_unit()?;
+            fields.skip(4);
+            let speed = fields
+                .number::<f64>()?
+                .map(|kmh| kmh * KILOMETERS_PER_HOUR_TO_METERS_PER_SECOND);
+            Sentence::Vtg(Vtg { course, speed })
+        }
+        b"GSA" => {
+            // Skip the selection mode.
+            fields.skip(1);
+            let mode = match fields.next() {
+                "2" => FixMode::TwoDimensional,
+                "3" => FixMode::ThreeDimensional,
+                _ => FixMode::NoFix,
+            };
+            let mut satellites = Vec::new();
+            for _ in 0..12 {
+                if let Some(id) = fields.number()? {
+                    satellites.push(id);
+                }
+            }
+            Sentence::Gsa(Gsa {
+                mode,
+                satellites,
+                pdop: fields.number()?,
+                hdop: fields.number()?,
+                vdop: fields.number()?,
+            })
+        }
+        b"GSV" => {
+            let total = fields.number()?.ok_or(ParseError::Malformed)?;
+            let number = fields.number()?.ok_or(ParseError::Malformed)?;
+            let in_view = fields.number()?.unwrap_or(0);
+            let mut satellites = Vec::new();
+            // Up to four satellites follow, possibly with a trailing signal ID.
+            while fields.remaining() >= 4 {
+                let id = fields.number()?;
+                let elevation = fields.number()?;
+                let azimuth = fields.number()?;
+                let snr = fields.number()?;
+                if let Some(id) = id {
+                    satellites.push(Satellite {
+                        id,
+                        elevation,
+                        azimuth,
+                        snr,
+                    });
+                }
+            }
+            Sentence::Gsv(Gsv {
+                total,
+                number,
+                in_view,
+                satellites,
+            })
+        }
+        _ => return Err(ParseError::Unsupported),
+    };
+
+    Ok((talker, sentence))
+}
+
+/// The user equivalent range error, in meters, assumed when converting a dilution
+/// of precision into an accuracy.
+///
+/// Receivers don't report their actual range error, so this is a typical value for
+/// consumer GPS receivers.
+#[cfg(any(target_os = "linux", feature = "replay"))]
+pub(crate) const UERE: f64 = 5.0;
+
+/// Combines the sentences a receiver emits for a single epoch into a location.
+#[cfg(any(target_os = "linux", feature = "replay"))]
+#[derive(Default)]
+pub(crate) struct Assembler {
+    /// The most recently reported date, used to timestamp sentences without one.
+    date: Option<Date>,
+    /// The time of day of the current epoch.
+    epoch: Option<Duration>,
+    /// Whether the receiver reported that it has no fix.
+    invalid: bool,
+    /// Whether the receiver reported a 2D fix, in which case altitude is unreliable.
+    two_dimensional: bool,
+    /// The most recently reported dilutions of precision, which `GSA` sentences
+    /// report once per epoch but not necessarily alongside `GGA` and `RMC`.
+    hdop: Option<f64>,
+    vdop: Option<f64>,
+    seen_gga: bool,
+    seen_rmc: bool,
+    /// Whether the current epoch has already been emitted.
+    emitted: bool,
+    coordinates: Option<Coordinates>,
+    altitude: Option<f64>,
+    bearing: Option<f64>,
+    speed: Option<f64>,
+}
+
+#[cfg(any(target_os = "linux", feature = "replay"))]
+impl Assembler {
+    /// Adds a sentence to the current epoch, returning a location if an epoch was
+    /// completed.
+    pub(crate) fn push(&mut self, sentence: Sentence) -> Option<crate::LocationBuilder> {
+        match sentence {
+            Sentence::Gga(gga) => {
+                let completed = self.begin_epoch(gga.time);
+                self.seen_gga = true;
+                self.invalid |= gga.quality == 0;
+                self.coordinates = self.coordinates.or(gga.coordinates);
+                self.altitude = gga.altitude;
+                self.hdop = gga.hdop.or(self.hdop);
+                completed.or_else(|| self.complete())
+            }
+            Sentence::Rmc(rmc) => {
+                let completed = self.begin_epoch(rmc.time);
+                self.seen_rmc = true;
+                self.invalid |= !rmc.valid;
+                self.date = rmc.date.or(self.date);
+                self.coordinates = self.coordinates.or(rmc.coordinates);
+                self.speed = rmc.speed.or(self.speed);
+                self.bearing = rmc.course.or(self.bearing);
+                completed.or_else(|| self.complete())
+            }
+            Sentence::Vtg(vtg) => {
+                self.speed = self.speed.or(vtg.speed);
+                self.bearing = self.bearing.or(vtg.course);
+                None
+            }
+            Sentence::Gsa(gsa) => {
+                self.two_dimensional = gsa.mode == FixMode::TwoDimensional;
+                self.hdop = gsa.hdop.or(self.hdop);
+                self.vdop = gsa.vdop.or(self.vdop);
+                None
+            }
+            // The satellites in view don't contribute to the location.
+            Sentence::Gsv(_) => None,
+        }
+    }
+
+    /// Starts a new epoch if `time` differs from the current one, returning the
+    /// location for the previous epoch if it was not yet emitted.
+    fn begin_epoch(&mut self, time: Option<Duration>) -> Option<crate::LocationBuilder> {
+        if time == self.epoch && time.is_some() {
+            return None;
+        }
+
+        let previous = if self.emitted { None } else { self.location() };
+        let date = self.date;
+        let two_dimensional = self.two_dimensional;
+        let (hdop, vdop) = (self.hdop, self.vdop);
+        *self = Assembler {
+            date,
+            epoch: time,
+            two_dimensional,
+            hdop,
+            vdop,
+            ..Default::default()
+        };
+        previous
+    }
+
+    /// Emits the current epoch once both `GGA` and `RMC` have been seen for it.
+    fn complete(&mut self) -> Option<crate::LocationBuilder> {
+        if self.emitted || !(self.seen_gga && self.seen_rmc) {
+            return None;
+        }
+        self.emitted = true;
+        self.location()
+    }
+
+    /// Returns the location for the final epoch if it was not yet emitted.
+    pub(crate) fn finish(&mut self) -> Option<crate::LocationBuilder> {
+        self.begin_epoch(None)
+    }
+
+    fn location(&self) -> Option<crate::LocationBuilder> {
+        if self.invalid {
+            return None;
+        }
+
+        Some(crate::LocationBuilder {
+            inner: crate::LocationSnapshot {
+                coordinates: self.coordinates?,
+                altitude: self.altitude.filter(|_| !self.two_dimensional),
+                bearing: self.bearing,
+                speed: self.speed,
+                time: self
+                    .date
+                    .zip(self.epoch)
+                    .and_then(|(date, time)| date.at(time)),
+                horizontal_accuracy: self.hdop.map(|hdop| hdop * UERE),
+                vertical_accuracy: self
+                    .vdop
+                    .map(|vdop| vdop * UERE)
+                    .filter(|_| !self.two_dimensional),
+                speed_accuracy: None,
+                bearing_accuracy: None,
+                request: None,
+            },
+        })
+    }
+}
+
+/// The comma-separated fields of a sentence, after the address.
+struct Fields<'a>(std::str::Split<'a, char>);
+
+impl<'a> Fields<'a> {
+    /// Returns the next field, treating missing trailing fields as empty.
+    fn next(&mut self) -> &'a str {
+        self.0.next().unwrap_or("")
+    }
+
+    fn skip(&mut self, n: usize) {
+        for _ in 0..n {
+            self.next();
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.0.clone().count()
+    }
+
+    fn number<T>(&mut self) -> Result<Option<T>, ParseError>
+    where
+        T: std::str::FromStr,
+    {
+        match self.next() {
+            "" => Ok(None),
+            field => field.parse().map(Some).map_err(|_| ParseError::Malformed),
+        }
+    }
+
+    /// Parses a number followed by a unit field, such as `545.4,M`.
+    fn number_with_unit(&mut self) -> Result<Option<f64>, ParseError> {
+        let number = self.number()?;
+        self.next();
+        Ok(number)
+    }
+
+    /// Parses a time of the form `hhmmss.ss`.
+    fn time(&mut self) -> Result<Option<Duration>, ParseError> {
+        let field = self.next();
+        if field.is_empty() {
+            return Ok(None);
+        }
+        let (hours, minutes, seconds) = split_digits(field, 2)
+            .and_then(|(hours, rest)| Some((hours, split_digits(rest, 2)?)))
+            .map(|(hours, (minutes, seconds))| (hours, minutes, seconds))
+            .ok_or(ParseError::Malformed)?;
+        let seconds: f64 = seconds.parse().map_err(|_| ParseError::Malformed)?;
+        if hours >= 24 || minutes >= 60 || !(0.0..61.0).contains(&seconds) {
+            return Err(ParseError::Malformed);
+        }
+        Ok(Some(
+            Duration::from_secs(u64::from(hours * 3600 + minutes * 60))
+                + Duration::from_secs_f64(seconds),
+        ))
+    }
+
+    /// Parses a date of the form `ddmmyy`.
+    fn date(&mut self) -> Result<Option<Date>, ParseError> {
+        let field = self.next();
+        if field.is_empty() {
+            return Ok(None);
+        }
+        if field.len() != 6 {
+            return Err(ParseError::Malformed);
+        }
+        let (day, rest) = split_digits(field, 2).ok_or(ParseError::Malformed)?;
+        let (month, year) = split_digits(rest, 2).ok_or(ParseError::Malformed)?;
+        let year: u16 = year.parse().map_err(|_| ParseError::Malformed)?;
+        Ok(Some(Date {
+            // Two-digit years are ambiguous; assume that the receiver is not from before 1980.
+            year: if year < 80 { 2000 + year } else { 1900 + year },
+            month: month as u8,
+            day: day as u8,
+        }))
+    }
+
+    /// Parses a latitude and longitude, each of which is followed by its hemisphere.
+    fn coordinates(&mut self) -> Result<Option<Coordinates>, ParseError> {
+        let latitude = self.angle(2, "N", "S")?;
+        let longitude = self.angle(3, "E", "W")?;
+        Ok(latitude
+            .zip(longitude)
+            .map(|(latitude, longitude)| Coordinates {
+                latitude,
+                longitude,
+            }))
+    }
+
+    /// Parses an angle of the form `d..dmm.mmmm` followed by its hemisphere.
+    fn angle(
+        &mut self,
+        degree_digits: usize,
+        positive: &str,
+        negative: &str,
+    ) -> Result<Option<f64>, ParseError> {
+        let value = self.next();
+        let hemisphere = self.next();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let (degrees, minutes) = split_digits(value, degree_digits).ok_or(ParseError::Malformed)?;
+        let minutes: f64 = minutes.parse().map_err(|_| ParseError::Malformed)?;
+        let angle = f64::from(degrees) + minutes / 60.0;
+        match hemisphere {
+            h if h == positive => Ok(Some(angle)),
+            h if h == negative => Ok(Some(-angle)),
+            _ => Err(ParseError::Malformed),
+        }
+    }
+}
+
+/// Splits off and parses the first `n` digits of `field`.
+fn split_digits(field: &str, n: usize) -> Option<(u32, &str)> {
+    let digits = field.get(..n)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, &field[n..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(latitude: f64, longitude: f64) -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude,
+            longitude,
+        })
+    }
+
+    #[test]
+    fn gga() {
+        assert_eq!(
+            parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n"),
+            Ok((
+                Talker::Gps,
+                Sentence::Gga(Gga {
+                    time: Some(Duration::from_secs(12 * 3600 + 35 * 60 + 19)),
+                    coordinates: coordinates(48.0 + 7.038 / 60.0, 11.0 + 31.0 / 60.0),
+                    quality: 1,
+                    satellites: Some(8),
+                    hdop: Some(0.9),
+                    altitude: Some(545.4),
+                    geoid_separation: Some(46.9),
+                })
+            ))
+        );
+        // Fields are missing while the receiver has no fix.
+        assert_eq!(
+            parse("$GPGGA,000002,,,,,0,00,,,M,,M,,"),
+            Ok((
+                Talker::Gps,
+                Sentence::Gga(Gga {
+                    time: Some(Duration::from_secs(2)),
+                    coordinates: None,
+                    quality: 0,
+                    satellites: Some(0),
+                    hdop: None,
+                    altitude: None,
+                    geoid_separation: None,
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn rmc() {
+        assert_eq!(
+            parse("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A"),
+            Ok((
+                Talker::Gps,
+                Sentence::Rmc(Rmc {
+                    time: Some(Duration::from_secs(12 * 3600 + 35 * 60 + 19)),
+                    valid: true,
+                    coordinates: coordinates(48.0 + 7.038 / 60.0, 11.0 + 31.0 / 60.0),
+                    speed: Some(22.4 * KNOTS_TO_METERS_PER_SECOND),
+                    course: Some(84.4),
+                    date: Some(Date {
+                        year: 1994,
+                        month: 3,
+                        day: 23,
+                    }),
+                })
+            ))
+        );
+        // Southern and western hemispheres, from a multi-constellation receiver.
+        let Ok((talker, Sentence::Rmc(rmc))) =
+            parse("$GNRMC,000001,A,3351.000,S,15112.000,W,0.0,,010100,,*21")
+        else {
+            panic!("not an RMC sentence");
+        };
+        assert_eq!(talker, Talker::Gnss);
+        assert_eq!(rmc.coordinates, coordinates(-33.85, -151.2));
+        assert_eq!(rmc.course, None);
+        assert_eq!(rmc.date.unwrap().year, 2000);
+    }
+
+    #[test]
+    fn vtg() {
+        assert_eq!(
+            parse("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48"),
+            Ok((
+                Talker::Gps,
+                Sentence::Vtg(Vtg {
+                    course: Some(54.7),
+                    speed: Some(10.2 * KILOMETERS_PER_HOUR_TO_METERS_PER_SECOND),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn gsa() {
+        assert_eq!(
+            parse("$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39"),
+            Ok((
+                Talker::Gps,
+                Sentence::Gsa(Gsa {
+                    mode: FixMode::ThreeDimensional,
+                    satellites: vec![4, 5, 9, 12, 24],
+                    pdop: Some(2.5),
+                    hdop: Some(1.3),
+                    vdop: Some(2.1),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn gsv() {
+        let satellite = |id, elevation, azimuth, snr| Satellite {
+            id,
+            elevation: Some(elevation),
+            azimuth: Some(azimuth),
+            snr: Some(snr),
+        };
+        assert_eq!(
+            parse("$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75"),
+            Ok((
+                Talker::Gps,
+                Sentence::Gsv(Gsv {
+                    total: 2,
+                    number: 1,
+                    in_view: 8,
+                    satellites: vec![
+                        satellite(1, 40.0, 83.0, 46.0),
+                        satellite(2, 17.0, 308.0, 41.0),
+                        satellite(12, 7.0, 344.0, 39.0),
+                        satellite(14, 22.0, 228.0, 45.0),
+                    ],
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for (sentence, error) in [
+            (
+                "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48",
+                ParseError::Checksum,
+            ),
+            ("$GPGGA,123519*4G", ParseError::Malformed),
+            ("GPGGA,123519,4807.038,N", ParseError::Malformed),
+            ("$G", ParseError::Malformed),
+            ("$GPGGA,250000,,,,,0", ParseError::Malformed),
+            (
+                "$GPGGA,123519,4807.038,X,01131.000,E",
+                ParseError::Malformed,
+            ),
+            ("$GPRMC,123519,A,,,,,,,2303", ParseError::Malformed),
+            ("$PGRME,15.0,M,45.0,M,25.0,M", ParseError::Unsupported),
+            ("$GPZDA,201530.00,04,07,2002,00,00", ParseError::Unsupported),
+        ] {
+            assert_eq!(parse(sentence), Err(error), "{sentence}");
+        }
+    }
+
+    #[cfg(any(target_os = "linux", feature = "replay"))]
+    #[test]
+    fn assembler() {
+        let mut assembler = Assembler::default();
+        let mut push = |sentence| assembler.push(parse(sentence).unwrap().1);
+
+        // A 2D fix, whose altitude isn't reported.
+        assert!(push("$GNGSA,A,2,01,02,03,,,,,,,,,,1.8,1.0,1.5*21").is_none());
+        assert!(push("$GNGGA,000001,3351.000,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*58").is_none());
+        let location = push("$GNRMC,000001,A,3351.000,S,15112.000,W,0.0,,010100,,*21")
+            .unwrap()
+            .inner;
+        assert_eq!(Some(location.coordinates), coordinates(-33.85, -151.2));
+        assert_eq!(location.altitude, None);
+        assert_eq!(location.horizontal_accuracy, Some(UERE));
+        assert_eq!(location.vertical_accuracy, None);
+        assert_eq!(
+            location.time,
+            Date {
+                year: 2000,
+                month: 1,
+                day: 1
+            }
+            .at(Duration::from_secs(1))
+        );
+
+        // Epochs without a fix are skipped.
+        assert!(push("$GPGGA,000002,3351.000,S,15112.000,W,0,00,,,M,,M,,*52").is_none());
+        assert!(push("$GPRMC,000002,V,,,,,,,010100,,*33").is_none());
+
+        // The date of the previous epoch is kept.
+        assert!(push("$GNGSA,A,3,01,02,03,,,,,,,,,,1.8,1.0,1.5").is_none());
+        assert!(push("$GNGGA,000003,3351.060,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*5C").is_none());
+        let location = push("$GNRMC,000003,A,3351.060,S,15112.000,W,10.0,180.0,010100,,*33")
+            .unwrap()
+            .inner;
+        assert_eq!(location.altitude, Some(10.0));
+        assert_eq!(location.vertical_accuracy, Some(1.5 * UERE));
+        assert_eq!(location.speed, Some(10.0 * KNOTS_TO_METERS_PER_SECOND));
+        assert_eq!(location.bearing, Some(180.0));
+        assert!(location.time.is_some());
+        assert!(assembler.finish().is_none());
+    }
+
+    #[cfg(any(target_os = "linux", feature = "replay"))]
+    #[test]
+    fn assembler_without_rmc() {
+        // Receivers that only send GGA complete an epoch when the next one starts,
+        // or when the input ends.
+        let mut assembler = Assembler::default();
+        let mut push = |sentence| assembler.push(parse(sentence).unwrap().1);
+        assert!(push("$GPGGA,000001,3351.000,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,").is_none());
+        let location = push("$GPGGA,000002,3351.060,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,")
+            .unwrap()
+            .inner;
+        assert_eq!(Some(location.coordinates), coordinates(-33.85, -151.2));
+        // Without a date, the time of the fix isn't known.
+        assert_eq!(location.time, None);
+        let location = assembler.finish().unwrap().inner;
+        assert_eq!(Some(location.coordinates), coordinates(-33.851, -151.2));
+    }
+}
@@ -0,0 +1,881 @@
+//! A backend that replays a recorded track through a [`Manager`].
+//!
+//! Tracks can be loaded from GPX files, NMEA 0183 logs or CSV files. Points are
+//! delivered at the pace they were recorded, optionally sped up or slowed down,
+//! and [`Location::time`] returns the recorded timestamp of each point.
+//!
+//! ```no_run
+//! # use robius_location::{replay::{Replay, Track}, *};
+//! # struct MyHandler;
+//! # impl Handler for MyHandler {
+//! #     fn handle(&self, _: Location<'_>) {}
+//! #     fn error(&self, _: Error) {}
+//! # }
+//! let replay = Replay::new(Track::load("drive.gpx").unwrap());
+//! replay.set_speed(10.0);
+//!
+//! let mut manager = Manager::with_backend(MyHandler, replay.backend()).unwrap();
+//! manager.start_updates().unwrap();
+//! ```
+//!
+//! [`Manager`]: crate::Manager
+//! [`Location::time`]: crate::Location::time
+
+use std::{
+    path::Path,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    nmea::{self, Assembler},
+    Access, Accuracy, AuthorizationStatus, Backend, Coordinates, Error, ErrorKind, Handler,
+    LocationBuilder, Result,
+};
+
+/// The spacing of points that don't have a timestamp.
+const DEFAULT_SPACING: Duration = Duration::from_secs(1);
+
+/// A recorded sequence of locations.
+#[derive(Clone, Debug)]
+pub struct Track {
+    points: Vec<Point>,
+}
+
+#[derive(Clone, Debug)]
+struct Point {
+    /// The time since the start of the track at which the point was recorded.
+    offset: Duration,
+    location: LocationBuilder,
+}
+
+impl Track {
+    /// Creates a track from locations in the order they were recorded.
+    ///
+    /// Locations are spaced according to their timestamps, or one second after the
+    /// previous location if they don't have one.
+    pub fn new<I>(locations: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = LocationBuilder>,
+    {
+        let mut points: Vec<Point> = Vec::new();
+        let mut start = None;
+        for location in locations {
+            let previous = points.last().map(|point| point.offset);
+            let offset = match (location.inner.time, start) {
+                (Some(time), Some(start)) => time
+                    .duration_since(start)
+                    .unwrap_or_default()
+                    .max(previous.unwrap_or_default()),
+                (Some(time), None) => {
+                    // Points before the first timestamp are kept at their spacing
+                    // by anchoring the start of the track before it.
+                    let offset = previous.map_or(Duration::ZERO, |p| p + DEFAULT_SPACING);
+                    start = Some(time.checked_sub(offset).unwrap_or(time));
+                    offset
+                }
+                (None, _) => previous.map_or(Duration::ZERO, |p| p + DEFAULT_SPACING),
+            };
+            points.push(Point { offset, location });
+        }
+
+        if points.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidConfiguration)
+                .with_message("the track contains no usable points"));
+        }
+        Ok(Self { points })
+    }
+
+    /// Loads a track from a file, choosing the format by its extension.
+    ///
+    /// Files ending in `.gpx` are read as GPX and files ending in `.csv` as CSV.
+    /// Anything else is read as an NMEA log.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("gpx") => Self::from_gpx(&contents),
+            Some(e) if e.eq_ignore_ascii_case("csv") => Self::from_csv(&contents),
+            _ => Self::from_nmea(&contents),
+        }
+    }
+
+    /// Parses the track points (`<trkpt>`) of a GPX document.
+    ///
+    /// Besides the coordinates, the `<ele>`, `<time>`, `<hdop>` and `<vdop>` elements
+    /// are read, as well as the `<speed>` and `<course>` elements written by many
+    /// loggers.
+    pub fn from_gpx(gpx: &str) -> Result<Self> {
+        let mut locations = Vec::new();
+        let mut rest = gpx;
+        while let Some(start) = rest.find("<trkpt") {
+            rest = &rest[start + "<trkpt".len()..];
+            let tag_end = rest.find('>').ok_or_else(|| unterminated("<trkpt"))?;
+            let (attributes, body) = if rest[..tag_end].ends_with('/') {
+                (&rest[..tag_end - 1], "")
+            } else {
+                let end = rest
+                    .find("</trkpt>")
+                    .ok_or_else(|| unterminated("<trkpt>"))?;
+                (&rest[..tag_end], &rest[tag_end + 1..end])
+            };
+            rest = &rest[tag_end + 1..];
+
+            let coordinates = Coordinates {
+                latitude: parse(attribute(attributes, "lat"))?,
+                longitude: parse(attribute(attributes, "lon"))?,
+            }
+            .validate()?;
+            let mut location = LocationBuilder::new(coordinates);
+            location.inner.altitude = element(body, "ele").and_then(|s| s.parse().ok());
+            location.inner.speed = element(body, "speed").and_then(|s| s.parse().ok());
+            location.inner.bearing = element(body, "course").and_then(|s| s.parse().ok());
+            location.inner.time = element(body, "time").and_then(crate::time::parse_iso8601);
+            location.inner.horizontal_accuracy = element(body, "hdop")
+                .and_then(|s| s.parse::<f64>().ok())
+                .map(|hdop| hdop * nmea::UERE);
+            location.inner.vertical_accuracy = element(body, "vdop")
+                .and_then(|s| s.parse::<f64>().ok())
+                .map(|vdop| vdop * nmea::UERE);
+            locations.push(location);
+        }
+        Self::new(locations)
+    }
+
+    /// Parses an NMEA 0183 log.
+    ///
+    /// Sentences that can't be parsed are skipped, as logs captured from a device
+    /// commonly contain some.
+    pub fn from_nmea(log: &str) -> Result<Self> {
+        let mut assembler = Assembler::default();
+        let mut locations: Vec<_> = log
+            .lines()
+            .filter_map(|line| nmea::parse(line).ok())
+            .filter_map(|(_, sentence)| assembler.push(sentence))
+            .collect();
+        locations.extend(assembler.finish());
+        Self::new(locations)
+    }
+
+    /// Parses CSV with the columns `time, latitude, longitude, altitude, speed,
+    /// bearing`.
+    ///
+    /// Times are either ISO 8601 timestamps or seconds since the Unix epoch. Only the
+    /// latitude and longitude are required; other columns may be empty or omitted. A
+    /// header row and lines starting with `#` are ignored.
+    pub fn from_csv(csv: &str) -> Result<Self> {
+        let mut locations = Vec::new();
+        let mut first = true;
+        for line in csv.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Only the first row that isn't a comment can be the header.
+            let header = std::mem::replace(&mut first, false);
+
+            let mut fields = line.split(',').map(str::trim);
+            let mut next = || fields.next().filter(|field| !field.is_empty());
+            let time = next();
+            let (latitude, longitude) = (next(), next());
+            if header && latitude.is_some_and(|latitude| latitude.parse::<f64>().is_err()) {
+                // The header row.
+                continue;
+            }
+
+            let mut location = LocationBuilder::new(Coordinates {
+                latitude: parse(latitude)?,
+                longitude: parse(longitude)?,
+            });
+            location.inner.altitude = next().map(|field| parse(Some(field))).transpose()?;
+            location.inner.speed = next().map(|field| parse(Some(field))).transpose()?;
+            location.inner.bearing = next().map(|field| parse(Some(field))).transpose()?;
+            location.inner.time = time.map(parse_time).transpose()?;
+            locations.push(location);
+        }
+        Self::new(locations)
+    }
+
+    /// Returns the number of points in the track.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Always returns `false`, as tracks can't be empty.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns the time between the first and last point.
+    pub fn duration(&self) -> Duration {
+        self.points
+            .last()
+            .map_or(Duration::ZERO, |point| point.offset)
+    }
+}
+
+fn parse(field: Option<&str>) -> Result<f64> {
+    let field = field.ok_or_else(|| Error::new(ErrorKind::Parse).with_message("missing number"))?;
+    field.parse().map_err(|e| {
+        Error::new(ErrorKind::Parse)
+            .with_message(format!("invalid number {field:?}"))
+            .with_source(e)
+    })
+}
+
+fn parse_time(field: &str) -> Result<SystemTime> {
+    if let Some(time) = crate::time::parse_iso8601(field) {
+        return Ok(time);
+    }
+    field
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .and_then(|seconds| SystemTime::UNIX_EPOCH.checked_add(seconds))
+        .ok_or_else(|| Error::new(ErrorKind::Parse).with_message(format!("invalid time {field:?}")))
+}
+
+fn unterminated(element: &str) -> Error {
+    Error::new(ErrorKind::Parse).with_message(format!("unterminated {element} element"))
+}
+
+/// Returns the value of an XML attribute in the contents of a start tag.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    loop {
+        let start = rest.find(name)?;
+        let preceded_by_space = rest[..start].ends_with(char::is_whitespace);
+        rest = &rest[start + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        if !preceded_by_space {
+            continue;
+        }
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+}
+
+/// Returns the text of the first child element with the given name.
+fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    let start = body.find(&open)? + open.len();
+    let end = body[start..].find(&close)? + start;
+    Some(body[start..end].trim())
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+struct State {
+    track: Track,
+    handler: Option<Arc<dyn Handler>>,
+    /// The index of the next point to deliver.
+    index: usize,
+    /// The position in the track at `anchor`.
+    position: Duration,
+    anchor: Instant,
+    speed: f64,
+    looping: bool,
+    paused: bool,
+    /// Whether the user asked for continuous updates.
+    continuous: bool,
+    /// Whether a single update is pending.
+    once: bool,
+    /// Whether the end of the track has been reported to the handler.
+    ended: bool,
+    /// Whether the backend has been dropped.
+    closed: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            track: Track { points: Vec::new() },
+            handler: None,
+            index: 0,
+            position: Duration::ZERO,
+            anchor: Instant::now(),
+            speed: 1.0,
+            looping: false,
+            paused: false,
+            continuous: false,
+            once: false,
+            ended: false,
+            closed: false,
+        }
+    }
+}
+
+impl State {
+    fn is_playing(&self) -> bool {
+        self.continuous && !self.paused && !self.ended
+    }
+
+    /// Returns the time played from the start of the track, which keeps increasing
+    /// past its end while a looping replay waits to restart.
+    fn elapsed(&self) -> Duration {
+        if self.is_playing() {
+            let elapsed = self.anchor.elapsed().as_secs_f64() * self.speed;
+            self.position + Duration::try_from_secs_f64(elapsed).unwrap_or(Duration::MAX / 2)
+        } else {
+            self.position
+        }
+    }
+
+    /// Returns the current position in the track.
+    fn position(&self) -> Duration {
+        self.elapsed().min(self.track.duration())
+    }
+
+    /// Fixes the current position, which must be done before changing anything that
+    /// affects the playback clock.
+    fn reanchor(&mut self) {
+        self.position = self.elapsed();
+        self.anchor = Instant::now();
+    }
+
+    /// Returns the most recently passed point, or the first point if none has been
+    /// passed yet.
+    fn current(&self) -> &Point {
+        &self.track.points[self.index.saturating_sub(1)]
+    }
+}
+
+/// The control handle for a replay backend.
+///
+/// Clones of the handle refer to the same backend.
+#[derive(Clone)]
+pub struct Replay {
+    shared: Arc<Shared>,
+}
+
+impl Replay {
+    /// Creates a replay of `track`, which is paused at its start until
+    /// updates are started.
+    pub fn new(track: Track) -> Self {
+        let shared = Arc::new(Shared::default());
+        lock(&shared).track = track;
+        Self { shared }
+    }
+
+    /// Returns the backend constructor to pass to
+    /// [`Manager::with_backend`](crate::Manager::with_backend).
+    pub fn backend(&self) -> impl FnOnce(Arc<dyn Handler>) -> Result<ReplayBackend> {
+        let shared = self.shared.clone();
+        move |handler| {
+            {
+                let mut state = lock(&shared);
+                state.handler = Some(handler);
+                state.closed = false;
+            }
+            let thread = {
+                let shared = shared.clone();
+                std::thread::spawn(move || play(&shared))
+            };
+            Ok(ReplayBackend {
+                shared,
+                thread: Some(thread),
+            })
+        }
+    }
+
+    /// Pauses playback.
+    pub fn pause(&self) {
+        let mut state = lock(&self.shared);
+        state.reanchor();
+        state.paused = true;
+    }
+
+    /// Resumes playback after [`pause`](Self::pause).
+    pub fn resume(&self) {
+        let mut state = lock(&self.shared);
+        state.reanchor();
+        state.paused = false;
+        self.shared.condvar.notify_all();
+    }
+
+    /// Moves playback to `position`, measured from the start of the track.
+    ///
+    /// The point at `position`, if any, is delivered next. Seeking also restarts a
+    /// replay that has reached the end of its track.
+    pub fn seek(&self, position: Duration) {
+        let mut state = lock(&self.shared);
+        let position = position.min(state.track.duration());
+        state.position = position;
+        state.anchor = Instant::now();
+        state.index = state
+            .track
+            .points
+            .partition_point(|point| point.offset < position);
+        state.ended = false;
+        self.shared.condvar.notify_all();
+    }
+
+    /// Sets the playback speed multiplier, e.g. `2.0` to replay twice as fast as the
+    /// track was recorded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` isn't a positive, finite number.
+    pub fn set_speed(&self, speed: f64) {
+        assert!(
+            speed.is_finite() && speed > 0.0,
+            "replay speed must be positive and finite"
+        );
+        let mut state = lock(&self.shared);
+        state.reanchor();
+        state.speed = speed;
+        self.shared.condvar.notify_all();
+    }
+
+    /// Sets whether playback restarts from the beginning at the end of the track,
+    /// rather than stopping. Looping replays never report the end of the track.
+    ///
+    /// The first point is replayed after the average time between points has passed
+    /// since the last one. Tracks whose points all have the same time can't loop,
+    /// and stop at the end as usual.
+    pub fn set_looping(&self, looping: bool) {
+        lock(&self.shared).looping = looping;
+        self.shared.condvar.notify_all();
+    }
+
+    /// Returns the current position, measured from the start of the track.
+    pub fn position(&self) -> Duration {
+        lock(&self.shared).position()
+    }
+
+    /// Returns whether the end of the track has been reached.
+    pub fn is_finished(&self) -> bool {
+        lock(&self.shared).ended
+    }
+}
+
+/// The backend half of a [`Replay`].
+pub struct ReplayBackend {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Backend for ReplayBackend {
+    fn request_authorization(&self, _access: Access, _accuracy: Accuracy) -> Result<()> {
+        Ok(())
+    }
+
+    fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        // Recorded tracks don't need authorization.
+        Ok(AuthorizationStatus::Background(Accuracy::Precise))
+    }
+
+    fn update_once(&self) -> Result<()> {
+        lock(&self.shared).once = true;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+
+    fn cancel_update_once(&self) -> Result<()> {
+        lock(&self.shared).once = false;
+        Ok(())
+    }
+
+    fn start_updates(&mut self) -> Result<()> {
+        let mut state = lock(&self.shared);
+        state.reanchor();
+        state.continuous = true;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+
+    fn stop_updates(&mut self) -> Result<()> {
+        let mut state = lock(&self.shared);
+        state.reanchor();
+        state.continuous = false;
+        Ok(())
+    }
+}
+
+impl Drop for ReplayBackend {
+    fn drop(&mut self) {
+        {
+            let mut state = lock(&self.shared);
+            state.reanchor();
+            state.closed = true;
+            state.continuous = false;
+            state.handler = None;
+        }
+        self.shared.condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            // The manager may be dropped by the handler on the playback thread itself.
+            if thread.thread().id() != std::thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Delivers the points of the track until the backend is dropped.
+fn play(shared: &Shared) {
+    let mut state = lock(shared);
+    loop {
+        if state.closed {
+            return;
+        }
+        let Some(handler) = state.handler.clone() else {
+            return;
+        };
+
+        if state.once {
+            state.once = false;
+            let location = state.current().location.clone();
+            // The lock must not be held while calling the handler, as it may call back
+            // into the manager.
+            drop(state);
+            handler.handle(location.build());
+            state = lock(shared);
+            continue;
+        }
+
+        if !state.is_playing() {
+            state = wait(shared, state, None);
+            continue;
+        }
+
+        let Some(point) = state.track.points.get(state.index) else {
+            let duration = state.track.duration();
+            // Tracks whose points were all recorded at once can't loop, as they would
+            // be replayed over and over without pause.
+            if state.looping && !duration.is_zero() {
+                // The track restarts after the average spacing of its points, as if
+                // the first one followed the last.
+                let spacing = duration.div_f64((state.track.len() - 1) as f64);
+                let elapsed = state.elapsed();
+                if elapsed >= duration + spacing {
+                    state.index = 0;
+                    state.position = Duration::ZERO;
+                    state.anchor = Instant::now();
+                } else {
+                    let remaining = (duration + spacing - elapsed).as_secs_f64() / state.speed;
+                    let timeout = Duration::try_from_secs_f64(remaining).unwrap_or(Duration::MAX);
+                    state = wait(shared, state, Some(timeout));
+                }
+            } else {
+                state.reanchor();
+                state.ended = true;
+                drop(state);
+                handler.finished();
+                state = lock(shared);
+            }
+            continue;
+        };
+
+        let position = state.position();
+        if point.offset <= position {
+            let location = point.location.clone();
+            state.index += 1;
+            drop(state);
+            handler.handle(location.build());
+            state = lock(shared);
+        } else {
+            let remaining = (point.offset - position).as_secs_f64() / state.speed;
+            let timeout = Duration::try_from_secs_f64(remaining).unwrap_or(Duration::MAX);
+            state = wait(shared, state, Some(timeout));
+        }
+    }
+}
+
+fn wait<'a>(
+    shared: &'a Shared,
+    state: MutexGuard<'a, State>,
+    timeout: Option<Duration>,
+) -> MutexGuard<'a, State> {
+    match timeout {
+        Some(timeout) => shared
+            .condvar
+            .wait_timeout(state, timeout)
+            .map(|(state, _)| state)
+            .unwrap_or_else(|e| e.into_inner().0),
+        None => shared
+            .condvar
+            .wait(state)
+            .unwrap_or_else(|e| e.into_inner()),
+    }
+}
+
+fn lock(shared: &Shared) -> MutexGuard<'_, State> {
+    shared.state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::{event::LocationEvent, LocationSnapshot, Manager};
+
+    /// How long to wait for an event that should arrive.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn at(latitude: f64, longitude: f64) -> LocationBuilder {
+        LocationBuilder::new(Coordinates {
+            latitude,
+            longitude,
+        })
+    }
+
+    fn offsets(track: &Track) -> Vec<Duration> {
+        track.points.iter().map(|point| point.offset).collect()
+    }
+
+    fn secs(secs: &[f64]) -> Vec<Duration> {
+        secs.iter().copied().map(Duration::from_secs_f64).collect()
+    }
+
+    fn start(replay: &Replay) -> (Manager, Receiver<LocationEvent>) {
+        let (sender, events) = mpsc::channel();
+        let handler = crate::event::FnHandler(move |event| {
+            let _ = sender.send(event);
+        });
+        let manager = Manager::with_backend(handler, replay.backend()).unwrap();
+        (manager, events)
+    }
+
+    fn next_location(events: &Receiver<LocationEvent>) -> LocationSnapshot {
+        match events.recv_timeout(TIMEOUT).unwrap() {
+            LocationEvent::Location(location) => location,
+            event => panic!("expected a location, got {event:?}"),
+        }
+    }
+
+    #[test]
+    fn spacing() {
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let track = Track::new([
+            // Points before the first timestamp keep their spacing.
+            at(0.0, 0.0),
+            at(0.0, 0.1),
+            at(0.0, 0.2).time(epoch),
+            at(0.0, 0.3).time(epoch + Duration::from_millis(2_500)),
+            // Points out of order are delivered right after the previous one.
+            at(0.0, 0.4).time(epoch),
+            at(0.0, 0.5),
+        ])
+        .unwrap();
+        assert_eq!(offsets(&track), secs(&[0.0, 1.0, 2.0, 4.5, 4.5, 5.5]));
+        assert_eq!(track.duration(), Duration::from_secs_f64(5.5));
+        assert_eq!(track.len(), 6);
+
+        let error = Track::new([]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    fn gpx() {
+        let track = Track::from_gpx(
+            r#"<?xml version="1.0"?>
+            <gpx version="1.1" creator="test">
+              <trk><name>Run</name><trkseg>
+                <trkpt lat="48.8582" lon="2.2945">
+                  <ele>35.5</ele>
+                  <time>2024-05-01T12:00:00Z</time>
+                  <hdop>1.5</hdop>
+                  <vdop>2</vdop>
+                  <speed>3.2</speed>
+                  <course>90</course>
+                </trkpt>
+                <trkpt lon='2.2950' lat='48.8585'><time>2024-05-01T12:00:05Z</time></trkpt>
+                <trkpt lat="48.8590" lon="2.2955"/>
+              </trkseg></trk>
+            </gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(offsets(&track), secs(&[0.0, 5.0, 6.0]));
+
+        let first = &track.points[0].location.inner;
+        assert_eq!(first.coordinates.latitude, 48.8582);
+        assert_eq!(first.coordinates.longitude, 2.2945);
+        assert_eq!(first.altitude, Some(35.5));
+        assert_eq!(first.horizontal_accuracy, Some(1.5 * nmea::UERE));
+        assert_eq!(first.vertical_accuracy, Some(2.0 * nmea::UERE));
+        assert_eq!(first.speed, Some(3.2));
+        assert_eq!(first.bearing, Some(90.0));
+        assert_eq!(
+            first.time,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_564_800))
+        );
+        let second = &track.points[1].location.inner;
+        assert_eq!(second.coordinates.longitude, 2.2950);
+        assert_eq!(second.altitude, None);
+        assert_eq!(track.points[2].location.inner.time, None);
+
+        for (gpx, kind) in [
+            ("<gpx></gpx>", ErrorKind::InvalidConfiguration),
+            (r#"<trkpt lon="2"/>"#, ErrorKind::Parse),
+            (r#"<trkpt xlat="1" lon="2"/>"#, ErrorKind::Parse),
+            (r#"<trkpt lat="x" lon="2"/>"#, ErrorKind::Parse),
+            (r#"<trkpt lat="91" lon="2"/>"#, ErrorKind::OutOfRange),
+            (r#"<trkpt lat="1" lon="2""#, ErrorKind::Parse),
+            (r#"<trkpt lat="1" lon="2"><ele>1</ele>"#, ErrorKind::Parse),
+        ] {
+            let error = Track::from_gpx(gpx).unwrap_err();
+            assert_eq!(error.kind(), kind, "{gpx}");
+        }
+    }
+
+    #[test]
+    fn nmea() {
+        let track = Track::from_nmea(
+            "$GPGGA,120000,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+             garbage\n\
+             $GPRMC,120000,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W\n\
+             $GPGGA,120002,4807.100,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+             $GPRMC,120002,A,4807.100,N,01131.000,E,022.4,084.4,230394,003.1,W\n\
+             $GPGGA,120003,4807.200,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n",
+        )
+        .unwrap();
+        // The last epoch has no RMC, so it is completed at the end of the log,
+        // without a time.
+        assert_eq!(offsets(&track), secs(&[0.0, 2.0, 3.0]));
+        let first = &track.points[0].location.inner;
+        assert!((first.coordinates.latitude - 48.1173).abs() < 1e-6);
+        assert_eq!(first.altitude, Some(545.4));
+        assert!(first.time.is_some());
+
+        let error = Track::from_nmea("garbage\n$GPGSV,1,1,00\n").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidConfiguration);
+    }
+
+    #[test]
+    fn csv() {
+        let track = Track::from_csv(
+            "# Recorded on a bike.\n\
+             \n\
+             time, latitude, longitude, altitude, speed, bearing\n\
+             1700000000, 48.1, 11.5, 500, 4.5, 270\n\
+             2023-11-14T22:13:30Z, 48.2, 11.6\n\
+             # Paused.\n\
+             , 48.3, 11.7, , 0\n",
+        )
+        .unwrap();
+        assert_eq!(offsets(&track), secs(&[0.0, 10.0, 11.0]));
+        let first = &track.points[0].location.inner;
+        assert_eq!(
+            first.time,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(first.altitude, Some(500.0));
+        assert_eq!(first.speed, Some(4.5));
+        assert_eq!(first.bearing, Some(270.0));
+        let last = &track.points[2].location.inner;
+        assert_eq!(last.coordinates.latitude, 48.3);
+        assert_eq!(last.altitude, None);
+        assert_eq!(last.speed, Some(0.0));
+        assert_eq!(last.time, None);
+
+        // Without a header, the first row is a point.
+        let track = Track::from_csv("1.5, 48.1, 11.5\n").unwrap();
+        assert_eq!(track.points[0].location.inner.coordinates.latitude, 48.1);
+
+        for csv in [
+            "",
+            "# Only a comment.\ntime, latitude, longitude\n",
+            "0, 48.1, 11.5\ntime, latitude, longitude\n",
+            "0, 48.1\n",
+            "0, 48.1, 11.5, high\n",
+            "yesterday, 48.1, 11.5\n",
+        ] {
+            assert!(Track::from_csv(csv).is_err(), "{csv:?}");
+        }
+    }
+
+    #[test]
+    fn pacing() {
+        let track = Track::new([at(0.0, 0.0), at(0.0, 1.0), at(0.0, 2.0)]).unwrap();
+        let replay = Replay::new(track);
+        // A second of the track passes every 50 ms.
+        replay.set_speed(20.0);
+        let (mut manager, events) = start(&replay);
+
+        // The replay is paused until updates are started, but answers single
+        // updates with its current point.
+        manager.update_once().unwrap();
+        assert_eq!(next_location(&events).coordinates.longitude, 0.0);
+        assert_eq!(replay.position(), Duration::ZERO);
+
+        let started = Instant::now();
+        manager.start_updates().unwrap();
+        for longitude in [0.0, 1.0, 2.0] {
+            assert_eq!(next_location(&events).coordinates.longitude, longitude);
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+        assert!(matches!(
+            events.recv_timeout(TIMEOUT),
+            Ok(LocationEvent::Finished)
+        ));
+        assert!(replay.is_finished());
+        assert_eq!(replay.position(), Duration::from_secs(2));
+
+        // Seeking restarts the replay from the point at the position.
+        replay.seek(Duration::from_millis(1_500));
+        assert!(!replay.is_finished());
+        assert_eq!(next_location(&events).coordinates.longitude, 2.0);
+        assert!(matches!(
+            events.recv_timeout(TIMEOUT),
+            Ok(LocationEvent::Finished)
+        ));
+    }
+
+    #[test]
+    fn looping() {
+        let track = Track::new([at(0.0, 0.0), at(0.0, 1.0)]).unwrap();
+        let replay = Replay::new(track);
+        replay.set_speed(50.0);
+        replay.set_looping(true);
+        let (mut manager, events) = start(&replay);
+        manager.start_updates().unwrap();
+
+        // The end of the track is never reported.
+        for longitude in [0.0, 1.0, 0.0, 1.0, 0.0] {
+            assert_eq!(next_location(&events).coordinates.longitude, longitude);
+        }
+        assert!(!replay.is_finished());
+
+        // Once looping is turned off, the replay ends with the track.
+        replay.set_looping(false);
+        while !matches!(
+            events.recv_timeout(TIMEOUT).unwrap(),
+            LocationEvent::Finished
+        ) {}
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn looping_without_duration() {
+        let time = SystemTime::UNIX_EPOCH;
+        let track = Track::new([at(0.0, 0.0).time(time), at(0.0, 1.0).time(time)]).unwrap();
+        let replay = Replay::new(track);
+        replay.set_looping(true);
+        let (mut manager, events) = start(&replay);
+        manager.start_updates().unwrap();
+
+        next_location(&events);
+        next_location(&events);
+        assert!(matches!(
+            events.recv_timeout(TIMEOUT),
+            Ok(LocationEvent::Finished)
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+//! Single updates requested with [`Manager::update_once`], which can be cancelled
+//! and are matched to the location or error that answers them.
+//!
+//! [`Manager::update_once`]: crate::Manager::update_once
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use crate::{Result, Shared};
+
+/// Identifies a request made with [`Manager::update_once`].
+///
+/// The location or error that answers the request carries its ID, see
+/// [`Location::request_id`], [`LocationSnapshot::request`] and
+/// [`Error::request_id`]. Malformed data skipped by the backend doesn't answer the
+/// request.
+///
+/// [`Manager::update_once`]: crate::Manager::update_once
+/// [`Location::request_id`]: crate::Location::request_id
+/// [`LocationSnapshot::request`]: crate::LocationSnapshot::request
+/// [`Error::request_id`]: crate::Error::request_id
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+/// A pending request made with [`Manager::update_once`].
+///
+/// Dropping the handle doesn't cancel the request.
+///
+/// [`Manager::update_once`]: crate::Manager::update_once
+pub struct RequestHandle {
+    shared: Arc<Shared>,
+    id: RequestId,
+}
+
+impl RequestHandle {
+    pub(crate) fn new(shared: Arc<Shared>, id: RequestId) -> Self {
+        Self { shared, id }
+    }
+
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// Cancels the request, so that no location or error is attributed to it.
+    ///
+    /// Requests made while another one is pending share its ID, and the backend
+    /// only stops looking for a location once all of them have been cancelled.
+    /// Backends that can't cancel a request still deliver the location to the
+    /// handler, without an ID. Cancelling a request that was already answered
+    /// does nothing.
+    pub fn cancel(self) -> Result<()> {
+        self.shared.cancel_once(self.id)
+    }
+}
+
+/// The single update the next location or error delivered by the backend answers.
+#[derive(Default)]
+pub(crate) struct Pending {
+    /// The ID of the pending request and the number of callers still waiting for
+    /// it.
+    request: Mutex<Option<(RequestId, usize)>>,
+    next_id: AtomicU64,
+}
+
+impl Pending {
+    /// Adds a caller to the pending request, creating one if there is none.
+    pub(crate) fn begin(&self) -> Option<RequestId> {
+        let mut request = self.request.lock().ok()?;
+        let (id, waiting) = request
+            .get_or_insert_with(|| (RequestId(self.next_id.fetch_add(1, Ordering::Relaxed)), 0));
+        *waiting += 1;
+        Some(*id)
+    }
+
+    /// Removes a caller from the request, returning whether it was the last one, in
+    /// which case the request is no longer pending.
+    pub(crate) fn cancel(&self, id: RequestId) -> bool {
+        let Ok(mut request) = self.request.lock() else {
+            return false;
+        };
+        match &mut *request {
+            Some((pending, waiting)) if *pending == id => {
+                *waiting -= 1;
+                if *waiting == 0 {
+                    *request = None;
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Marks the pending request as answered, returning its ID.
+    pub(crate) fn take(&self) -> Option<RequestId> {
+        self.request.lock().ok()?.take().map(|(id, _)| id)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use crate::{
+        event::FnHandler,
+        mock::{Call, Mock},
+        Coordinates, ErrorKind, LocationBuilder, LocationEvent, Manager, UpdateRequest,
+    };
+
+    fn manager() -> (Manager, Mock, Receiver<LocationEvent>) {
+        let mock = Mock::new();
+        let (sender, events) = mpsc::channel();
+        let manager = Manager::with_backend(
+            FnHandler(move |event| {
+                let _ = sender.send(event);
+            }),
+            mock.backend(),
+        )
+        .unwrap();
+        (manager, mock, events)
+    }
+
+    fn fix() -> crate::Location<'static> {
+        LocationBuilder::new(Coordinates {
+            latitude: 52.0,
+            longitude: 13.0,
+        })
+        .build()
+    }
+
+    #[test]
+    fn cancellation() {
+        let (manager, mock, events) = manager();
+
+        let first = manager.update_once().unwrap();
+        let second = manager.update_once().unwrap();
+        assert_eq!(first.id(), second.id());
+        assert_eq!(mock.calls(), [Call::UpdateOnce, Call::UpdateOnce]);
+
+        // The backend keeps looking while a caller is still waiting.
+        first.cancel().unwrap();
+        assert_eq!(mock.calls().last(), Some(&Call::UpdateOnce));
+        let id = second.id();
+        second.cancel().unwrap();
+        assert_eq!(mock.calls().last(), Some(&Call::CancelUpdateOnce));
+
+        // A location delivered anyway doesn't answer the cancelled request.
+        mock.push(fix());
+        let LocationEvent::Location(location) = events.try_recv().unwrap() else {
+            panic!("expected a location");
+        };
+        assert_eq!(location.request, None);
+
+        // The next request gets a new ID.
+        let next = manager.update_once().unwrap();
+        assert_ne!(next.id(), id);
+        // Cancelling an answered request does nothing.
+        mock.push(fix());
+        mock.clear_calls();
+        next.cancel().unwrap();
+        assert_eq!(mock.calls(), []);
+    }
+
+    #[test]
+    fn id_propagation() {
+        let (manager, mock, events) = manager();
+        let (sender, subscribed) = mpsc::channel();
+        let _subscription = manager
+            .subscribe(
+                FnHandler(move |event| {
+                    let _ = sender.send(event);
+                }),
+                UpdateRequest::default(),
+            )
+            .unwrap();
+
+        let request = manager.update_once().unwrap();
+        // Malformed data doesn't answer the request.
+        mock.push_error(ErrorKind::Parse);
+        let LocationEvent::Error(error) = events.try_recv().unwrap() else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.request_id(), None);
+
+        mock.push(fix());
+        let LocationEvent::Location(location) = events.try_recv().unwrap() else {
+            panic!("expected a location");
+        };
+        assert_eq!(location.request, Some(request.id()));
+        // Listeners see the ID too.
+        let location = std::iter::from_fn(|| subscribed.try_recv().ok())
+            .find_map(|event| match event {
+                LocationEvent::Location(location) => Some(location),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(location.request, Some(request.id()));
+
+        // Other errors answer the request.
+        let request = manager.update_once().unwrap();
+        mock.push_error(ErrorKind::Network);
+        let LocationEvent::Error(error) = events.try_recv().unwrap() else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.request_id(), Some(request.id()));
+        mock.push(fix());
+        let LocationEvent::Location(location) = events.try_recv().unwrap() else {
+            panic!("expected a location");
+        };
+        assert_eq!(location.request, None);
+    }
+}
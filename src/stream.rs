@@ -0,0 +1,157 @@
+//! An asynchronous stream of location updates, see [`Manager::updates`].
+//!
+//! [`Manager::updates`]: crate::Manager::updates
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+
+use crate::{dispatch::Listener, Error, ErrorKind, LocationSnapshot, Result, Shared};
+
+/// Which items an [`Updates`] stream drops when its buffer is full.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the oldest buffered item to make room for the new one, so that the
+    /// stream always yields the most recent locations.
+    #[default]
+    DropOldest,
+    /// Drop the new item, so that the stream yields every location up to the point
+    /// the buffer filled up.
+    DropNewest,
+}
+
+/// A stream of location updates, created by [`Manager::updates`].
+///
+/// [`Manager::updates`]: crate::Manager::updates
+pub struct Updates {
+    shared: Arc<Shared>,
+    channel: Arc<Channel>,
+    started: bool,
+    done: bool,
+}
+
+impl Updates {
+    pub(crate) fn new(shared: Arc<Shared>, capacity: usize, overflow: Overflow) -> Self {
+        Self {
+            shared,
+            channel: Arc::new(Channel {
+                queue: Mutex::new(Queue {
+                    items: VecDeque::new(),
+                    capacity: capacity.max(1),
+                    overflow,
+                    waker: None,
+                    finished: false,
+                }),
+            }),
+            started: false,
+            done: false,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        // The listener is registered first so that no update delivered once updates
+        // have started is missed.
+        let listener: Arc<dyn Listener> = self.channel.clone();
+        self.shared.dispatcher.add(&listener);
+        self.shared.lock()?.acquire_stream()?;
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl Stream for Updates {
+    type Item = Result<LocationSnapshot>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if !self.started {
+            if let Err(e) = self.start() {
+                self.done = true;
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+
+        let Ok(mut queue) = self.channel.queue.lock() else {
+            return Poll::Ready(Some(Err(Error::new(ErrorKind::Unknown))));
+        };
+        if let Some(item) = queue.items.pop_front() {
+            return Poll::Ready(Some(item));
+        }
+        if queue.finished {
+            drop(queue);
+            self.done = true;
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Updates {
+    fn drop(&mut self) {
+        if self.started {
+            if let Ok(mut state) = self.shared.lock() {
+                let _ = state.release_stream();
+            }
+        }
+    }
+}
+
+struct Channel {
+    queue: Mutex<Queue>,
+}
+
+struct Queue {
+    items: VecDeque<Result<LocationSnapshot>>,
+    capacity: usize,
+    overflow: Overflow,
+    waker: Option<Waker>,
+    /// Whether the backend has no more updates to deliver.
+    finished: bool,
+}
+
+impl Channel {
+    fn push(&self, item: Result<LocationSnapshot>) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        if queue.items.len() >= queue.capacity {
+            match queue.overflow {
+                Overflow::DropOldest => {
+                    queue.items.pop_front();
+                }
+                Overflow::DropNewest => return,
+            }
+        }
+        queue.items.push_back(item);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Listener for Channel {
+    fn location(&self, location: &LocationSnapshot) {
+        self.push(Ok(location.clone()));
+    }
+
+    fn error(&self, error: Error) {
+        self.push(Err(error));
+    }
+
+    fn finished(&self) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.finished = true;
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
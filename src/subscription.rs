@@ -0,0 +1,286 @@
+//! Several consumers sharing the updates of one manager, see
+//! [`Manager::subscribe`].
+//!
+//! [`Manager::subscribe`]: crate::Manager::subscribe
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    dispatch::Listener, throttle::Throttle, AuthorizationStatus, Error, Handler, LocationSnapshot,
+    Result, Shared, UpdateRequest,
+};
+
+/// A handler receiving updates through a [`Subscription`].
+pub(crate) struct Subscriber {
+    handler: Box<dyn Handler>,
+    /// Drops the updates that the subscriber didn't ask for, as the backend runs
+    /// at the most demanding request of all subscribers.
+    throttle: Mutex<Throttle>,
+}
+
+impl Subscriber {
+    pub(crate) fn new<T>(handler: T, request: &UpdateRequest) -> Self
+    where
+        T: Handler,
+    {
+        Self {
+            handler: Box::new(handler),
+            throttle: Mutex::new(Throttle::new(request)),
+        }
+    }
+}
+
+impl Listener for Subscriber {
+    fn location(&self, location: &LocationSnapshot) {
+        let accepted = self
+            .throttle
+            .lock()
+            .is_ok_and(|mut throttle| throttle.accept(location.coordinates));
+        if accepted {
+            self.handler.handle(location.clone().into());
+        }
+    }
+
+    fn error(&self, error: Error) {
+        self.handler.error(error);
+    }
+
+    fn finished(&self) {
+        self.handler.finished();
+    }
+
+    fn authorization_changed(&self, status: AuthorizationStatus) {
+        self.handler.authorization_changed(status);
+    }
+}
+
+/// A handler's subscription to the updates of a manager, created by
+/// [`Manager::subscribe`].
+///
+/// The handler stops receiving updates when the subscription is dropped.
+///
+/// [`Manager::subscribe`]: crate::Manager::subscribe
+pub struct Subscription {
+    shared: Arc<Shared>,
+    id: u64,
+    // Keeps the subscriber registered with the dispatcher.
+    _subscriber: Arc<Subscriber>,
+}
+
+impl Subscription {
+    pub(crate) fn new(shared: Arc<Shared>, id: u64, subscriber: Arc<Subscriber>) -> Self {
+        Self {
+            shared,
+            id,
+            _subscriber: subscriber,
+        }
+    }
+
+    /// Ends the subscription, returning any error from stopping or reconfiguring
+    /// the backend.
+    ///
+    /// Dropping the subscription does the same but ignores errors.
+    pub fn unsubscribe(self) -> Result<()> {
+        self.shared.lock()?.unsubscribe(self.id)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // Does nothing if `unsubscribe` was already called.
+        if let Ok(mut state) = self.shared.lock() {
+            let _ = state.unsubscribe(self.id);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::{
+        sync::mpsc::{self, Receiver},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        event::FnHandler,
+        mock::{Call, Mock},
+        Coordinates, ErrorKind, LocationBuilder, LocationEvent, Manager, Priority,
+    };
+
+    fn handler() -> (impl Handler, Receiver<LocationEvent>) {
+        let (sender, events) = mpsc::channel();
+        let handler = FnHandler(move |event| {
+            let _ = sender.send(event);
+        });
+        (handler, events)
+    }
+
+    fn manager() -> (Manager, Mock, Receiver<LocationEvent>) {
+        let mock = Mock::new();
+        let (handler, events) = handler();
+        let manager = Manager::with_backend(handler, mock.backend()).unwrap();
+        (manager, mock, events)
+    }
+
+    fn push(mock: &Mock, latitude: f64) {
+        mock.push(
+            LocationBuilder::new(Coordinates {
+                latitude,
+                longitude: 0.0,
+            })
+            .build(),
+        );
+    }
+
+    /// Returns the latitudes of the locations received so far.
+    fn latitudes(events: &Receiver<LocationEvent>) -> Vec<f64> {
+        events
+            .try_iter()
+            .filter_map(|event| match event {
+                LocationEvent::Location(location) => Some(location.coordinates.latitude),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merging() {
+        let (mut manager, mock, _events) = manager();
+        let first = UpdateRequest {
+            interval: Duration::from_secs(10),
+            min_interval: None,
+            min_distance: 50.0,
+            priority: Priority::Balanced,
+            max_update_delay: Duration::from_secs(60),
+        };
+        let second = UpdateRequest {
+            interval: Duration::from_secs(5),
+            min_interval: Some(Duration::from_secs(2)),
+            min_distance: 100.0,
+            priority: Priority::LowPower,
+            max_update_delay: Duration::from_secs(30),
+        };
+        let merged = UpdateRequest {
+            interval: Duration::from_secs(5),
+            min_interval: Some(Duration::from_secs(2)),
+            min_distance: 50.0,
+            priority: Priority::Balanced,
+            max_update_delay: Duration::from_secs(30),
+        };
+
+        let a = manager.subscribe(handler().0, first).unwrap();
+        let b = manager.subscribe(handler().0, second).unwrap();
+        // An identical request doesn't reconfigure the backend.
+        let c = manager.subscribe(handler().0, first).unwrap();
+        assert_eq!(
+            mock.calls(),
+            [Call::StartUpdates(first), Call::StartUpdates(merged)]
+        );
+
+        mock.clear_calls();
+        drop(b);
+        assert_eq!(mock.calls(), [Call::StartUpdates(first)]);
+
+        // Updates started by the manager take part as well.
+        mock.clear_calls();
+        manager.start_updates().unwrap();
+        let merged = UpdateRequest {
+            interval: Duration::from_secs(1),
+            priority: Priority::HighAccuracy,
+            max_update_delay: Duration::ZERO,
+            min_distance: 0.0,
+            ..first
+        };
+        assert_eq!(mock.calls(), [Call::StartUpdates(merged)]);
+        mock.clear_calls();
+        manager.stop_updates().unwrap();
+        assert_eq!(mock.calls(), [Call::StartUpdates(first)]);
+        drop((a, c));
+    }
+
+    #[test]
+    fn stopping() {
+        let (mut manager, mock, _events) = manager();
+        let a = manager
+            .subscribe(handler().0, UpdateRequest::default())
+            .unwrap();
+        let b = manager
+            .subscribe(handler().0, UpdateRequest::default())
+            .unwrap();
+        assert!(mock.is_updating());
+
+        drop(a);
+        assert!(mock.is_updating());
+        b.unsubscribe().unwrap();
+        assert!(!mock.is_updating());
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::StartUpdates(UpdateRequest::default()),
+                Call::StopUpdates
+            ]
+        );
+
+        // Updates started by the manager outlive the subscriptions.
+        manager.start_updates().unwrap();
+        let c = manager
+            .subscribe(handler().0, UpdateRequest::default())
+            .unwrap();
+        drop(c);
+        assert!(mock.is_updating());
+        manager.stop_updates().unwrap();
+        assert!(!mock.is_updating());
+    }
+
+    #[test]
+    fn throttle() {
+        let (manager, mock, events) = manager();
+        let every = UpdateRequest {
+            interval: Duration::ZERO,
+            ..Default::default()
+        };
+        let (subscriber, all) = handler();
+        let _all = manager.subscribe(subscriber, every).unwrap();
+        let (subscriber, far) = handler();
+        let far_subscription = manager
+            .subscribe(
+                subscriber,
+                UpdateRequest {
+                    min_distance: 1_000.0,
+                    ..every
+                },
+            )
+            .unwrap();
+        let (subscriber, rare) = handler();
+        let _rare = manager
+            .subscribe(
+                subscriber,
+                UpdateRequest {
+                    interval: Duration::from_secs(3_600),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        // 0.001° of latitude is about 111 m.
+        for latitude in [0.0, 0.001, 0.02, 0.021] {
+            push(&mock, latitude);
+        }
+        assert_eq!(latitudes(&events), [0.0, 0.001, 0.02, 0.021]);
+        assert_eq!(latitudes(&all), [0.0, 0.001, 0.02, 0.021]);
+        assert_eq!(latitudes(&far), [0.0, 0.02]);
+        assert_eq!(latitudes(&rare), [0.0]);
+
+        // Other events aren't throttled.
+        mock.push_error(ErrorKind::TemporarilyUnavailable);
+        assert!(matches!(far.try_recv(), Ok(LocationEvent::Error(_))));
+        assert!(matches!(rare.try_recv(), Ok(LocationEvent::Error(_))));
+
+        // A dropped subscription receives nothing more.
+        drop(far_subscription);
+        push(&mock, 1.0);
+        assert!(latitudes(&far).is_empty());
+        assert_eq!(latitudes(&all), [1.0]);
+    }
+}
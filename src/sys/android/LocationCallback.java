@@ -2,17 +2,23 @@
 
 package robius.location;
 
+import android.app.Activity;
+import android.app.Application;
 import android.location.Location;
 import android.location.LocationListener;
+import android.os.Bundle;
 import java.util.function.Consumer;
 import java.util.List;
 
 /*
  * `Consumer<Location>` is implemented for `LocationManager.getCurrentLocation`.
  * `LocationListener` is implemented for `LocationManager.requestLocationUpdates`.
+ * `ActivityLifecycleCallbacks` is implemented to notice when a permission dialog is dismissed, as
+ * the activity is resumed afterwards.
  */
 
-public class LocationCallback implements Consumer<Location>, LocationListener {
+public class LocationCallback
+        implements Consumer<Location>, LocationListener, Application.ActivityLifecycleCallbacks {
     private long handlerPtrHigh;
     private long handlerPtrLow;
     private boolean executing;
@@ -24,6 +30,12 @@ public class LocationCallback implements Consumer<Location>, LocationListener {
      */
     private native void rustCallback(long handlerPtrHigh, long handlerPtrLow, Location location);
 
+    /*
+     * The name and signature of this function must be kept in sync with `RUST_RESUMED_NAME`, and
+     * `RUST_RESUMED_SIGNATURE` respectively.
+     */
+    private native void rustResumed(long handlerPtrHigh, long handlerPtrLow);
+
     public LocationCallback(long handlerPtrHigh, long handlerPtrLow) {
         this.handlerPtrHigh = handlerPtrHigh;
         this.handlerPtrLow = handlerPtrLow;
@@ -68,4 +80,24 @@ public class LocationCallback implements Consumer<Location>, LocationListener {
         }
         this.executing = false;
     }
+
+    public void onActivityResumed(Activity activity) {
+        this.executing = true;
+        if (!this.doNotExecute) {
+                rustResumed(this.handlerPtrHigh, this.handlerPtrLow);
+        }
+        this.executing = false;
+    }
+
+    public void onActivityCreated(Activity activity, Bundle savedInstanceState) {}
+
+    public void onActivityStarted(Activity activity) {}
+
+    public void onActivityPaused(Activity activity) {}
+
+    public void onActivityStopped(Activity activity) {}
+
+    public void onActivitySaveInstanceState(Activity activity, Bundle outState) {}
+
+    public void onActivityDestroyed(Activity activity) {}
 }
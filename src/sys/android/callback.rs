@@ -1,4 +1,7 @@
-use std::{marker::PhantomData, sync::OnceLock};
+use std::{
+    marker::PhantomData,
+    sync::{atomic::Ordering, OnceLock},
+};
 
 use jni::{
     objects::{GlobalRef, JClass, JObject, JValueGen},
@@ -15,6 +18,10 @@ const RUST_CALLBACK_NAME: &str = "rustCallback";
 // NOTE: This must be kept in sync with the signature of `rust_callback`, and
 // the signature specified in `LocationCallback.java`.
 const RUST_CALLBACK_SIGNATURE: &str = "(JJLandroid/location/Location;)V";
+// NOTE: These must be kept in sync with `LocationCallback.java` and the
+// signature of `rust_resumed`.
+const RUST_RESUMED_NAME: &str = "rustResumed";
+const RUST_RESUMED_SIGNATURE: &str = "(JJ)V";
 
 // NOTE: The signature of this function must be kept in sync with
 // `RUST_CALLBACK_SIGNATURE`.
@@ -25,24 +32,48 @@ unsafe extern "C" fn rust_callback<'a>(
     handler_ptr_low: jlong,
     location: JObject<'a>,
 ) {
+    // SAFETY: See `Drop` implementation for `sys::android::Manager`.
+    let inner = unsafe { inner(handler_ptr_high, handler_ptr_low) };
+
+    let location = crate::Location {
+        inner: crate::LocationInner::Sys(super::Location {
+            inner: env.new_global_ref(location).unwrap(),
+            phantom: PhantomData,
+        }),
+        request: None,
+    };
+    inner.handler.handle(location);
+}
+
+// NOTE: The signature of this function must be kept in sync with
+// `RUST_RESUMED_SIGNATURE`.
+unsafe extern "C" fn rust_resumed<'a>(
+    _: JNIEnv<'a>,
+    _: JObject<'a>,
+    handler_ptr_high: jlong,
+    handler_ptr_low: jlong,
+) {
+    // SAFETY: See `Drop` implementation for `sys::android::Manager`.
+    let inner = unsafe { inner(handler_ptr_high, handler_ptr_low) };
+
+    // Any permission dialog has been dismissed by the time the activity is resumed.
+    inner.prompting.store(false, Ordering::Release);
+    let _ = inner.refresh();
+}
+
+/// Reassembles the pointer to the state shared with the callback.
+///
+/// # Safety
+///
+/// The pointer must have been passed to `construct_callback`, and the manager
+/// owning it must not have been dropped.
+unsafe fn inner<'a>(high: jlong, low: jlong) -> &'a super::InnerHandler {
     // TODO: 32-bit? What's that?
     #[cfg(not(target_pointer_width = "64"))]
     compiler_error!("non-64-bit Android targets are not supported");
 
-    let handler_ptr: *const super::InnerHandler =
-        unsafe { std::mem::transmute([handler_ptr_high, handler_ptr_low]) };
-    // SAFETY: See `Drop` implementation for `sys::android::Manager`.
-    let handler = unsafe { &*handler_ptr };
-
-    if let Ok(handler) = handler.lock() {
-        let location = crate::Location {
-            inner: super::Location {
-                inner: env.new_global_ref(location).unwrap(),
-                phantom: PhantomData,
-            },
-        };
-        handler.handle(location);
-    }
+    let handler_ptr: *const super::InnerHandler = unsafe { std::mem::transmute([high, low]) };
+    unsafe { &*handler_ptr }
 }
 
 static CALLBACK_CLASS: OnceLock<GlobalRef> = OnceLock::new();
@@ -63,11 +94,18 @@ pub(super) fn get_callback_class(env: &mut JNIEnv<'_>) -> Result<&'static Global
 fn register_rust_callback<'a>(env: &mut JNIEnv<'a>, callback_class: &JClass<'a>) -> Result<()> {
     env.register_native_methods(
         callback_class,
-        &[NativeMethod {
-            name: RUST_CALLBACK_NAME.into(),
-            sig: RUST_CALLBACK_SIGNATURE.into(),
-            fn_ptr: rust_callback as *mut _,
-        }],
+        &[
+            NativeMethod {
+                name: RUST_CALLBACK_NAME.into(),
+                sig: RUST_CALLBACK_SIGNATURE.into(),
+                fn_ptr: rust_callback as *mut _,
+            },
+            NativeMethod {
+                name: RUST_RESUMED_NAME.into(),
+                sig: RUST_RESUMED_SIGNATURE.into(),
+                fn_ptr: rust_resumed as *mut _,
+            },
+        ],
     )
     .map_err(|e| e.into())
 }
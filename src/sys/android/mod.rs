@@ -2,7 +2,10 @@ mod callback;
 
 use std::{
     marker::PhantomData,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -11,9 +14,91 @@ use jni::{
     JNIEnv,
 };
 
-use crate::{Access, Accuracy, Coordinates, Error, Handler, Result};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Coordinates, Error, ErrorKind, Handler, Priority,
+    Result, UpdateRequest,
+};
 
-type InnerHandler = Mutex<dyn Handler>;
+type InnerHandler = Inner<dyn Handler>;
+
+const ACCESS_FINE_LOCATION: &str = "android.permission.ACCESS_FINE_LOCATION";
+const ACCESS_COARSE_LOCATION: &str = "android.permission.ACCESS_COARSE_LOCATION";
+const ACCESS_BACKGROUND_LOCATION: &str = "android.permission.ACCESS_BACKGROUND_LOCATION";
+
+/// The value of `PackageManager.PERMISSION_GRANTED`.
+const PERMISSION_GRANTED: i32 = 0;
+
+/// The request code passed to `requestPermissions`, chosen to be unlikely to clash
+/// with the application's own requests. Request codes must fit in 16 bits.
+///
+/// The result is never delivered to us, as that would require overriding
+/// `onRequestPermissionsResult` in the activity. Instead, the granted permissions
+/// are checked when the activity is resumed after the permission dialog is
+/// dismissed, and whenever the authorization status is queried.
+const PERMISSION_REQUEST_CODE: i32 = 0x524C;
+
+/// The value of `LocationRequest.PASSIVE_INTERVAL`.
+const PASSIVE_INTERVAL: i64 = i64::MAX;
+
+// The values of the `LocationRequest.QUALITY_*` constants.
+const QUALITY_HIGH_ACCURACY: i32 = 100;
+const QUALITY_BALANCED_POWER_ACCURACY: i32 = 102;
+const QUALITY_LOW_POWER: i32 = 104;
+
+const LOCATION_REQUEST_BUILDER: &str = "android/location/LocationRequest$Builder";
+
+/// The state shared with the Java callback.
+struct Inner<H: ?Sized> {
+    /// Whether authorization has been requested, which distinguishes a denial from
+    /// the user not having been asked yet.
+    requested: AtomicBool,
+    /// Whether a permission dialog may be showing, until the activity is resumed.
+    prompting: AtomicBool,
+    /// The last observed authorization status.
+    status: Mutex<Option<AuthorizationStatus>>,
+    handler: H,
+}
+
+impl InnerHandler {
+    /// Checks the granted permissions, notifying the handler if the authorization
+    /// status changed since it was last checked.
+    fn refresh(&self) -> Result<AuthorizationStatus> {
+        let status = robius_android_env::with_activity(|env, context| {
+            let accuracy = if is_granted(env, context, ACCESS_FINE_LOCATION)? {
+                Some(Accuracy::Precise)
+            } else if is_granted(env, context, ACCESS_COARSE_LOCATION)? {
+                Some(Accuracy::Approximate)
+            } else {
+                None
+            };
+
+            Ok(match accuracy {
+                Some(accuracy) if is_granted(env, context, ACCESS_BACKGROUND_LOCATION)? => {
+                    AuthorizationStatus::Background(accuracy)
+                }
+                Some(accuracy) => AuthorizationStatus::ForegroundOnly(accuracy),
+                // The user hasn't answered yet.
+                None if self.prompting.load(Ordering::Acquire) => {
+                    AuthorizationStatus::NotDetermined
+                }
+                None if self.requested.load(Ordering::Acquire) => AuthorizationStatus::Denied,
+                None => AuthorizationStatus::NotDetermined,
+            })
+        })
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
+        .and_then(|x| x)?;
+
+        let previous = self
+            .status
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?
+            .replace(status);
+        if previous.is_some_and(|previous| previous != status) {
+            self.handler.authorization_changed(status);
+        }
+        Ok(status)
+    }
+}
 
 pub struct Manager {
     callback: GlobalRef,
@@ -21,55 +106,100 @@ pub struct Manager {
     // the manager we make sure that `rust_callback` will never be called again before reboxing
     // (and hence deallocating) the handler. See the `Drop` implementation for more details.
     inner: *const InnerHandler,
+    /// The `CancellationSignal` of the pending single update, if any.
+    cancellation: Mutex<Option<GlobalRef>>,
 }
 
+// SAFETY: `inner` is owned by the manager like a `Box` would be, and points to
+// state that is `Send + Sync`, as the Java callback already accesses it from other
+// threads. `GlobalRef` is thread-safe, and the JNI environment is attached to the
+// calling thread for every call.
+unsafe impl Send for Manager {}
+unsafe impl Sync for Manager {}
+
 impl Manager {
     pub fn new<T>(handler: T) -> Result<Self>
     where
         T: Handler,
     {
-        let inner = Box::into_raw(Box::new(Mutex::new(handler)));
+        let inner: Box<InnerHandler> = Box::new(Inner {
+            requested: AtomicBool::new(false),
+            prompting: AtomicBool::new(false),
+            status: Mutex::new(None),
+            handler,
+        });
+        let inner = Box::into_raw(inner);
 
         Ok(Manager {
-            callback: robius_android_env::with_activity(|env, _| {
+            callback: robius_android_env::with_activity(|env, activity| {
                 let callback = construct_callback(env, inner)?;
+                // The activity is resumed once a permission dialog is dismissed.
+                let application = get_application(env, activity)?;
+                env.call_method(
+                    application,
+                    "registerActivityLifecycleCallbacks",
+                    "(Landroid/app/Application$ActivityLifecycleCallbacks;)V",
+                    &[JValueGen::Object(&callback)],
+                )?;
                 env.new_global_ref(callback).map_err(|e| e.into())
             })
-            .map_err(|_| Error::AndroidEnvironment)
+            .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
             .and_then(|x| x)?,
             inner,
+            cancellation: Mutex::new(None),
         })
     }
 
-    pub fn request_authorization(&self, _access: Access, accuracy: Accuracy) -> Result<()> {
-        robius_android_env::with_activity(|env, current_activity| {
-            let permissions = env.new_string(match accuracy {
-                Accuracy::Approximate => "android.permission.ACCESS_COARSE_LOCATION",
-                Accuracy::Precise => "android.permission.ACCESS_FINE_LOCATION",
-            })?;
+    fn inner(&self) -> &InnerHandler {
+        // SAFETY: The handler is only deallocated when the manager is dropped.
+        unsafe { &*self.inner }
+    }
+
+    pub fn request_authorization(&self, access: Access, accuracy: Accuracy) -> Result<()> {
+        // Since Android 11, background access has to be requested separately after
+        // foreground access has been granted.
+        let permission = match (access, self.authorization_status()?) {
+            (Access::Background, AuthorizationStatus::ForegroundOnly(_)) => {
+                ACCESS_BACKGROUND_LOCATION
+            }
+            _ => match accuracy {
+                Accuracy::Approximate => ACCESS_COARSE_LOCATION,
+                Accuracy::Precise => ACCESS_FINE_LOCATION,
+            },
+        };
+        self.inner().requested.store(true, Ordering::Release);
+        self.inner().prompting.store(true, Ordering::Release);
 
+        robius_android_env::with_activity(|env, current_activity| {
+            let permissions = env.new_string(permission)?;
             let array = env.new_object_array(1, "java/lang/String", permissions)?;
-            // TODO: Ideally we would provide functionality to wait for for authorization.
-            let request_code = 3;
 
             env.call_method(
                 current_activity,
                 "requestPermissions",
                 "([Ljava/lang/String;I)V",
-                &[JValueGen::Object(&array), JValueGen::Int(request_code)],
+                &[
+                    JValueGen::Object(&array),
+                    JValueGen::Int(PERMISSION_REQUEST_CODE),
+                ],
             )?;
 
             Ok(())
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
+    pub fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        self.inner().refresh()
+    }
+
     pub fn update_once(&self) -> Result<()> {
         robius_android_env::with_activity(|env, context| {
             let manager = get_location_manager(env, context)?;
             let provider = env.new_string("fused")?;
             let executor = get_executor(env, context)?;
+            let signal = env.new_object("android/os/CancellationSignal", "()V", &[])?;
 
             env.call_method(
                 manager,
@@ -78,23 +208,45 @@ impl Manager {
                  Ljava/util/function/Consumer;)V",
                 &[
                     JValueGen::Object(&provider),
-                    JValueGen::Object(&JObject::null()),
+                    JValueGen::Object(&signal),
                     JValueGen::Object(&executor),
                     JValueGen::Object(&self.callback),
                 ],
             )?;
 
+            let signal = env.new_global_ref(signal)?;
+            *self
+                .cancellation
+                .lock()
+                .map_err(|_| Error::new(ErrorKind::Unknown))? = Some(signal);
             Ok(())
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
-    pub fn start_updates(&self) -> Result<()> {
+    pub fn cancel_update_once(&self) -> Result<()> {
+        let Some(signal) = self
+            .cancellation
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?
+            .take()
+        else {
+            return Ok(());
+        };
+        robius_android_env::with_activity(|env, _| {
+            env.call_method(&signal, "cancel", "()V", &[])?;
+            Ok(())
+        })
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
+        .and_then(|x| x)
+    }
+
+    pub fn start_updates(&self, request: UpdateRequest) -> Result<()> {
         robius_android_env::with_activity(|env, context| {
             let manager = get_location_manager(env, context)?;
             let provider = env.new_string("fused")?;
-            let request = construct_location_request(env)?;
+            let request = construct_location_request(env, &request)?;
             let executor = get_executor(env, context)?;
 
             env.call_method(
@@ -112,7 +264,7 @@ impl Manager {
 
             Ok(())
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
@@ -127,7 +279,7 @@ impl Manager {
             )?;
             Ok(())
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 }
@@ -142,6 +294,17 @@ impl Drop for Manager {
         // happens-after this method is invoked.
         self.stop_updates().unwrap();
 
+        let _ = robius_android_env::with_activity(|env, activity| {
+            let application = get_application(env, activity)?;
+            env.call_method(
+                application,
+                "unregisterActivityLifecycleCallbacks",
+                "(Landroid/app/Application$ActivityLifecycleCallbacks;)V",
+                &[JValueGen::Object(&self.callback)],
+            )?;
+            Ok::<_, Error>(())
+        });
+
         // This is just to avoid some funky race conditions with the Java function. By
         // using two variables we ensure that if our check happens to occur
         // between the function start and `this.executing = true` (in e.g.
@@ -175,6 +338,30 @@ impl Drop for Manager {
     }
 }
 
+fn is_granted(env: &mut JNIEnv<'_>, context: &JObject<'_>, permission: &str) -> Result<bool> {
+    let permission = env.new_string(permission)?;
+    let result = env
+        .call_method(
+            context,
+            "checkSelfPermission",
+            "(Ljava/lang/String;)I",
+            &[JValueGen::Object(&permission)],
+        )?
+        .i()?;
+    Ok(result == PERMISSION_GRANTED)
+}
+
+fn get_application<'a>(env: &mut JNIEnv<'a>, activity: &JObject<'_>) -> Result<JObject<'a>> {
+    env.call_method(
+        activity,
+        "getApplication",
+        "()Landroid/app/Application;",
+        &[],
+    )?
+    .l()
+    .map_err(|e| e.into())
+}
+
 fn get_location_manager<'a>(env: &mut JNIEnv<'a>, context: &JObject<'_>) -> Result<JObject<'a>> {
     let service_name = env.new_string("location")?;
 
@@ -218,15 +405,58 @@ fn construct_callback<'a>(
     .map_err(|e| e.into())
 }
 
-fn construct_location_request<'a>(env: &mut JNIEnv<'a>) -> Result<JObject<'a>> {
+fn construct_location_request<'a>(
+    env: &mut JNIEnv<'a>,
+    request: &UpdateRequest,
+) -> Result<JObject<'a>> {
+    fn millis(duration: std::time::Duration) -> i64 {
+        i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+    }
+
+    let interval = match request.priority {
+        Priority::Passive => PASSIVE_INTERVAL,
+        _ => millis(request.interval),
+    };
+    let quality = match request.priority {
+        Priority::HighAccuracy => QUALITY_HIGH_ACCURACY,
+        Priority::Balanced => QUALITY_BALANCED_POWER_ACCURACY,
+        Priority::LowPower | Priority::Passive => QUALITY_LOW_POWER,
+    };
+
     let builder = env.new_object(
-        "android/location/LocationRequest$Builder",
+        LOCATION_REQUEST_BUILDER,
         "(J)V",
-        // TODO: Don't hardcode
-        // TODO: Could use:
-        // https://developer.android.com/reference/android/location/LocationRequest#PASSIVE_INTERVAL
-        // but then we have to determine a minupdateinterval.
-        &[JValueGen::Long(1000)],
+        &[JValueGen::Long(interval)],
+    )?;
+    let returns_builder = |arg: &str| format!("({arg})L{LOCATION_REQUEST_BUILDER};");
+
+    // The setters return the builder itself, so their results can be ignored. Passive
+    // requests need an explicit minimum interval.
+    env.call_method(
+        &builder,
+        "setQuality",
+        returns_builder("I"),
+        &[JValueGen::Int(quality)],
+    )?;
+    env.call_method(
+        &builder,
+        "setMinUpdateIntervalMillis",
+        returns_builder("J"),
+        &[JValueGen::Long(millis(
+            request.min_interval.unwrap_or(request.interval),
+        ))],
+    )?;
+    env.call_method(
+        &builder,
+        "setMinUpdateDistanceMeters",
+        returns_builder("F"),
+        &[JValueGen::Float(request.min_distance as f32)],
+    )?;
+    env.call_method(
+        &builder,
+        "setMaxUpdateDelayMillis",
+        returns_builder("J"),
+        &[JValueGen::Long(millis(request.max_update_delay))],
     )?;
 
     env.call_method(
@@ -259,7 +489,7 @@ impl Location<'_> {
                 longitude,
             })
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         // Poor man's `flatten`
         .and_then(|x| x)
     }
@@ -270,7 +500,7 @@ impl Location<'_> {
                 .d()
                 .map_err(|e| e.into())
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
@@ -281,7 +511,7 @@ impl Location<'_> {
                 Err(e) => Err(e.into()),
             }
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
@@ -292,7 +522,7 @@ impl Location<'_> {
                 Err(e) => Err(e.into()),
             }
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
     }
 
@@ -303,14 +533,45 @@ impl Location<'_> {
                 Err(e) => Err(e.into()),
             }
         })
-        .map_err(|_| Error::AndroidEnvironment)
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
         .and_then(|x| x)
         .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs))
     }
+
+    pub fn horizontal_accuracy(&self) -> Result<f64> {
+        self.optional_float("hasAccuracy", "getAccuracy")
+    }
+
+    pub fn vertical_accuracy(&self) -> Result<f64> {
+        self.optional_float("hasVerticalAccuracy", "getVerticalAccuracyMeters")
+    }
+
+    pub fn speed_accuracy(&self) -> Result<f64> {
+        self.optional_float("hasSpeedAccuracy", "getSpeedAccuracyMetersPerSecond")
+    }
+
+    pub fn bearing_accuracy(&self) -> Result<f64> {
+        self.optional_float("hasBearingAccuracy", "getBearingAccuracyDegrees")
+    }
+
+    /// Calls the float getter `get` if the boolean method `has` returns true.
+    fn optional_float(&self, has: &str, get: &str) -> Result<f64> {
+        robius_android_env::with_activity(|env, _| {
+            if !env.call_method(&self.inner, has, "()Z", &[])?.z()? {
+                return Err(Error::new(ErrorKind::TemporarilyUnavailable));
+            }
+            match env.call_method(&self.inner, get, "()F", &[])?.f() {
+                Ok(value) => Ok(value as f64),
+                Err(e) => Err(e.into()),
+            }
+        })
+        .map_err(|_| Error::new(ErrorKind::AndroidEnvironment))
+        .and_then(|x| x)
+    }
 }
 
 impl From<jni::errors::Error> for Error {
-    fn from(_: jni::errors::Error) -> Self {
-        Error::Unknown
+    fn from(e: jni::errors::Error) -> Self {
+        Error::new(ErrorKind::AndroidEnvironment).with_source(e)
     }
 }
@@ -5,7 +5,7 @@ use objc2_core_location::{CLError, CLLocation, CLLocationManager, CLLocationMana
 use objc2_foundation::{NSArray, NSError, NSObject, NSObjectProtocol};
 
 use super::Location;
-use crate::{Error, Handler};
+use crate::{Error, ErrorKind, Handler};
 
 type InnerHandler = dyn Handler;
 
@@ -30,13 +30,10 @@ define_class!(
             locations: &NSArray<CLLocation>,
         ) {
             for location in locations.iter() {
-                self.ivars().handler.handle(
-                    crate::Location {
-                        inner: Location {
-                            inner: &location,
-                        },
-                    }
-                );
+                self.ivars().handler.handle(crate::Location {
+                    inner: crate::LocationInner::Sys(Location { inner: &location }),
+                    request: None,
+                });
             }
 
             // for i in 0..locations.len() {
@@ -49,31 +46,42 @@ define_class!(
             // }
         }
 
+        #[unsafe(method(locationManagerDidChangeAuthorization:))]
+        #[allow(non_snake_case)]
+        unsafe fn locationManagerDidChangeAuthorization(&self, manager: &CLLocationManager) {
+            self.ivars()
+                .handler
+                .authorization_changed(super::authorization_status(manager));
+        }
+
         #[unsafe(method(locationManager:didFailWithError:))]
         #[allow(non_snake_case)]
         unsafe fn locationManager_didFailWithError(&self, _: &CLLocationManager, error: &NSError) {
-            self.ivars().handler.error(match CLError(error.code()) {
+            let kind = match CLError(error.code()) {
                 // kCLErrorLocationUnknown
-                CLError::LocationUnknown => Error::TemporarilyUnavailable,
+                CLError::LocationUnknown => ErrorKind::TemporarilyUnavailable,
                 // kCLErrorDenied
-                CLError::Denied => Error::AuthorizationDenied,
+                CLError::Denied => ErrorKind::AuthorizationDenied,
                 // kCLErrorNetwork
-                CLError::Network => Error::Network,
-                _ => Error::Unknown,
-            })
+                CLError::Network => ErrorKind::Network,
+                _ => ErrorKind::Unknown,
+            };
+            self.ivars().handler.error(
+                Error::new(kind)
+                    .with_code(error.code() as i64)
+                    .with_message(error.localizedDescription().to_string()),
+            )
         }
     }
 );
 
-
 impl RobiusLocationDelegate {
     /// Allocates a new `RobiusLocationDelegate` and initializes it with the given handler
     /// to be called upon location updates and errors.
     pub(super) fn new<T: Handler>(mtm: MainThreadMarker, handler: T) -> Retained<Self> {
-        let this = Self::alloc(mtm)
-            .set_ivars(Ivars {
-                handler: Box::new(handler),
-            });
+        let this = Self::alloc(mtm).set_ivars(Ivars {
+            handler: Box::new(handler),
+        });
         unsafe { msg_send![super(this), init] }
     }
 }
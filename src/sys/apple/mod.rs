@@ -1,21 +1,38 @@
 mod delegate;
 
-use std::time::{Duration, SystemTime};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
 
 use delegate::RobiusLocationDelegate as Delegate;
 use objc2::{rc::Retained, runtime::ProtocolObject};
 use objc2_core_location::{
-    CLLocation, CLLocationCoordinate2D, CLLocationManager, CLLocationManagerDelegate,
+    kCLDistanceFilterNone, kCLLocationAccuracyBest, kCLLocationAccuracyHundredMeters,
+    kCLLocationAccuracyKilometer, kCLLocationAccuracyThreeKilometers, CLAccuracyAuthorization,
+    CLAuthorizationStatus, CLLocation, CLLocationCoordinate2D, CLLocationManager,
+    CLLocationManagerDelegate,
 };
 
-use crate::{Access, Accuracy, Coordinates, Handler, Result};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Coordinates, Handler, Priority, Result, UpdateRequest,
+};
 
 pub(crate) struct Manager {
     inner: Retained<CLLocationManager>,
     // We must not to drop the Delegate/handler until the manager itself is dropped.
     _delegate: Retained<ProtocolObject<dyn CLLocationManagerDelegate>>,
+    /// Whether continuous updates have been started.
+    continuous: AtomicBool,
 }
 
+// SAFETY: `CLLocationManager` may be messaged from any thread, and calls it delivers
+// to its delegate are made on the run loop of the thread it was created on, which
+// `new` ensures is the main thread. The delegate is only retained and released
+// here, which is thread-safe, and the handler it holds is `Send + Sync`.
+unsafe impl Send for Manager {}
+unsafe impl Sync for Manager {}
+
 impl Manager {
     pub(crate) fn new<T>(handler: T) -> Result<Self>
     where
@@ -24,40 +41,95 @@ impl Manager {
         // Although `CLLocationManager::new()` does not require a MainThreadMarker,
         // it actually does require that it is initialized from the main thread.
         let mtm = objc2::MainThreadMarker::new()
-            .ok_or(crate::Error::NotMainThread)?;
+            .ok_or(crate::Error::new(crate::ErrorKind::NotMainThread))?;
         let inner = unsafe { CLLocationManager::new() };
         let delegate = ProtocolObject::from_retained(Delegate::new(mtm, handler));
         unsafe { inner.setDelegate(Some(&delegate)) };
         Ok(Self {
             inner,
             _delegate: delegate,
+            continuous: AtomicBool::new(false),
         })
     }
 
     pub(crate) fn request_authorization(&self, access: Access, _: Accuracy) -> Result<()> {
         match access {
-            Access::Foreground => unsafe { self.inner.requestWhenInUseAuthorization(); },
-            Access::Background => unsafe { self.inner.requestAlwaysAuthorization(); },
+            Access::Foreground => unsafe {
+                self.inner.requestWhenInUseAuthorization();
+            },
+            Access::Background => unsafe {
+                self.inner.requestAlwaysAuthorization();
+            },
         }
         Ok(())
     }
 
+    pub(crate) fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        Ok(authorization_status(&self.inner))
+    }
+
     pub(crate) fn update_once(&self) -> Result<()> {
-        unsafe { self.inner.requestLocation(); }
+        unsafe {
+            self.inner.requestLocation();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        // Stopping updates also cancels `requestLocation`, which must not interrupt
+        // continuous updates.
+        if !self.continuous.load(Ordering::Acquire) {
+            unsafe {
+                self.inner.stopUpdatingLocation();
+            }
+        }
         Ok(())
     }
 
-    pub(crate) fn start_updates(&self) -> Result<()> {
-        unsafe { self.inner.startUpdatingLocation(); }
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        // Core Location doesn't support setting an update interval, so only the
+        // accuracy and distance filter are configured.
+        unsafe {
+            self.inner.setDesiredAccuracy(match request.priority {
+                Priority::HighAccuracy => kCLLocationAccuracyBest,
+                Priority::Balanced => kCLLocationAccuracyHundredMeters,
+                Priority::LowPower => kCLLocationAccuracyKilometer,
+                Priority::Passive => kCLLocationAccuracyThreeKilometers,
+            });
+            self.inner.setDistanceFilter(if request.min_distance > 0.0 {
+                request.min_distance
+            } else {
+                kCLDistanceFilterNone
+            });
+            self.inner.startUpdatingLocation();
+        }
+        self.continuous.store(true, Ordering::Release);
         Ok(())
     }
 
     pub(crate) fn stop_updates(&self) -> Result<()> {
-        unsafe { self.inner.stopUpdatingLocation(); }
+        self.continuous.store(false, Ordering::Release);
+        unsafe {
+            self.inner.stopUpdatingLocation();
+        }
         Ok(())
     }
 }
 
+fn authorization_status(manager: &CLLocationManager) -> AuthorizationStatus {
+    let accuracy = match unsafe { manager.accuracyAuthorization() } {
+        CLAccuracyAuthorization::ReducedAccuracy => Accuracy::Approximate,
+        _ => Accuracy::Precise,
+    };
+    match unsafe { manager.authorizationStatus() } {
+        CLAuthorizationStatus::AuthorizedAlways => AuthorizationStatus::Background(accuracy),
+        CLAuthorizationStatus::AuthorizedWhenInUse => AuthorizationStatus::ForegroundOnly(accuracy),
+        CLAuthorizationStatus::Restricted => AuthorizationStatus::Restricted,
+        CLAuthorizationStatus::Denied => AuthorizationStatus::Denied,
+        _ => AuthorizationStatus::NotDetermined,
+    }
+}
+
 pub(crate) struct Location<'a> {
     inner: &'a CLLocation,
 }
@@ -91,4 +163,30 @@ impl Location<'_> {
         let secs = unsafe { self.inner.timestamp().timeIntervalSince1970() };
         Ok(SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs))
     }
+
+    // Core Location reports a negative accuracy when the value is invalid.
+
+    pub(crate) fn horizontal_accuracy(&self) -> Result<f64> {
+        valid_accuracy(unsafe { self.inner.horizontalAccuracy() })
+    }
+
+    pub(crate) fn vertical_accuracy(&self) -> Result<f64> {
+        valid_accuracy(unsafe { self.inner.verticalAccuracy() })
+    }
+
+    pub(crate) fn speed_accuracy(&self) -> Result<f64> {
+        valid_accuracy(unsafe { self.inner.speedAccuracy() })
+    }
+
+    pub(crate) fn bearing_accuracy(&self) -> Result<f64> {
+        valid_accuracy(unsafe { self.inner.courseAccuracy() })
+    }
+}
+
+fn valid_accuracy(accuracy: f64) -> Result<f64> {
+    if accuracy >= 0.0 {
+        Ok(accuracy)
+    } else {
+        Err(crate::Error::new(crate::ErrorKind::TemporarilyUnavailable))
+    }
 }
@@ -0,0 +1,355 @@
+//! A location backend that talks to [GeoClue2][geoclue] over the system bus.
+//!
+//! [geoclue]: https://www.freedesktop.org/software/geoclue/docs/
+
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use zbus::{
+    blocking::Connection,
+    proxy::CacheProperties,
+    zvariant::{ObjectPath, OwnedObjectPath},
+};
+
+use super::{Authorization, Location};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Error, ErrorKind, Handler, Priority, Result,
+    UpdateRequest,
+};
+
+/// The `GClueAccuracyLevel` requested for [`Accuracy::Approximate`] and low power
+/// updates.
+const ACCURACY_LEVEL_CITY: u32 = 4;
+/// The `GClueAccuracyLevel` requested for balanced updates.
+const ACCURACY_LEVEL_STREET: u32 = 6;
+/// The `GClueAccuracyLevel` requested for [`Accuracy::Precise`].
+const ACCURACY_LEVEL_EXACT: u32 = 8;
+
+/// The desktop ID used when the name of the current executable can't be determined.
+const FALLBACK_DESKTOP_ID: &str = "robius-location";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.GeoClue2.Manager",
+    default_service = "org.freedesktop.GeoClue2",
+    default_path = "/org/freedesktop/GeoClue2/Manager"
+)]
+trait GeoClueManager {
+    fn get_client(&self) -> zbus::Result<OwnedObjectPath>;
+
+    fn delete_client(&self, client: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.GeoClue2.Client",
+    default_service = "org.freedesktop.GeoClue2"
+)]
+trait Client {
+    fn start(&self) -> zbus::Result<()>;
+
+    fn stop(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn location(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn set_desktop_id(&self, id: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_requested_accuracy_level(&self, level: u32) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_time_threshold(&self, seconds: u32) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_distance_threshold(&self, meters: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn location_updated(&self, old: ObjectPath<'_>, new: ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.GeoClue2.Location",
+    default_service = "org.freedesktop.GeoClue2"
+)]
+trait Location {
+    #[zbus(property)]
+    fn latitude(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn longitude(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn accuracy(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn altitude(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn speed(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn heading(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn timestamp(&self) -> zbus::Result<(u64, u64)>;
+}
+
+/// State shared between the manager and the thread listening for `LocationUpdated`.
+struct State {
+    /// Whether the user asked for continuous updates.
+    continuous: AtomicBool,
+    /// Whether a single update is pending.
+    once: AtomicBool,
+    authorization: Authorization,
+    /// The accuracy level the user requested authorization for.
+    accuracy_level: AtomicU32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            continuous: AtomicBool::new(false),
+            once: AtomicBool::new(false),
+            authorization: Authorization::new(),
+            accuracy_level: AtomicU32::new(ACCURACY_LEVEL_EXACT),
+        }
+    }
+}
+
+pub(crate) struct Manager {
+    connection: Connection,
+    client: ClientProxyBlocking<'static>,
+    client_path: OwnedObjectPath,
+    handler: Arc<dyn Handler>,
+    state: Arc<State>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl Manager {
+    pub(crate) fn new<T>(handler: T) -> Result<Self>
+    where
+        T: Handler,
+    {
+        // NOTE: `DBUS_SYSTEM_BUS_ADDRESS` is respected here, which allows pointing the
+        // manager at a private bus.
+        let connection = Connection::system()?;
+        let client_path = GeoClueManagerProxyBlocking::new(&connection)?.get_client()?;
+        let client = ClientProxyBlocking::builder(&connection)
+            .path(client_path.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()?;
+        client.set_desktop_id(&desktop_id())?;
+
+        let handler: Arc<dyn Handler> = Arc::new(handler);
+        let state = Arc::new(State::default());
+        // GeoClue defaults to `GCLUE_ACCURACY_LEVEL_NONE`, which gets no locations.
+        client.set_requested_accuracy_level(state.accuracy_level.load(Ordering::Acquire))?;
+
+        // Subscribe before spawning the thread so that no signal emitted after this
+        // function returns can be missed.
+        let updates = client.receive_location_updated()?;
+        let listener = {
+            let connection = connection.clone();
+            let client = client.clone();
+            let handler = handler.clone();
+            let state = state.clone();
+            std::thread::spawn(move || {
+                // The iterator ends once the connection is closed in `Drop`.
+                for signal in updates {
+                    let location = signal
+                        .args()
+                        .map_err(Into::into)
+                        .and_then(|args| read_location(&connection, &args.new));
+                    match location {
+                        Ok(location) => {
+                            // GeoClue only sends locations to authorized clients.
+                            state.authorization.grant(handler.as_ref());
+                            handler.handle(crate::Location {
+                                inner: crate::LocationInner::Sys(location),
+                                request: None,
+                            });
+                        }
+                        Err(e) => {
+                            // The client keeps running until a location can be read.
+                            let ends_request = e.ends_request();
+                            handler.error(e);
+                            if !ends_request {
+                                continue;
+                            }
+                        }
+                    }
+
+                    if state.once.swap(false, Ordering::AcqRel)
+                        && !state.continuous.load(Ordering::Acquire)
+                    {
+                        let _ = client.stop();
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            connection,
+            client,
+            client_path,
+            handler,
+            state,
+            listener: Some(listener),
+        })
+    }
+
+    pub(crate) fn request_authorization(&self, _access: Access, accuracy: Accuracy) -> Result<()> {
+        // GeoClue asks its agent for authorization when the client is started, so all
+        // we can do is record the accuracy that will be requested.
+        let level = match accuracy {
+            Accuracy::Approximate => ACCURACY_LEVEL_CITY,
+            Accuracy::Precise => ACCURACY_LEVEL_EXACT,
+        };
+        self.state.authorization.set_accuracy(accuracy);
+        self.state.accuracy_level.store(level, Ordering::Release);
+        self.client
+            .set_requested_accuracy_level(level)
+            .map_err(|e| e.into())
+    }
+
+    pub(crate) fn authorization_status(&self) -> AuthorizationStatus {
+        self.state.authorization.status()
+    }
+
+    pub(crate) fn update_once(&self) -> Result<()> {
+        if self.state.continuous.load(Ordering::Acquire) {
+            // The client is already running, so we can hand out the most recent location
+            // rather than waiting for the next one.
+            let path = self.client.location()?;
+            if path.as_str() != "/" {
+                let location = read_location(&self.connection, &path)?;
+                // This is called with the manager locked, so the location is delivered
+                // from another thread in case the handler calls back into the manager.
+                let handler = self.handler.clone();
+                std::thread::spawn(move || {
+                    handler.handle(crate::Location {
+                        inner: crate::LocationInner::Sys(location),
+                        request: None,
+                    })
+                });
+                return Ok(());
+            }
+        }
+
+        // Continuous updates may have lowered the accuracy level.
+        self.client
+            .set_requested_accuracy_level(self.state.accuracy_level.load(Ordering::Acquire))?;
+        self.state.once.store(true, Ordering::Release);
+        self.start()
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        if !self.state.once.swap(false, Ordering::AcqRel)
+            || self.state.continuous.load(Ordering::Acquire)
+        {
+            return Ok(());
+        }
+        self.client.stop().map_err(|e| e.into())
+    }
+
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        // GeoClue has no notion of batching, and passive clients aren't supported, so
+        // the closest it gets is a low accuracy level.
+        let level = match request.priority {
+            Priority::HighAccuracy => ACCURACY_LEVEL_EXACT,
+            Priority::Balanced => ACCURACY_LEVEL_STREET,
+            Priority::LowPower | Priority::Passive => ACCURACY_LEVEL_CITY,
+        };
+        let interval = request.min_interval.unwrap_or(request.interval);
+        self.client.set_requested_accuracy_level(
+            level.min(self.state.accuracy_level.load(Ordering::Acquire)),
+        )?;
+        self.client
+            .set_time_threshold(u32::try_from(interval.as_secs()).unwrap_or(u32::MAX))?;
+        self.client
+            .set_distance_threshold(request.min_distance as u32)?;
+
+        self.state.continuous.store(true, Ordering::Release);
+        self.start()
+    }
+
+    pub(crate) fn stop_updates(&self) -> Result<()> {
+        self.state.continuous.store(false, Ordering::Release);
+        if self.state.once.load(Ordering::Acquire) {
+            // The client will be stopped once the pending update has been delivered.
+            return Ok(());
+        }
+        self.client.stop().map_err(|e| e.into())
+    }
+
+    fn start(&self) -> Result<()> {
+        let result = self.client.start().map_err(Error::from);
+        if result
+            .as_ref()
+            .is_err_and(|e| e.kind() == ErrorKind::AuthorizationDenied)
+        {
+            // The agent refused to authorize the client.
+            self.state.authorization.deny(self.handler.as_ref());
+        }
+        result
+    }
+}
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        let _ = self.client.stop();
+        if let Ok(manager) = GeoClueManagerProxyBlocking::new(&self.connection) {
+            let _ = manager.delete_client(&self.client_path);
+        }
+        let _ = self.connection.clone().close();
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+fn read_location(connection: &Connection, path: &ObjectPath<'_>) -> Result<Location<'static>> {
+    let location = LocationProxyBlocking::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+
+    // GeoClue uses sentinel values for data that isn't known.
+    let altitude = location.altitude()?;
+    let speed = location.speed()?;
+    let heading = location.heading()?;
+    let (secs, micros) = location.timestamp()?;
+
+    Ok(Location {
+        latitude: location.latitude()?,
+        longitude: location.longitude()?,
+        altitude: (altitude != -f64::MAX).then_some(altitude),
+        bearing: (heading >= 0.0).then_some(heading),
+        speed: (speed >= 0.0).then_some(speed),
+        time: Some(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros),
+        ),
+        horizontal_accuracy: Some(location.accuracy()?),
+        // GeoClue only reports the horizontal accuracy.
+        vertical_accuracy: None,
+        speed_accuracy: None,
+        bearing_accuracy: None,
+        _phantom_data: PhantomData,
+    })
+}
+
+/// The ID GeoClue uses to look up the application's `.desktop` file when deciding
+/// whether to authorize it.
+fn desktop_id() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .unwrap_or_else(|| FALLBACK_DESKTOP_ID.to_owned())
+}
@@ -0,0 +1,433 @@
+//! A location backend that reads reports from a [gpsd] daemon using its JSON
+//! protocol.
+//!
+//! [gpsd]: https://gpsd.io/gpsd_json.html
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    marker::PhantomData,
+    net::{Shutdown, TcpStream},
+    os::unix::net::UnixStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use serde::Deserialize;
+
+use super::Location;
+use crate::{
+    nmea::UERE, throttle::Throttle, Access, Accuracy, Error, ErrorKind, Handler, Result,
+    UpdateRequest,
+};
+
+const WATCH_ENABLE: &[u8] = b"?WATCH={\"enable\":true,\"json\":true};\n";
+const WATCH_DISABLE: &[u8] = b"?WATCH={\"enable\":false};\n";
+
+/// A report sent by gpsd, distinguished by its `class` member.
+#[derive(Deserialize)]
+#[serde(tag = "class")]
+enum Report {
+    #[serde(rename = "TPV")]
+    Tpv(Box<Tpv>),
+    #[serde(rename = "SKY")]
+    Sky(Sky),
+    #[serde(other)]
+    Other,
+}
+
+/// A time-position-velocity report.
+#[derive(Deserialize)]
+struct Tpv {
+    #[serde(default)]
+    mode: u8,
+    time: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    #[serde(rename = "altMSL")]
+    alt_msl: Option<f64>,
+    alt: Option<f64>,
+    track: Option<f64>,
+    speed: Option<f64>,
+    /// The estimated horizontal error, which older versions of gpsd don't report.
+    eph: Option<f64>,
+    /// The estimated longitude error.
+    epx: Option<f64>,
+    /// The estimated latitude error.
+    epy: Option<f64>,
+    /// The estimated vertical error.
+    epv: Option<f64>,
+    /// The estimated speed error.
+    eps: Option<f64>,
+    /// The estimated track error.
+    epd: Option<f64>,
+}
+
+/// A report of the satellites in view, of which only the dilutions of precision
+/// are used.
+#[derive(Default, Deserialize)]
+struct Sky {
+    /// The horizontal dilution of precision.
+    hdop: Option<f64>,
+    /// The vertical dilution of precision.
+    vdop: Option<f64>,
+}
+
+impl Tpv {
+    /// Converts the report into a location, returning `None` if the receiver doesn't
+    /// have a fix.
+    ///
+    /// Accuracies that gpsd doesn't estimate are derived from the dilutions of
+    /// precision of the latest SKY report, as for NMEA receivers.
+    fn into_location(self, sky: &Sky) -> Option<Location<'static>> {
+        // Mode 0 means unknown and mode 1 means no fix.
+        if self.mode < 2 {
+            return None;
+        }
+
+        Some(Location {
+            latitude: self.lat?,
+            longitude: self.lon?,
+            // Altitude is only valid in a 3D fix.
+            altitude: (self.mode >= 3)
+                .then(|| self.alt_msl.or(self.alt))
+                .flatten(),
+            bearing: self.track,
+            speed: self.speed,
+            time: self.time.as_deref().and_then(crate::time::parse_iso8601),
+            horizontal_accuracy: self
+                .eph
+                .or_else(|| Some(self.epx?.hypot(self.epy?)))
+                .or(sky.hdop.map(|hdop| hdop * UERE)),
+            vertical_accuracy: self
+                .epv
+                .or(sky.vdop.map(|vdop| vdop * UERE))
+                .filter(|_| self.mode >= 3),
+            speed_accuracy: self.eps,
+            bearing_accuracy: self.epd,
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+/// A connection to gpsd, either over TCP or a Unix domain socket.
+enum Socket {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Socket {
+    fn connect(address: &str) -> io::Result<Self> {
+        if address.starts_with('/') {
+            UnixStream::connect(address).map(Socket::Unix)
+        } else {
+            TcpStream::connect(address).map(Socket::Tcp)
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Socket::Tcp(stream) => stream.try_clone().map(Socket::Tcp),
+            Socket::Unix(stream) => stream.try_clone().map(Socket::Unix),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            Socket::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            Socket::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.write(buf),
+            Socket::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.flush(),
+            Socket::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// State shared between the manager and the thread reading reports.
+struct Shared {
+    socket: Mutex<Socket>,
+    /// Whether the user asked for continuous updates.
+    continuous: AtomicBool,
+    /// Whether a single update is pending.
+    once: AtomicBool,
+    /// Whether the manager is being dropped.
+    closed: AtomicBool,
+    throttle: Mutex<Throttle>,
+}
+
+impl Shared {
+    /// Returns whether a location at `coordinates` should be delivered.
+    fn wants(&self, coordinates: crate::Coordinates) -> bool {
+        // A pending single update is delivered regardless of the throttle.
+        let once = self.once.load(Ordering::Acquire);
+        let continuous = self.continuous.load(Ordering::Acquire)
+            && self
+                .throttle
+                .lock()
+                .map(|mut throttle| throttle.accept(coordinates))
+                .unwrap_or(true);
+        once || continuous
+    }
+
+    fn watch(&self, enable: bool) -> Result<()> {
+        let mut socket = self
+            .socket
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?;
+        socket.write_all(if enable { WATCH_ENABLE } else { WATCH_DISABLE })?;
+        Ok(())
+    }
+}
+
+pub(crate) struct Manager {
+    shared: Arc<Shared>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Manager {
+    pub(crate) fn new<T>(handler: T, address: &str) -> Result<Self>
+    where
+        T: Handler,
+    {
+        let socket = Socket::connect(address)?;
+        let reader = BufReader::new(socket.try_clone()?);
+        let shared = Arc::new(Shared {
+            socket: Mutex::new(socket),
+            continuous: AtomicBool::new(false),
+            once: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            throttle: Mutex::new(Throttle::default()),
+        });
+
+        let reader = {
+            let shared = shared.clone();
+            std::thread::spawn(move || read_reports(reader, &shared, &handler))
+        };
+
+        Ok(Self {
+            shared,
+            reader: Some(reader),
+        })
+    }
+
+    pub(crate) fn request_authorization(&self, _access: Access, _accuracy: Accuracy) -> Result<()> {
+        // gpsd doesn't have a notion of authorization.
+        Ok(())
+    }
+
+    pub(crate) fn update_once(&self) -> Result<()> {
+        self.shared.once.store(true, Ordering::Release);
+        self.shared.watch(true)
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        if !self.shared.once.swap(false, Ordering::AcqRel)
+            || self.shared.continuous.load(Ordering::Acquire)
+        {
+            return Ok(());
+        }
+        self.shared.watch(false)
+    }
+
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        // gpsd reports every fix, so the request is applied by dropping updates.
+        self.shared
+            .throttle
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?
+            .configure(&request);
+        self.shared.continuous.store(true, Ordering::Release);
+        self.shared.watch(true)
+    }
+
+    pub(crate) fn stop_updates(&self) -> Result<()> {
+        self.shared.continuous.store(false, Ordering::Release);
+        if self.shared.once.load(Ordering::Acquire) {
+            // The watch will be disabled once the pending update has been delivered.
+            return Ok(());
+        }
+        self.shared.watch(false)
+    }
+}
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Ok(socket) = self.shared.socket.lock() {
+            let _ = socket.shutdown();
+        }
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn read_reports<T>(reader: BufReader<Socket>, shared: &Shared, handler: &T)
+where
+    T: Handler,
+{
+    let mut sky = Sky::default();
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        match serde_json::from_str::<Report>(&line) {
+            Ok(Report::Tpv(tpv)) => {
+                let Some(location) = tpv.into_location(&sky) else {
+                    continue;
+                };
+                if !location.coordinates().is_ok_and(|c| shared.wants(c)) {
+                    continue;
+                }
+                handler.handle(crate::Location {
+                    inner: crate::LocationInner::Sys(location),
+                    request: None,
+                });
+
+                if shared.once.swap(false, Ordering::AcqRel)
+                    && !shared.continuous.load(Ordering::Acquire)
+                {
+                    let _ = shared.watch(false);
+                }
+            }
+            // Some SKY reports only list satellites, so they don't clear the DOPs.
+            Ok(Report::Sky(report)) => {
+                sky.hdop = report.hdop.or(sky.hdop);
+                sky.vdop = report.vdop.or(sky.vdop);
+            }
+            Ok(Report::Other) => {}
+            Err(e) => handler.error(Error::new(ErrorKind::Parse).with_source(e)),
+        }
+    }
+
+    if !shared.closed.load(Ordering::Acquire) {
+        handler.error(
+            Error::new(ErrorKind::ServiceUnavailable).with_message("gpsd closed the connection"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    fn tpv(report: &str) -> Box<Tpv> {
+        match serde_json::from_str(report) {
+            Ok(Report::Tpv(tpv)) => tpv,
+            _ => panic!("not a TPV report: {report}"),
+        }
+    }
+
+    #[test]
+    fn reports() {
+        assert!(matches!(
+            serde_json::from_str(r#"{"class":"VERSION","release":"3.25","proto_major":3}"#),
+            Ok(Report::Other)
+        ));
+        assert!(matches!(
+            serde_json::from_str(r#"{"class":"SKY","hdop":1.2,"vdop":2.5,"satellites":[]}"#),
+            Ok(Report::Sky(Sky {
+                hdop: Some(1.2),
+                vdop: Some(2.5)
+            }))
+        ));
+        assert!(serde_json::from_str::<Report>(r#"{"class":"TPV","lat":"north"}"#).is_err());
+        assert!(serde_json::from_str::<Report>("?WATCH").is_err());
+    }
+
+    #[test]
+    fn three_dimensional_fix() {
+        let location = tpv(
+            r#"{"class":"TPV","mode":3,"time":"2024-05-01T12:00:00.500Z",
+            "lat":59.9139,"lon":10.7522,"altHAE":80.5,"altMSL":41.2,"track":123.4,
+            "speed":1.5,"eph":4.1,"epx":3.0,"epy":4.0,"epv":7.5,"eps":0.3,"epd":12.0}"#,
+        )
+        .into_location(&Sky::default())
+        .unwrap();
+        assert_eq!((location.latitude, location.longitude), (59.9139, 10.7522));
+        assert_eq!(location.altitude, Some(41.2));
+        assert_eq!(location.bearing, Some(123.4));
+        assert_eq!(location.speed, Some(1.5));
+        assert_eq!(
+            location.time,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_714_564_800_500))
+        );
+        // The reported horizontal error is preferred to the one per axis.
+        assert_eq!(location.horizontal_accuracy, Some(4.1));
+        assert_eq!(location.vertical_accuracy, Some(7.5));
+        assert_eq!(location.speed_accuracy, Some(0.3));
+        assert_eq!(location.bearing_accuracy, Some(12.0));
+    }
+
+    #[test]
+    fn two_dimensional_fix() {
+        let location =
+            tpv(r#"{"class":"TPV","mode":2,"lat":1.0,"lon":2.0,"alt":80.5,"epx":3.0,"epy":4.0,"epv":7.5}"#)
+                .into_location(&Sky::default())
+                .unwrap();
+        assert_eq!(location.altitude, None);
+        assert_eq!(location.horizontal_accuracy, Some(5.0));
+        assert_eq!(location.vertical_accuracy, None);
+        assert_eq!(location.time, None);
+    }
+
+    #[test]
+    fn accuracies_from_dilutions_of_precision() {
+        let sky = Sky {
+            hdop: Some(1.2),
+            vdop: Some(2.0),
+        };
+        let location = tpv(r#"{"class":"TPV","mode":3,"lat":1.0,"lon":2.0,"alt":80.5}"#)
+            .into_location(&sky)
+            .unwrap();
+        assert_eq!(location.altitude, Some(80.5));
+        assert_eq!(location.horizontal_accuracy, Some(1.2 * UERE));
+        assert_eq!(location.vertical_accuracy, Some(2.0 * UERE));
+
+        // gpsd's own estimates take precedence.
+        let location = tpv(r#"{"class":"TPV","mode":3,"lat":1.0,"lon":2.0,"eph":3.0,"epv":4.0}"#)
+            .into_location(&sky)
+            .unwrap();
+        assert_eq!(location.horizontal_accuracy, Some(3.0));
+        assert_eq!(location.vertical_accuracy, Some(4.0));
+    }
+
+    #[test]
+    fn no_fix() {
+        let sky = Sky::default();
+        for report in [
+            r#"{"class":"TPV","lat":1.0,"lon":2.0}"#,
+            r#"{"class":"TPV","mode":1,"lat":1.0,"lon":2.0}"#,
+            r#"{"class":"TPV","mode":3,"lat":1.0}"#,
+        ] {
+            assert!(tpv(report).into_location(&sky).is_none(), "{report}");
+        }
+    }
+}
@@ -0,0 +1,261 @@
+mod geoclue;
+mod gpsd;
+mod nmea;
+mod portal;
+
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Coordinates, Error, ErrorKind, Handler, Result,
+    UpdateRequest,
+};
+
+pub(crate) enum Manager {
+    GeoClue(geoclue::Manager),
+    Gpsd(gpsd::Manager),
+    Nmea(nmea::Manager),
+    Portal(portal::Manager),
+}
+
+impl Manager {
+    pub(crate) fn new<T>(handler: T) -> Result<Self>
+    where
+        T: Handler,
+    {
+        // Sandboxed applications can't talk to GeoClue directly.
+        if portal::is_sandboxed() {
+            portal::Manager::new(handler).map(Manager::Portal)
+        } else {
+            geoclue::Manager::new(handler).map(Manager::GeoClue)
+        }
+    }
+
+    pub(crate) fn with_gpsd<T>(handler: T, address: &str) -> Result<Self>
+    where
+        T: Handler,
+    {
+        gpsd::Manager::new(handler, address).map(Manager::Gpsd)
+    }
+
+    pub(crate) fn with_nmea<T>(handler: T, path: &Path) -> Result<Self>
+    where
+        T: Handler,
+    {
+        nmea::Manager::new(handler, path).map(Manager::Nmea)
+    }
+
+    pub(crate) fn request_authorization(&self, access: Access, accuracy: Accuracy) -> Result<()> {
+        match self {
+            Manager::GeoClue(manager) => manager.request_authorization(access, accuracy),
+            Manager::Gpsd(manager) => manager.request_authorization(access, accuracy),
+            Manager::Nmea(manager) => manager.request_authorization(access, accuracy),
+            Manager::Portal(manager) => manager.request_authorization(access, accuracy),
+        }
+    }
+
+    pub(crate) fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        match self {
+            Manager::GeoClue(manager) => Ok(manager.authorization_status()),
+            // Access to gpsd and devices is governed by socket and file permissions,
+            // which were checked when the manager was created.
+            Manager::Gpsd(_) | Manager::Nmea(_) => {
+                Ok(AuthorizationStatus::Background(Accuracy::Precise))
+            }
+            Manager::Portal(manager) => Ok(manager.authorization_status()),
+        }
+    }
+
+    pub(crate) fn update_once(&self) -> Result<()> {
+        match self {
+            Manager::GeoClue(manager) => manager.update_once(),
+            Manager::Gpsd(manager) => manager.update_once(),
+            Manager::Nmea(manager) => manager.update_once(),
+            Manager::Portal(manager) => manager.update_once(),
+        }
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        match self {
+            Manager::GeoClue(manager) => manager.cancel_update_once(),
+            Manager::Gpsd(manager) => manager.cancel_update_once(),
+            Manager::Nmea(manager) => manager.cancel_update_once(),
+            Manager::Portal(manager) => manager.cancel_update_once(),
+        }
+    }
+
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        match self {
+            Manager::GeoClue(manager) => manager.start_updates(request),
+            Manager::Gpsd(manager) => manager.start_updates(request),
+            Manager::Nmea(manager) => manager.start_updates(request),
+            Manager::Portal(manager) => manager.start_updates(request),
+        }
+    }
+
+    pub(crate) fn stop_updates(&self) -> Result<()> {
+        match self {
+            Manager::GeoClue(manager) => manager.stop_updates(),
+            Manager::Gpsd(manager) => manager.stop_updates(),
+            Manager::Nmea(manager) => manager.stop_updates(),
+            Manager::Portal(manager) => manager.stop_updates(),
+        }
+    }
+}
+
+/// Tracks the authorization status for backends that only learn about it from the
+/// outcome of their requests.
+///
+/// Desktop applications aren't restricted to the foreground, so authorization is
+/// always reported as [`AuthorizationStatus::Background`].
+struct Authorization {
+    state: Mutex<(AuthorizationStatus, Accuracy)>,
+}
+
+impl Authorization {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new((AuthorizationStatus::NotDetermined, Accuracy::Precise)),
+        }
+    }
+
+    fn status(&self) -> AuthorizationStatus {
+        self.state
+            .lock()
+            .map(|state| state.0)
+            .unwrap_or(AuthorizationStatus::NotDetermined)
+    }
+
+    /// Sets the accuracy that is reported once authorization is granted.
+    fn set_accuracy(&self, accuracy: Accuracy) {
+        if let Ok(mut state) = self.state.lock() {
+            state.1 = accuracy;
+        }
+    }
+
+    fn grant(&self, handler: &dyn Handler) {
+        let status = self
+            .state
+            .lock()
+            .map(|state| AuthorizationStatus::Background(state.1));
+        if let Ok(status) = status {
+            self.set(status, handler);
+        }
+    }
+
+    fn deny(&self, handler: &dyn Handler) {
+        self.set(AuthorizationStatus::Denied, handler);
+    }
+
+    fn set(&self, status: AuthorizationStatus, handler: &dyn Handler) {
+        let changed = self
+            .state
+            .lock()
+            .map(|mut state| std::mem::replace(&mut state.0, status) != status)
+            .unwrap_or(false);
+        // The lock must not be held while calling the handler, as it may call back
+        // into the manager.
+        if changed {
+            handler.authorization_changed(status);
+        }
+    }
+}
+
+pub(crate) struct Location<'a> {
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<f64>,
+    bearing: Option<f64>,
+    speed: Option<f64>,
+    time: Option<SystemTime>,
+    horizontal_accuracy: Option<f64>,
+    vertical_accuracy: Option<f64>,
+    speed_accuracy: Option<f64>,
+    bearing_accuracy: Option<f64>,
+    _phantom_data: PhantomData<&'a ()>,
+}
+
+impl Location<'_> {
+    pub(crate) fn coordinates(&self) -> Result<Coordinates> {
+        Ok(Coordinates {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        })
+    }
+
+    pub(crate) fn altitude(&self) -> Result<f64> {
+        self.altitude
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn bearing(&self) -> Result<f64> {
+        self.bearing
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn speed(&self) -> Result<f64> {
+        self.speed
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn time(&self) -> Result<SystemTime> {
+        self.time
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn horizontal_accuracy(&self) -> Result<f64> {
+        self.horizontal_accuracy
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn vertical_accuracy(&self) -> Result<f64> {
+        self.vertical_accuracy
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn speed_accuracy(&self) -> Result<f64> {
+        self.speed_accuracy
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub(crate) fn bearing_accuracy(&self) -> Result<f64> {
+        self.bearing_accuracy
+            .ok_or(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+}
+
+impl From<zbus::Error> for Error {
+    fn from(e: zbus::Error) -> Self {
+        let kind = match e {
+            zbus::Error::FDO(e) => return (*e).into(),
+            // Errors replied by services, such as GeoClue denying access, are
+            // identified by their name.
+            zbus::Error::MethodError(..) => return zbus::fdo::Error::from(e).into(),
+            // The bus itself can't be reached.
+            zbus::Error::InputOutput(_) | zbus::Error::Address(_) => ErrorKind::ServiceUnavailable,
+            zbus::Error::Variant(_) => ErrorKind::Parse,
+            _ => ErrorKind::Unknown,
+        };
+        Error::new(kind).with_source(e)
+    }
+}
+
+impl From<zbus::fdo::Error> for Error {
+    fn from(e: zbus::fdo::Error) -> Self {
+        let kind = match e {
+            zbus::fdo::Error::AccessDenied(_) | zbus::fdo::Error::AuthFailed(_) => {
+                ErrorKind::AuthorizationDenied
+            }
+            zbus::fdo::Error::ServiceUnknown(_) | zbus::fdo::Error::NameHasNoOwner(_) => {
+                ErrorKind::ServiceUnavailable
+            }
+            zbus::fdo::Error::NoNetwork(_) => ErrorKind::Network,
+            zbus::fdo::Error::InvalidArgs(_) => ErrorKind::InvalidConfiguration,
+            zbus::fdo::Error::Timeout(_) | zbus::fdo::Error::TimedOut(_) => ErrorKind::Timeout,
+            _ => ErrorKind::Unknown,
+        };
+        Error::new(kind).with_source(e)
+    }
+}
@@ -0,0 +1,211 @@
+//! A location backend that reads NMEA 0183 sentences from a serial device, file
+//! or pipe.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use crate::{
+    nmea::{self, Assembler},
+    throttle::Throttle,
+    Access, Accuracy, Coordinates, Error, ErrorKind, Handler, Result, UpdateRequest,
+};
+
+/// State shared between the manager and the thread reading sentences.
+#[derive(Default)]
+struct Shared {
+    /// Whether the user asked for continuous updates.
+    continuous: AtomicBool,
+    /// Whether a single update is pending.
+    once: AtomicBool,
+    /// Whether the manager has been dropped.
+    closed: AtomicBool,
+    throttle: Mutex<Throttle>,
+}
+
+impl Shared {
+    /// Returns whether a location at `coordinates` should be delivered, consuming a
+    /// pending single update.
+    fn wants(&self, coordinates: Coordinates) -> bool {
+        let continuous = self.continuous.load(Ordering::Acquire)
+            && self
+                .throttle
+                .lock()
+                .map(|mut throttle| throttle.accept(coordinates))
+                .unwrap_or(true);
+        // A pending single update is delivered regardless of the throttle.
+        self.once.swap(false, Ordering::AcqRel) || continuous
+    }
+}
+
+pub(crate) struct Manager {
+    shared: Arc<Shared>,
+    /// The write end of a pipe that wakes the reader when it is closed, as a
+    /// blocking read on a device can't be interrupted otherwise.
+    wake: Option<OwnedFd>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Manager {
+    pub(crate) fn new<T>(handler: T, path: &Path) -> Result<Self>
+    where
+        T: Handler,
+    {
+        let device = File::open(path)?;
+        let (woken, wake) = pipe()?;
+        let shared = Arc::new(Shared::default());
+
+        let reader = {
+            let shared = shared.clone();
+            std::thread::spawn(move || read_sentences(device, &woken, &shared, &handler))
+        };
+
+        Ok(Self {
+            shared,
+            wake: Some(wake),
+            reader: Some(reader),
+        })
+    }
+
+    pub(crate) fn request_authorization(&self, _access: Access, _accuracy: Accuracy) -> Result<()> {
+        // Access to the device is governed by file permissions, which were checked when
+        // it was opened.
+        Ok(())
+    }
+
+    pub(crate) fn update_once(&self) -> Result<()> {
+        self.shared.once.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        self.shared.once.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        // Receivers send at a fixed rate, so the request is applied by dropping
+        // updates.
+        self.shared
+            .throttle
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?
+            .configure(&request);
+        self.shared.continuous.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub(crate) fn stop_updates(&self) -> Result<()> {
+        self.shared.continuous.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        drop(self.wake.take());
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Creates a pipe, returning its read and write ends.
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two file descriptors written by `pipe2`.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `pipe2` returned two new file descriptors that nothing else owns.
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Waits until `device` can be read, returning `false` if `woken` became readable
+/// first, i.e. its write end was closed.
+fn wait(device: &File, woken: &OwnedFd) -> io::Result<bool> {
+    let mut fds = [device.as_raw_fd(), woken.as_raw_fd()].map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    // SAFETY: `fds` is an array of two initialized `pollfd`s.
+    while unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    Ok(fds[1].revents == 0)
+}
+
+fn read_sentences<T>(mut device: File, woken: &OwnedFd, shared: &Shared, handler: &T)
+where
+    T: Handler,
+{
+    let mut assembler = Assembler::default();
+    let mut handle_line = |line: &[u8]| {
+        // Devices commonly emit garbage when they are first opened, so invalid
+        // sentences are skipped rather than reported.
+        let Ok((_, sentence)) = std::str::from_utf8(line)
+            .map_err(|_| nmea::ParseError::Malformed)
+            .and_then(nmea::parse)
+        else {
+            return;
+        };
+        if let Some(location) = assembler.push(sentence) {
+            if shared.wants(location.inner.coordinates) {
+                handler.handle(location.build());
+            }
+        }
+    };
+
+    // Receivers are always sending, so sentences are parsed even when no update is
+    // wanted to keep track of the date and fix status.
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        match wait(&device, woken) {
+            Ok(true) => {}
+            // The manager has been dropped.
+            Ok(false) => return,
+            Err(_) => break,
+        }
+        let read = match device.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.iter().rposition(|&byte| byte == b'\n') {
+            buffer[..end]
+                .split(|&byte| byte == b'\n')
+                .for_each(&mut handle_line);
+            buffer.drain(..=end);
+        }
+    }
+    if !buffer.is_empty() {
+        handle_line(&buffer);
+    }
+
+    if shared.closed.load(Ordering::Acquire) {
+        return;
+    }
+    if let Some(location) = assembler.finish() {
+        if shared.wants(location.inner.coordinates) {
+            handler.handle(location.build());
+        }
+    }
+    // The device was unplugged or the input ended.
+    handler.error(Error::new(ErrorKind::TemporarilyUnavailable));
+}
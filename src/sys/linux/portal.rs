@@ -0,0 +1,407 @@
+//! A location backend that uses the [XDG desktop portal][portal] Location
+//! interface, which is the only way for sandboxed (i.e. Flatpak or Snap)
+//! applications to access location data.
+//!
+//! [portal]: https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Location.html
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use zbus::{
+    blocking::Connection,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+use super::{Authorization, Location};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Error, ErrorKind, Handler, Priority, Result,
+    UpdateRequest,
+};
+
+/// The portal accuracy level requested for [`Accuracy::Approximate`] and low power
+/// updates.
+const ACCURACY_CITY: u32 = 2;
+/// The portal accuracy level requested for balanced updates.
+const ACCURACY_STREET: u32 = 4;
+/// The portal accuracy level requested for [`Accuracy::Precise`].
+const ACCURACY_EXACT: u32 = 5;
+
+/// The response code of a successful portal request.
+const RESPONSE_SUCCESS: u32 = 0;
+
+/// Used to generate unique handle tokens.
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Location",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Portal {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn start(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn location_updated(
+        &self,
+        session_handle: ObjectPath<'_>,
+        location: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Request",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Request {
+    #[zbus(signal)]
+    fn response(&self, response: u32, results: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Session",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Session {
+    fn close(&self) -> zbus::Result<()>;
+}
+
+/// Returns whether the current process is running inside a Flatpak or Snap sandbox.
+pub(super) fn is_sandboxed() -> bool {
+    std::path::Path::new("/.flatpak-info").exists() || std::env::var_os("SNAP").is_some()
+}
+
+/// State shared between the manager and the threads listening for signals.
+struct Shared {
+    connection: Connection,
+    /// The currently running session, if any.
+    session: Mutex<Option<OwnedObjectPath>>,
+    /// The portal accuracy level the user requested authorization for.
+    accuracy: AtomicU32,
+    /// The portal accuracy level preferred by the update request, which is capped at
+    /// `accuracy`.
+    priority_accuracy: AtomicU32,
+    /// The minimum time between updates, in seconds.
+    time_threshold: AtomicU32,
+    /// The minimum distance between updates, in meters.
+    distance_threshold: AtomicU32,
+    /// Whether the user asked for continuous updates.
+    continuous: AtomicBool,
+    /// Whether a single update is pending.
+    once: AtomicBool,
+    authorization: Authorization,
+}
+
+impl Shared {
+    /// Creates and starts a session unless one is already running.
+    fn start_session(self: &Arc<Self>, handler: &Arc<dyn Handler>) -> Result<()> {
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?;
+        if session.is_some() {
+            return Ok(());
+        }
+
+        let portal = PortalProxyBlocking::new(&self.connection)?;
+        let accuracy = self
+            .accuracy
+            .load(Ordering::Acquire)
+            .min(self.priority_accuracy.load(Ordering::Acquire));
+        let session_path = portal.create_session(HashMap::from([
+            ("session_handle_token", Value::from(new_token())),
+            ("accuracy", Value::from(accuracy)),
+            (
+                "time-threshold",
+                Value::from(self.time_threshold.load(Ordering::Acquire)),
+            ),
+            (
+                "distance-threshold",
+                Value::from(self.distance_threshold.load(Ordering::Acquire)),
+            ),
+        ]))?;
+
+        // Subscribe to the response before starting so that it can't be missed.
+        let request_token = new_token();
+        let request = RequestProxyBlocking::builder(&self.connection)
+            .path(request_path(&self.connection, &request_token)?)?
+            .build()?;
+        let mut responses = request.receive_response()?;
+
+        portal.start(
+            &session_path,
+            "",
+            HashMap::from([("handle_token", Value::from(request_token))]),
+        )?;
+        *session = Some(session_path.clone());
+
+        // The user may take a while to respond to the permission dialog, so the
+        // response is handled in the background.
+        let handler = handler.clone();
+        let shared = self.clone();
+        std::thread::spawn(move || {
+            let Some(response) = responses.next() else {
+                return;
+            };
+            match response.args() {
+                Ok(args) if args.response == RESPONSE_SUCCESS => {
+                    shared.authorization.grant(handler.as_ref());
+                }
+                Ok(args) => {
+                    if shared.is_current_session(&session_path) {
+                        let _ = shared.stop_session();
+                    }
+                    shared.authorization.deny(handler.as_ref());
+                    handler.error(
+                        Error::new(ErrorKind::AuthorizationDenied).with_code(args.response.into()),
+                    );
+                }
+                Err(e) => handler.error(e.into()),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Applies an update request to new sessions, returning whether anything changed.
+    fn configure(&self, request: &UpdateRequest) -> bool {
+        let accuracy = match request.priority {
+            Priority::HighAccuracy => ACCURACY_EXACT,
+            Priority::Balanced => ACCURACY_STREET,
+            Priority::LowPower | Priority::Passive => ACCURACY_CITY,
+        };
+        let interval = request.min_interval.unwrap_or(request.interval);
+        let time_threshold = u32::try_from(interval.as_secs()).unwrap_or(u32::MAX);
+        let distance_threshold = request.min_distance as u32;
+
+        // All of the swaps must happen, so they can't be short-circuited.
+        [
+            self.priority_accuracy.swap(accuracy, Ordering::AcqRel) != accuracy,
+            self.time_threshold.swap(time_threshold, Ordering::AcqRel) != time_threshold,
+            self.distance_threshold
+                .swap(distance_threshold, Ordering::AcqRel)
+                != distance_threshold,
+        ]
+        .contains(&true)
+    }
+
+    fn stop_session(&self) -> Result<()> {
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Unknown))?;
+        match session.take() {
+            Some(path) => close_session(&self.connection, &path),
+            None => Ok(()),
+        }
+    }
+
+    fn is_current_session(&self, path: &ObjectPath<'_>) -> bool {
+        self.session
+            .lock()
+            .map(|session| session.as_deref() == Some(path))
+            .unwrap_or(false)
+    }
+}
+
+pub(crate) struct Manager {
+    shared: Arc<Shared>,
+    handler: Arc<dyn Handler>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl Manager {
+    pub(crate) fn new<T>(handler: T) -> Result<Self>
+    where
+        T: Handler,
+    {
+        let connection = Connection::session()?;
+        let updates = PortalProxyBlocking::new(&connection)?.receive_location_updated()?;
+
+        let handler: Arc<dyn Handler> = Arc::new(handler);
+        let shared = Arc::new(Shared {
+            connection,
+            session: Mutex::new(None),
+            accuracy: AtomicU32::new(ACCURACY_EXACT),
+            priority_accuracy: AtomicU32::new(ACCURACY_EXACT),
+            time_threshold: AtomicU32::new(0),
+            distance_threshold: AtomicU32::new(0),
+            continuous: AtomicBool::new(false),
+            once: AtomicBool::new(false),
+            authorization: Authorization::new(),
+        });
+
+        let listener = {
+            let shared = shared.clone();
+            let handler = handler.clone();
+            std::thread::spawn(move || {
+                // The iterator ends once the connection is closed in `Drop`.
+                for signal in updates {
+                    let args = match signal.args() {
+                        Ok(args) => args,
+                        Err(e) => {
+                            handler.error(e.into());
+                            continue;
+                        }
+                    };
+                    if !shared.is_current_session(&args.session_handle) {
+                        continue;
+                    }
+
+                    let Some(location) = read_location(&args.location) else {
+                        // The session keeps running until a location can be read.
+                        handler.error(
+                            Error::new(ErrorKind::Parse).with_message("malformed portal location"),
+                        );
+                        continue;
+                    };
+                    handler.handle(crate::Location {
+                        inner: crate::LocationInner::Sys(location),
+                        request: None,
+                    });
+
+                    if shared.once.swap(false, Ordering::AcqRel)
+                        && !shared.continuous.load(Ordering::Acquire)
+                    {
+                        let _ = shared.stop_session();
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            shared,
+            handler,
+            listener: Some(listener),
+        })
+    }
+
+    pub(crate) fn request_authorization(&self, _access: Access, accuracy: Accuracy) -> Result<()> {
+        // The portal asks the user for permission when a session is started, so all we
+        // can do is record the accuracy that will be requested.
+        self.shared.authorization.set_accuracy(accuracy);
+        self.shared.accuracy.store(
+            match accuracy {
+                Accuracy::Approximate => ACCURACY_CITY,
+                Accuracy::Precise => ACCURACY_EXACT,
+            },
+            Ordering::Release,
+        );
+        Ok(())
+    }
+
+    pub(crate) fn authorization_status(&self) -> AuthorizationStatus {
+        self.shared.authorization.status()
+    }
+
+    pub(crate) fn update_once(&self) -> Result<()> {
+        self.shared.once.store(true, Ordering::Release);
+        self.shared.start_session(&self.handler)
+    }
+
+    pub(crate) fn cancel_update_once(&self) -> Result<()> {
+        if !self.shared.once.swap(false, Ordering::AcqRel)
+            || self.shared.continuous.load(Ordering::Acquire)
+        {
+            return Ok(());
+        }
+        self.shared.stop_session()
+    }
+
+    pub(crate) fn start_updates(&self, request: UpdateRequest) -> Result<()> {
+        if self.shared.configure(&request) {
+            // Sessions can't be reconfigured, so a running one has to be replaced.
+            self.shared.stop_session()?;
+        }
+        self.shared.continuous.store(true, Ordering::Release);
+        self.shared.start_session(&self.handler)
+    }
+
+    pub(crate) fn stop_updates(&self) -> Result<()> {
+        self.shared.continuous.store(false, Ordering::Release);
+        if self.shared.once.load(Ordering::Acquire) {
+            // The session will be closed once the pending update has been delivered.
+            return Ok(());
+        }
+        // The portal has no way to pause a session, so it has to be closed.
+        self.shared.stop_session()
+    }
+}
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        let _ = self.shared.stop_session();
+        let _ = self.shared.connection.clone().close();
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+fn close_session(connection: &Connection, path: &ObjectPath<'_>) -> Result<()> {
+    SessionProxyBlocking::builder(connection)
+        .path(path)?
+        .build()?
+        .close()
+        .map_err(|e| e.into())
+}
+
+fn new_token() -> String {
+    format!(
+        "robius_location_{}",
+        NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The path of the request object the portal creates for `token`.
+fn request_path(connection: &Connection, token: &str) -> Result<OwnedObjectPath> {
+    let sender = connection
+        .unique_name()
+        .ok_or(Error::new(ErrorKind::Unknown))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    OwnedObjectPath::try_from(format!(
+        "/org/freedesktop/portal/desktop/request/{sender}/{token}"
+    ))
+    .map_err(|e| Error::new(ErrorKind::Unknown).with_source(e))
+}
+
+fn read_location(location: &HashMap<String, OwnedValue>) -> Option<Location<'static>> {
+    let get = |key: &str| location.get(key)?.downcast_ref::<f64>().ok();
+
+    // The portal uses the same sentinel values as GeoClue for data that isn't known.
+    let timestamp = location
+        .get("Timestamp")
+        .and_then(|value| <(u64, u64)>::try_from(value.try_clone().ok()?).ok());
+
+    Some(Location {
+        latitude: get("Latitude")?,
+        longitude: get("Longitude")?,
+        altitude: get("Altitude").filter(|altitude| *altitude != -f64::MAX),
+        bearing: get("Heading").filter(|heading| *heading >= 0.0),
+        speed: get("Speed").filter(|speed| *speed >= 0.0),
+        time: timestamp.map(|(secs, micros)| {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros)
+        }),
+        horizontal_accuracy: get("Accuracy"),
+        // The portal only reports the horizontal accuracy.
+        vertical_accuracy: None,
+        speed_accuracy: None,
+        bearing_accuracy: None,
+        _phantom_data: PhantomData,
+    })
+}
@@ -1,6 +1,9 @@
 use std::marker::PhantomData;
 
-use crate::{Access, Accuracy, Coordinates, Handler, Result};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Coordinates, Error, ErrorKind, Handler, Result,
+    UpdateRequest,
+};
 
 pub(crate) struct Manager;
 
@@ -9,23 +12,31 @@ impl Manager {
     where
         T: Handler,
     {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn request_authorization(&self, _access: Access, _accuracy: Accuracy) -> Result<()> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn update_once(&self) -> Result<()> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn cancel_update_once(&self) -> Result<()> {
+        Err(Error::new(ErrorKind::Unknown))
     }
 
-    pub fn start_updates(&self) -> Result<()> {
-        Err(Error::Unknown)
+    pub fn start_updates(&self, _request: UpdateRequest) -> Result<()> {
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn stop_updates(&self) -> Result<()> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 }
 
@@ -35,22 +46,38 @@ pub struct Location<'a> {
 
 impl Location<'_> {
     pub fn coordinates(&self) -> Result<Coordinates> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn altitude(&self) -> Result<f64> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn bearing(&self) -> Result<f64> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn speed(&self) -> Result<f64> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
     }
 
     pub fn time(&self) -> Result<SystemTime> {
-        Err(Error::Unknown)
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn horizontal_accuracy(&self) -> Result<f64> {
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn vertical_accuracy(&self) -> Result<f64> {
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn speed_accuracy(&self) -> Result<f64> {
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn bearing_accuracy(&self) -> Result<f64> {
+        Err(Error::new(ErrorKind::Unknown))
     }
 }
@@ -5,13 +5,18 @@ use std::{
 };
 
 use windows::{
+    core::HRESULT,
     Devices::Geolocation::{
-        Geocoordinate, GeolocationAccessStatus, Geolocator, PositionStatus, StatusChangedEventArgs,
+        Geocoordinate, GeolocationAccessStatus, Geolocator, PositionAccuracy, PositionStatus,
+        StatusChangedEventArgs,
     },
     Foundation::{EventRegistrationToken, TypedEventHandler},
 };
 
-use crate::{Access, Accuracy, Coordinates, Error, Handler, Result};
+use crate::{
+    Access, Accuracy, AuthorizationStatus, Coordinates, Error, ErrorKind, Handler, Priority,
+    Result, UpdateRequest,
+};
 
 pub(crate) struct Manager {
     inner: Arc<Geolocator>,
@@ -19,6 +24,9 @@ pub(crate) struct Manager {
     // NOTE: Technically the Mutex isn't necessary, but removing it requires some finnicky unsafe.
     rust_handler: Arc<Mutex<dyn Handler>>,
     token: Option<EventRegistrationToken>,
+    /// The authorization status, which is only known once access has been requested
+    /// or location services have been disabled.
+    status: Arc<Mutex<AuthorizationStatus>>,
 }
 
 impl Manager {
@@ -29,6 +37,8 @@ impl Manager {
         let geolocator = Arc::new(Geolocator::new()?);
         let rust_handler = Arc::new(Mutex::new(handler));
         let rust_handler_cloned = rust_handler.clone();
+        let status = Arc::new(Mutex::new(AuthorizationStatus::NotDetermined));
+        let status_cloned = status.clone();
 
         let event_handler: TypedEventHandler<Geolocator, StatusChangedEventArgs> =
             TypedEventHandler::new(
@@ -43,25 +53,30 @@ impl Manager {
                                                 handler.handle(location)
                                             }
                                         } else {
-                                            handler.error(Error::Unknown);
+                                            handler.error(Error::new(ErrorKind::Unknown));
                                         }
                                     }
                                     PositionStatus::Initializing => {}
                                     PositionStatus::NoData => {
-                                        handler.error(Error::TemporarilyUnavailable)
+                                        handler.error(Error::new(ErrorKind::TemporarilyUnavailable))
                                     }
                                     PositionStatus::Disabled => {
-                                        handler.error(Error::AuthorizationDenied)
+                                        set_status(
+                                            &status_cloned,
+                                            AuthorizationStatus::Denied,
+                                            &*handler,
+                                        );
+                                        handler.error(Error::new(ErrorKind::AuthorizationDenied))
                                     }
                                     // PositionStatus::NotInitialized => {}
                                     PositionStatus::NotAvailable => {
-                                        handler.error(Error::PermanentlyUnavailable)
+                                        handler.error(Error::new(ErrorKind::PermanentlyUnavailable))
                                     }
-                                    _ => handler.error(Error::Unknown),
+                                    _ => handler.error(Error::new(ErrorKind::Unknown)),
                                 },
-                                Err(_) => handler.error(Error::Unknown),
+                                Err(_) => handler.error(Error::new(ErrorKind::Unknown)),
                             },
-                            None => handler.error(Error::Unknown),
+                            None => handler.error(Error::new(ErrorKind::Unknown)),
                         }
                     }
 
@@ -74,15 +89,33 @@ impl Manager {
             handler: event_handler,
             rust_handler,
             token: None,
+            status,
         })
     }
 
     pub fn request_authorization(&self, _access: Access, _accuracy: Accuracy) -> Result<()> {
-        match Geolocator::RequestAccessAsync()?.get()? {
-            GeolocationAccessStatus::Allowed => Ok(()),
-            GeolocationAccessStatus::Denied => Err(Error::AuthorizationDenied),
-            _ => Err(Error::Unknown),
+        let (status, result) = match Geolocator::RequestAccessAsync()?.get()? {
+            // Windows doesn't distinguish between foreground and background access.
+            GeolocationAccessStatus::Allowed => {
+                (AuthorizationStatus::Background(Accuracy::Precise), Ok(()))
+            }
+            GeolocationAccessStatus::Denied => (
+                AuthorizationStatus::Denied,
+                Err(Error::new(ErrorKind::AuthorizationDenied)),
+            ),
+            _ => return Err(Error::new(ErrorKind::Unknown)),
+        };
+        if let Ok(handler) = self.rust_handler.lock() {
+            set_status(&self.status, status, &*handler);
         }
+        result
+    }
+
+    pub fn authorization_status(&self) -> Result<AuthorizationStatus> {
+        self.status
+            .lock()
+            .map(|status| *status)
+            .map_err(|_| Error::new(ErrorKind::Unknown))
     }
 
     pub fn update_once(&self) -> Result<()> {
@@ -106,9 +139,26 @@ impl Manager {
         Ok(())
     }
 
-    pub fn start_updates(&mut self) -> Result<()> {
-        let token = self.inner.StatusChanged(&self.handler)?;
-        self.token = Some(token);
+    pub fn cancel_update_once(&self) -> Result<()> {
+        // `GetGeopositionAsync` is awaited on a background thread and can't be
+        // interrupted, so the location is still delivered.
+        Ok(())
+    }
+
+    pub fn start_updates(&mut self, request: UpdateRequest) -> Result<()> {
+        self.inner.SetDesiredAccuracy(match request.priority {
+            Priority::HighAccuracy => PositionAccuracy::High,
+            _ => PositionAccuracy::Default,
+        })?;
+        self.inner.SetMovementThreshold(request.min_distance)?;
+        let interval = request.min_interval.unwrap_or(request.interval);
+        self.inner
+            .SetReportInterval(u32::try_from(interval.as_millis()).unwrap_or(u32::MAX))?;
+
+        // Updates may already be running, in which case only the settings change.
+        if self.token.is_none() {
+            self.token = Some(self.inner.StatusChanged(&self.handler)?);
+        }
         Ok(())
     }
 
@@ -161,22 +211,65 @@ impl Location<'_> {
         //     .Timestamp()?
         //     .UniversalTime
         //     .try_into()
-        //     .map_err(|_| Error::Unknown)?;
-        Err(Error::Unknown)
+        //     .map_err(|_| Error::new(ErrorKind::Unknown))?;
+        Err(Error::new(ErrorKind::Unknown))
+    }
+
+    pub fn horizontal_accuracy(&self) -> Result<f64> {
+        self.inner.Accuracy().map_err(|e| e.into())
+    }
+
+    pub fn vertical_accuracy(&self) -> Result<f64> {
+        self.inner.AltitudeAccuracy()?.Value().map_err(|e| e.into())
+    }
+
+    pub fn speed_accuracy(&self) -> Result<f64> {
+        // `Geocoordinate` doesn't report the accuracy of the speed.
+        Err(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+
+    pub fn bearing_accuracy(&self) -> Result<f64> {
+        // `Geocoordinate` doesn't report the accuracy of the heading.
+        Err(Error::new(ErrorKind::TemporarilyUnavailable))
+    }
+}
+
+fn set_status(
+    current: &Mutex<AuthorizationStatus>,
+    status: AuthorizationStatus,
+    handler: &dyn Handler,
+) {
+    let changed = current
+        .lock()
+        .map(|mut current| std::mem::replace(&mut *current, status) != status)
+        .unwrap_or(false);
+    if changed {
+        handler.authorization_changed(status);
     }
 }
 
 fn get_location(geolocator: &Geolocator) -> Result<crate::Location> {
     Ok(crate::Location {
-        inner: Location {
+        inner: crate::LocationInner::Sys(Location {
             inner: geolocator.GetGeopositionAsync()?.get()?.Coordinate()?,
             _phantom_data: PhantomData,
-        },
+        }),
+        request: None,
     })
 }
 
 impl From<windows::core::Error> for Error {
-    fn from(_: windows::core::Error) -> Self {
-        Error::Unknown
+    fn from(e: windows::core::Error) -> Self {
+        /// `E_ACCESSDENIED`, returned when the app isn't allowed to access location.
+        const E_ACCESSDENIED: HRESULT = HRESULT(0x8007_0005_u32 as i32);
+
+        let kind = match e.code() {
+            E_ACCESSDENIED => ErrorKind::AuthorizationDenied,
+            _ => ErrorKind::Unknown,
+        };
+        Error::new(kind)
+            .with_code(e.code().0.into())
+            .with_message(e.message())
+            .with_source(e)
     }
 }
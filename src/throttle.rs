@@ -0,0 +1,47 @@
+//! Software filtering of updates for consumers whose source runs faster than they
+//! asked for.
+
+use std::time::{Duration, Instant};
+
+use crate::{Coordinates, EarthModel, UpdateRequest};
+
+/// Drops continuous updates that arrive sooner or closer to the previous one than an
+/// [`UpdateRequest`] allows, for sources that can't be configured or are shared with
+/// more demanding consumers.
+#[derive(Default)]
+pub(crate) struct Throttle {
+    min_interval: Duration,
+    min_distance: f64,
+    /// When and where the last update was delivered.
+    last: Option<(Instant, Coordinates)>,
+}
+
+impl Throttle {
+    pub(crate) fn new(request: &UpdateRequest) -> Self {
+        let mut throttle = Self::default();
+        throttle.configure(request);
+        throttle
+    }
+
+    pub(crate) fn configure(&mut self, request: &UpdateRequest) {
+        self.min_interval = request.min_interval.unwrap_or(request.interval);
+        self.min_distance = request.min_distance;
+        self.last = None;
+    }
+
+    /// Returns whether an update at `coordinates` should be delivered, recording it
+    /// as the last update if so.
+    pub(crate) fn accept(&mut self, coordinates: Coordinates) -> bool {
+        if let Some((time, last)) = self.last {
+            // Sources rarely deliver at exactly the requested interval, so updates
+            // that are only slightly early are let through rather than halving the
+            // rate.
+            let early = time.elapsed() + self.min_interval / 10 < self.min_interval;
+            if early || last.distance_to(coordinates, EarthModel::Sphere) < self.min_distance {
+                return false;
+            }
+        }
+        self.last = Some((Instant::now(), coordinates));
+        true
+    }
+}
@@ -0,0 +1,66 @@
+//! Helpers for converting the calendar times used by location formats into
+//! [`SystemTime`]s.
+
+use std::time::{Duration, SystemTime};
+
+/// Converts a UTC calendar date to the [`SystemTime`] at midnight of that day,
+/// returning `None` if it is invalid or lies before the Unix epoch.
+pub(crate) fn from_date(year: u32, month: u32, day: u32) -> Option<SystemTime> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch from Howard Hinnant's `days_from_civil`.
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = u64::try_from(days * 86_400).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Parses an ISO 8601 timestamp such as `2005-06-08T10:34:48.283Z` or
+/// `2005-06-08T12:34:48+02:00`.
+#[cfg_attr(not(any(target_os = "linux", feature = "replay")), allow(dead_code))]
+pub(crate) fn parse_iso8601(time: &str) -> Option<SystemTime> {
+    let (date, time) = time.split_once('T')?;
+
+    // Split off the UTC offset, which is either `Z` or of the form `+hh:mm`.
+    let (time, offset) = match time.strip_suffix('Z') {
+        Some(time) => (time, 0),
+        None => {
+            let (time, sign) = time.split_at(time.rfind(['+', '-'])?);
+            let (hours, minutes) = sign[1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3_600 + minutes.parse::<i64>().ok()? * 60;
+            (
+                time,
+                if sign.starts_with('-') {
+                    -offset
+                } else {
+                    offset
+                },
+            )
+        }
+    };
+
+    let mut date = date.splitn(3, '-').map(str::parse::<u32>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    let mut time = time.splitn(3, ':');
+    let hours = time.next()?.parse::<u64>().ok()?;
+    let minutes = time.next()?.parse::<u64>().ok()?;
+    let seconds = Duration::try_from_secs_f64(time.next()?.parse().ok()?).ok()?;
+
+    let local =
+        from_date(year, month, day)? + Duration::from_secs(hours * 3_600 + minutes * 60) + seconds;
+    let offset_duration = Duration::from_secs(offset.unsigned_abs());
+    if offset < 0 {
+        local.checked_add(offset_duration)
+    } else {
+        local.checked_sub(offset_duration)
+    }
+}
@@ -0,0 +1,379 @@
+//! Detection of fixes that can't be right, such as jumps of kilometers caused by
+//! errors in Wi-Fi positioning databases, which can be dropped from the locations
+//! delivered to a manager with [`Manager::set_validation_config`], or checked on
+//! their own.
+//!
+//! Invalid fixes are reported to [`Handler::rejected`].
+//!
+//! ```
+//! # use std::time::{Duration, SystemTime};
+//! # use robius_location::{validation::*, Coordinates, LocationBuilder};
+//! let mut validator = Validator::new(ValidationConfig::default()).unwrap();
+//! let now = SystemTime::now();
+//! let fix = |latitude, seconds| {
+//!     LocationBuilder::new(Coordinates { latitude, longitude: 2.0 })
+//!         .time(now - Duration::from_secs(10) + Duration::from_secs(seconds))
+//!         .horizontal_accuracy(20.0)
+//!         .build()
+//!         .snapshot()
+//!         .unwrap()
+//! };
+//!
+//! assert_eq!(validator.check(&fix(48.0, 0)), None);
+//! // 11 km in a second.
+//! assert!(matches!(validator.check(&fix(48.1, 1)), Some(Rejection::Teleport { .. })));
+//! assert_eq!(validator.check(&fix(48.0001, 2)), None);
+//! ```
+//!
+//! [`Manager::set_validation_config`]: crate::Manager::set_validation_config
+//! [`Handler::rejected`]: crate::Handler::rejected
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Coordinates, EarthModel, Error, ErrorKind, LocationSnapshot, Result};
+
+/// How close to 0°, 0° coordinates must be to be considered a placeholder.
+const NULL_ISLAND_TOLERANCE: f64 = 1e-7;
+
+/// The checks applied by a [`Validator`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationConfig {
+    /// The fastest speed, in meters per second, at which the device can move
+    /// between fixes, beyond which the later fix is rejected. Defaults to 300 m/s,
+    /// about the speed of an airliner. Distances are reduced by the accuracy of
+    /// both fixes.
+    pub max_speed: Option<f64>,
+    /// The oldest a fix can be when it is received. Defaults to a minute.
+    pub max_age: Option<Duration>,
+    /// The largest horizontal accuracy, in meters, of a valid fix. Fixes with an
+    /// unknown accuracy are always accepted.
+    pub max_accuracy: Option<f64>,
+    /// Whether invalid fixes are still delivered after being reported, rather than
+    /// dropped. They are never kept as the last known location.
+    pub flag_only: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: Some(300.0),
+            max_age: Some(Duration::from_secs(60)),
+            max_accuracy: None,
+            flag_only: false,
+        }
+    }
+}
+
+/// Why a fix was found invalid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rejection {
+    /// The coordinates are NaN or out of range.
+    InvalidCoordinates,
+    /// The coordinates are 0°, 0°, which some sources report when they have no
+    /// fix.
+    NullIsland,
+    /// The horizontal accuracy, in meters, is worse than
+    /// [`ValidationConfig::max_accuracy`].
+    Inaccurate { accuracy: f64 },
+    /// The fix is older than [`ValidationConfig::max_age`].
+    Stale { age: Duration },
+    /// The fix was taken before the previous valid one.
+    OutOfOrder,
+    /// Reaching the fix from the previous valid one requires moving faster, in
+    /// meters per second, than [`ValidationConfig::max_speed`].
+    Teleport { speed: f64 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::InvalidCoordinates => f.write_str("invalid coordinates"),
+            Rejection::NullIsland => f.write_str("coordinates at 0°, 0°"),
+            Rejection::Inaccurate { accuracy } => write!(f, "accuracy of {accuracy} m"),
+            Rejection::Stale { age } => write!(f, "fix is {age:?} old"),
+            Rejection::OutOfOrder => f.write_str("fix is older than the previous one"),
+            Rejection::Teleport { speed } => write!(f, "implied speed of {speed} m/s"),
+        }
+    }
+}
+
+/// Checks fixes against a [`ValidationConfig`] and the previous valid fix.
+#[derive(Clone, Debug)]
+pub struct Validator {
+    config: ValidationConfig,
+    /// The previous valid fix, and when it was taken or else received.
+    last: Option<(LocationSnapshot, SystemTime)>,
+}
+
+impl Validator {
+    /// Creates a validator.
+    ///
+    /// Fails with [`ErrorKind::InvalidConfiguration`] if a limit isn't positive.
+    pub fn new(config: ValidationConfig) -> Result<Self> {
+        let positive = |value: Option<f64>| value.is_none_or(|value| value > 0.0);
+        if !positive(config.max_speed) || !positive(config.max_accuracy) {
+            return Err(Error::new(ErrorKind::InvalidConfiguration)
+                .with_message("validation limits must be positive"));
+        }
+        Ok(Self { config, last: None })
+    }
+
+    pub fn config(&self) -> &ValidationConfig {
+        &self.config
+    }
+
+    /// Forgets the previous valid fix.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Checks a fix, returning why it is invalid, if it is. Valid fixes are
+    /// remembered to check the next ones against.
+    pub fn check(&mut self, location: &LocationSnapshot) -> Option<Rejection> {
+        let rejection = self.rejection(location);
+        if rejection.is_none() {
+            let time = location.time.unwrap_or_else(SystemTime::now);
+            self.last = Some((location.clone(), time));
+        }
+        rejection
+    }
+
+    fn rejection(&self, location: &LocationSnapshot) -> Option<Rejection> {
+        let Coordinates {
+            latitude,
+            longitude,
+        } = location.coordinates;
+        if location.coordinates.validate().is_err() {
+            return Some(Rejection::InvalidCoordinates);
+        }
+        if latitude.abs() < NULL_ISLAND_TOLERANCE && longitude.abs() < NULL_ISLAND_TOLERANCE {
+            return Some(Rejection::NullIsland);
+        }
+        if let (Some(accuracy), Some(max)) =
+            (location.horizontal_accuracy, self.config.max_accuracy)
+        {
+            if accuracy > max {
+                return Some(Rejection::Inaccurate { accuracy });
+            }
+        }
+        if let (Some(time), Some(max_age)) = (location.time, self.config.max_age) {
+            let age = time.elapsed().unwrap_or_default();
+            if age > max_age {
+                return Some(Rejection::Stale { age });
+            }
+        }
+
+        let (last, last_time) = self.last.as_ref()?;
+        if let (Some(time), Some(last)) = (location.time, last.time) {
+            if time < last {
+                return Some(Rejection::OutOfOrder);
+            }
+        }
+        let max_speed = self.config.max_speed?;
+        let time = location.time.unwrap_or_else(SystemTime::now);
+        let elapsed = time
+            .duration_since(*last_time)
+            .unwrap_or_default()
+            .as_secs_f64();
+        // Only the distance the fixes can't account for must have been traveled.
+        let margin =
+            location.horizontal_accuracy.unwrap_or(0.0) + last.horizontal_accuracy.unwrap_or(0.0);
+        let distance = last
+            .coordinates
+            .distance_to(location.coordinates, EarthModel::Sphere)
+            - margin;
+        if distance > max_speed * elapsed {
+            return Some(Rejection::Teleport {
+                speed: distance / elapsed,
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocationBuilder;
+
+    /// A fix at the given coordinates, taken `secs_ago` seconds ago.
+    fn fix(latitude: f64, longitude: f64, secs_ago: u64) -> LocationSnapshot {
+        LocationBuilder::new(Coordinates {
+            latitude,
+            longitude,
+        })
+        .time(SystemTime::now() - Duration::from_secs(secs_ago))
+        .horizontal_accuracy(10.0)
+        .build()
+        .snapshot()
+        .unwrap()
+    }
+
+    fn validator() -> Validator {
+        Validator::new(ValidationConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn teleport() {
+        let mut validator = validator();
+        assert_eq!(validator.check(&fix(48.0, 2.0, 20)), None);
+        // 1.1 km in ten seconds, less the accuracies, is 108 m/s.
+        assert_eq!(validator.check(&fix(48.01, 2.0, 10)), None);
+        // 111 km in ten seconds.
+        let Some(Rejection::Teleport { speed }) = validator.check(&fix(49.01, 2.0, 0)) else {
+            panic!("expected a teleport");
+        };
+        assert!((speed - 11_117.0).abs() < 10.0, "{speed}");
+        // The rejected fix isn't remembered.
+        assert_eq!(validator.check(&fix(48.01, 2.0, 0)), None);
+
+        validator.reset();
+        assert_eq!(validator.check(&fix(0.1, 2.0, 0)), None);
+    }
+
+    #[test]
+    fn teleport_within_accuracy() {
+        let mut validator = validator();
+        let fix = |latitude, accuracy| {
+            LocationBuilder::new(Coordinates {
+                latitude,
+                longitude: 2.0,
+            })
+            .time(SystemTime::now())
+            .horizontal_accuracy(accuracy)
+            .build()
+            .snapshot()
+            .unwrap()
+        };
+        assert_eq!(validator.check(&fix(48.0, 10.0)), None);
+        // Jumping 1.1 km at once is within the accuracy of a coarse fix.
+        assert_eq!(validator.check(&fix(48.01, 2000.0)), None);
+        assert_eq!(validator.check(&fix(48.0, 10.0)), None);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut validator = validator();
+        assert_eq!(validator.check(&fix(48.0, 2.0, 5)), None);
+        assert_eq!(
+            validator.check(&fix(48.0, 2.0, 10)),
+            Some(Rejection::OutOfOrder)
+        );
+    }
+
+    #[test]
+    fn stale() {
+        let mut validator = validator();
+        assert!(matches!(
+            validator.check(&fix(48.0, 2.0, 120)),
+            Some(Rejection::Stale { age }) if age >= Duration::from_secs(120)
+        ));
+        assert_eq!(validator.check(&fix(48.0, 2.0, 30)), None);
+
+        let mut validator = Validator::new(ValidationConfig {
+            max_age: None,
+            ..ValidationConfig::default()
+        })
+        .unwrap();
+        assert_eq!(validator.check(&fix(48.0, 2.0, 3600)), None);
+    }
+
+    #[test]
+    fn null_island() {
+        let mut validator = validator();
+        assert_eq!(
+            validator.check(&fix(0.0, 0.0, 0)),
+            Some(Rejection::NullIsland)
+        );
+        assert_eq!(
+            validator.check(&fix(-0.0, 1e-8, 0)),
+            Some(Rejection::NullIsland)
+        );
+        // Close to, but not at, 0°, 0°.
+        assert_eq!(validator.check(&fix(0.0, 0.001, 0)), None);
+    }
+
+    #[test]
+    fn invalid_coordinates() {
+        let mut validator = validator();
+        for (latitude, longitude) in [(f64::NAN, 2.0), (91.0, 2.0), (48.0, 181.0)] {
+            assert_eq!(
+                validator.check(&fix(latitude, longitude, 0)),
+                Some(Rejection::InvalidCoordinates)
+            );
+        }
+    }
+
+    #[test]
+    fn inaccurate() {
+        let mut validator = Validator::new(ValidationConfig {
+            max_accuracy: Some(5.0),
+            ..ValidationConfig::default()
+        })
+        .unwrap();
+        assert_eq!(
+            validator.check(&fix(48.0, 2.0, 0)),
+            Some(Rejection::Inaccurate { accuracy: 10.0 })
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        for config in [
+            ValidationConfig {
+                max_speed: Some(0.0),
+                ..ValidationConfig::default()
+            },
+            ValidationConfig {
+                max_accuracy: Some(-1.0),
+                ..ValidationConfig::default()
+            },
+        ] {
+            let error = Validator::new(config).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidConfiguration);
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn dropped_location_answers_the_pending_request() {
+        use std::sync::mpsc;
+
+        use crate::{event::FnHandler, mock::Mock, LocationEvent, Manager};
+
+        let mock = Mock::new();
+        let (send, events) = mpsc::channel();
+        let manager = Manager::with_backend(
+            FnHandler(move |event: LocationEvent| {
+                let _ = send.send(event);
+            }),
+            mock.backend(),
+        )
+        .unwrap();
+        manager
+            .set_validation_config(Some(ValidationConfig::default()))
+            .unwrap();
+
+        let request = manager.update_once().unwrap();
+        mock.push(
+            LocationBuilder::new(Coordinates {
+                latitude: 0.0,
+                longitude: 0.0,
+            })
+            .build(),
+        );
+        let rejected = events.try_recv().unwrap();
+        assert!(matches!(
+            rejected,
+            LocationEvent::Rejected(_, Rejection::NullIsland)
+        ));
+        let LocationEvent::Error(error) = events.try_recv().unwrap() else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.kind(), ErrorKind::TemporarilyUnavailable);
+        assert_eq!(error.request_id(), Some(request.id()));
+    }
+}
@@ -0,0 +1,114 @@
+//! Helpers shared by the Linux backend tests, which drive the backends through
+//! `Manager` against stand-ins for the services they talk to.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use robius_location::{
+    AuthorizationStatus, Error, Handler, Location, LocationEvent, LocationSnapshot,
+};
+
+/// How long to wait for an event before failing.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A private D-Bus daemon, which is killed when dropped.
+pub struct Bus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl Bus {
+    /// Starts a bus, returning `None` if `dbus-daemon` isn't installed.
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Self {
+            daemon,
+            address: address.trim().to_owned(),
+        })
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// A handler that sends every event to a channel, for managers that can't be
+/// created with `Manager::channel`.
+pub struct Events(Sender<LocationEvent>);
+
+impl Events {
+    pub fn new() -> (Self, Receiver<LocationEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self(sender), receiver)
+    }
+}
+
+impl Handler for Events {
+    fn handle(&self, location: Location<'_>) {
+        let _ = self.0.send(match location.snapshot() {
+            Ok(location) => LocationEvent::Location(location),
+            Err(e) => LocationEvent::Error(e),
+        });
+    }
+
+    fn error(&self, error: Error) {
+        let _ = self.0.send(LocationEvent::Error(error));
+    }
+
+    fn authorization_changed(&self, status: AuthorizationStatus) {
+        let _ = self.0.send(LocationEvent::AuthorizationChanged(status));
+    }
+}
+
+/// Returns the next event that isn't an authorization change.
+pub fn next_event(events: &Receiver<LocationEvent>) -> LocationEvent {
+    loop {
+        match events
+            .recv_timeout(TIMEOUT)
+            .expect("no event was delivered")
+        {
+            LocationEvent::AuthorizationChanged(_) => continue,
+            event => return event,
+        }
+    }
+}
+
+/// Returns the next location, failing on any other event.
+pub fn next_location(events: &Receiver<LocationEvent>) -> LocationSnapshot {
+    match next_event(events) {
+        LocationEvent::Location(location) => location,
+        event => panic!("expected a location, got {event:?}"),
+    }
+}
+
+/// Returns the next error, failing on any other event.
+pub fn next_error(events: &Receiver<LocationEvent>) -> Error {
+    match next_event(events) {
+        LocationEvent::Error(error) => error,
+        event => panic!("expected an error, got {event:?}"),
+    }
+}
+
+/// A path for a socket or device in the temporary directory, unique to this
+/// process.
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("robius-location-{}-{name}", std::process::id()))
+}
@@ -0,0 +1,367 @@
+//! Drives the GeoClue backend against a stand-in for GeoClue on a private bus.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use common::{next_location, Bus, TIMEOUT};
+use robius_location::{
+    Access, Accuracy, AuthorizationStatus, ErrorKind, Manager, Priority, UpdateRequest,
+};
+use zbus::{
+    blocking::{connection, Connection},
+    fdo, interface,
+    zvariant::{ObjectPath, OwnedObjectPath},
+};
+
+const MANAGER_PATH: &str = "/org/freedesktop/GeoClue2/Manager";
+const CLIENT_PATH: &str = "/org/freedesktop/GeoClue2/Client/1";
+const LOCATION_PATH: &str = "/org/freedesktop/GeoClue2/Location/1";
+
+/// A call made to the fake service.
+#[derive(Debug, PartialEq)]
+enum Call {
+    Start,
+    Stop,
+    DeleteClient,
+}
+
+struct FakeManager {
+    calls: Sender<Call>,
+}
+
+#[interface(name = "org.freedesktop.GeoClue2.Manager")]
+impl FakeManager {
+    fn get_client(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(CLIENT_PATH).unwrap()
+    }
+
+    fn delete_client(&self, _client: ObjectPath<'_>) {
+        let _ = self.calls.send(Call::DeleteClient);
+    }
+}
+
+struct FakeClient {
+    calls: Sender<Call>,
+    /// Whether the agent refuses to authorize the client.
+    deny: Arc<AtomicBool>,
+    location: Arc<Mutex<&'static str>>,
+    desktop_id: String,
+    accuracy_level: u32,
+    time_threshold: u32,
+    distance_threshold: u32,
+}
+
+#[interface(name = "org.freedesktop.GeoClue2.Client")]
+impl FakeClient {
+    fn start(&self) -> fdo::Result<()> {
+        if self.deny.load(Ordering::Acquire) {
+            return Err(fdo::Error::AccessDenied("not authorized".into()));
+        }
+        let _ = self.calls.send(Call::Start);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        let _ = self.calls.send(Call::Stop);
+    }
+
+    #[zbus(property)]
+    fn location(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(*self.location.lock().unwrap()).unwrap()
+    }
+
+    #[zbus(property)]
+    fn desktop_id(&self) -> String {
+        self.desktop_id.clone()
+    }
+
+    #[zbus(property)]
+    fn set_desktop_id(&mut self, id: String) {
+        self.desktop_id = id;
+    }
+
+    #[zbus(property)]
+    fn requested_accuracy_level(&self) -> u32 {
+        self.accuracy_level
+    }
+
+    #[zbus(property)]
+    fn set_requested_accuracy_level(&mut self, level: u32) {
+        self.accuracy_level = level;
+    }
+
+    #[zbus(property)]
+    fn time_threshold(&self) -> u32 {
+        self.time_threshold
+    }
+
+    #[zbus(property)]
+    fn set_time_threshold(&mut self, seconds: u32) {
+        self.time_threshold = seconds;
+    }
+
+    #[zbus(property)]
+    fn distance_threshold(&self) -> u32 {
+        self.distance_threshold
+    }
+
+    #[zbus(property)]
+    fn set_distance_threshold(&mut self, meters: u32) {
+        self.distance_threshold = meters;
+    }
+}
+
+/// The properties the manager set on the client.
+#[derive(Debug, PartialEq)]
+struct Settings {
+    accuracy_level: u32,
+    time_threshold: u32,
+    distance_threshold: u32,
+}
+
+fn settings(service: &Connection) -> Settings {
+    let client = service
+        .object_server()
+        .interface::<_, FakeClient>(CLIENT_PATH)
+        .unwrap();
+    let client = client.get();
+    Settings {
+        accuracy_level: client.accuracy_level,
+        time_threshold: client.time_threshold,
+        distance_threshold: client.distance_threshold,
+    }
+}
+
+struct FakeLocation;
+
+#[interface(name = "org.freedesktop.GeoClue2.Location")]
+impl FakeLocation {
+    #[zbus(property)]
+    fn latitude(&self) -> f64 {
+        59.9139
+    }
+
+    #[zbus(property)]
+    fn longitude(&self) -> f64 {
+        10.7522
+    }
+
+    #[zbus(property)]
+    fn accuracy(&self) -> f64 {
+        25.0
+    }
+
+    /// Unknown.
+    #[zbus(property)]
+    fn altitude(&self) -> f64 {
+        -f64::MAX
+    }
+
+    /// Unknown.
+    #[zbus(property)]
+    fn speed(&self) -> f64 {
+        -1.0
+    }
+
+    #[zbus(property)]
+    fn heading(&self) -> f64 {
+        90.0
+    }
+
+    #[zbus(property)]
+    fn timestamp(&self) -> (u64, u64) {
+        (1_700_000_000, 500_000)
+    }
+}
+
+#[test]
+fn geoclue() {
+    let Some(bus) = Bus::start() else {
+        eprintln!("skipping: dbus-daemon isn't installed");
+        return;
+    };
+    // The manager finds the bus through the environment.
+    std::env::set_var("DBUS_SYSTEM_BUS_ADDRESS", &bus.address);
+
+    let (sender, calls) = mpsc::channel();
+    let deny = Arc::new(AtomicBool::new(false));
+    let location = Arc::new(Mutex::new("/"));
+    let service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.GeoClue2")
+        .unwrap()
+        .serve_at(
+            MANAGER_PATH,
+            FakeManager {
+                calls: sender.clone(),
+            },
+        )
+        .unwrap()
+        .serve_at(
+            CLIENT_PATH,
+            FakeClient {
+                calls: sender,
+                deny: deny.clone(),
+                location: location.clone(),
+                desktop_id: String::new(),
+                accuracy_level: 0,
+                time_threshold: 0,
+                distance_threshold: 0,
+            },
+        )
+        .unwrap()
+        .serve_at(LOCATION_PATH, FakeLocation)
+        .unwrap()
+        .build()
+        .unwrap();
+    let emit_location = || {
+        service
+            .emit_signal(
+                None::<()>,
+                CLIENT_PATH,
+                "org.freedesktop.GeoClue2.Client",
+                "LocationUpdated",
+                &(
+                    ObjectPath::try_from("/").unwrap(),
+                    ObjectPath::try_from(LOCATION_PATH).unwrap(),
+                ),
+            )
+            .unwrap()
+    };
+
+    let (mut manager, events) = Manager::channel().unwrap();
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::NotDetermined
+    );
+
+    // The client is named after the executable, and asks for exact locations from
+    // the start.
+    assert_eq!(settings(&service).accuracy_level, 8);
+    let executable = std::env::current_exe().unwrap();
+    assert_eq!(
+        service
+            .object_server()
+            .interface::<_, FakeClient>(CLIENT_PATH)
+            .unwrap()
+            .get()
+            .desktop_id,
+        executable.file_stem().unwrap().to_str().unwrap()
+    );
+
+    // A single update starts the client until a location arrives.
+    manager.update_once().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(
+        settings(&service),
+        Settings {
+            accuracy_level: 8,
+            time_threshold: 0,
+            distance_threshold: 0,
+        }
+    );
+    emit_location();
+    let fix = next_location(&events);
+    assert_eq!(fix.coordinates.latitude, 59.9139);
+    assert_eq!(fix.coordinates.longitude, 10.7522);
+    assert_eq!(fix.horizontal_accuracy, Some(25.0));
+    assert_eq!(fix.altitude, None);
+    assert_eq!(fix.speed, None);
+    assert_eq!(fix.bearing, Some(90.0));
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Stop));
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::Background(Accuracy::Precise)
+    );
+
+    // While updates are running, a single update is answered with the current
+    // location.
+    manager.start_updates().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(
+        settings(&service),
+        Settings {
+            accuracy_level: 8,
+            time_threshold: 1,
+            distance_threshold: 0,
+        }
+    );
+    *location.lock().unwrap() = LOCATION_PATH;
+    manager.update_once().unwrap();
+    assert_eq!(next_location(&events).horizontal_accuracy, Some(25.0));
+    emit_location();
+    next_location(&events);
+    manager.stop_updates().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Stop));
+
+    // The shortest interval and the distance become the thresholds, and the
+    // priority the accuracy level.
+    manager
+        .start_updates_with(UpdateRequest {
+            interval: Duration::from_secs(30),
+            min_interval: Some(Duration::from_secs(10)),
+            min_distance: 100.5,
+            priority: Priority::Balanced,
+            max_update_delay: Duration::from_secs(60),
+        })
+        .unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(
+        settings(&service),
+        Settings {
+            accuracy_level: 6,
+            time_threshold: 10,
+            distance_threshold: 100,
+        }
+    );
+    manager.stop_updates().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Stop));
+
+    // A single update asks for exact locations again.
+    *location.lock().unwrap() = "/";
+    manager.update_once().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(settings(&service).accuracy_level, 8);
+    emit_location();
+    next_location(&events);
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Stop));
+
+    // Updates don't ask for more than the authorized accuracy.
+    manager
+        .request_authorization(Access::Foreground, Accuracy::Approximate)
+        .unwrap();
+    assert_eq!(settings(&service).accuracy_level, 4);
+    manager.start_updates().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(settings(&service).accuracy_level, 4);
+    manager.stop_updates().unwrap();
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Stop));
+
+    // The agent refusing to authorize the client denies authorization.
+    deny.store(true, Ordering::Release);
+    let Err(error) = manager.update_once() else {
+        panic!("the update was started without authorization");
+    };
+    assert_eq!(error.kind(), ErrorKind::AuthorizationDenied);
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::Denied
+    );
+
+    drop(manager);
+    while calls
+        .recv_timeout(TIMEOUT)
+        .expect("the client wasn't deleted")
+        != Call::DeleteClient
+    {}
+}
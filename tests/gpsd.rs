@@ -0,0 +1,65 @@
+//! Drives the gpsd backend against a stand-in for gpsd on a Unix domain socket.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::Shutdown,
+    os::unix::net::UnixListener,
+};
+
+use common::{next_error, next_location, temp_path, Events, TIMEOUT};
+use robius_location::{ErrorKind, Manager};
+
+const WATCH_ENABLE: &str = r#"?WATCH={"enable":true,"json":true};"#;
+const WATCH_DISABLE: &str = r#"?WATCH={"enable":false};"#;
+
+#[test]
+fn gpsd() {
+    let path = temp_path("gpsd.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let (handler, events) = Events::new();
+    let mut manager = Manager::with_gpsd(handler, path.to_str().unwrap()).unwrap();
+    let (mut gpsd, _) = listener.accept().unwrap();
+    let _ = std::fs::remove_file(&path);
+    gpsd.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let mut commands = BufReader::new(gpsd.try_clone().unwrap()).lines();
+    let mut command = || commands.next().unwrap().unwrap();
+    let mut send = |report: &str| writeln!(gpsd, "{report}").unwrap();
+
+    send(r#"{"class":"VERSION","release":"3.25","proto_major":3,"proto_minor":15}"#);
+
+    // A single update watches until a fix arrives, with accuracies derived from the
+    // dilutions of precision if gpsd doesn't estimate them.
+    manager.update_once().unwrap();
+    assert_eq!(command(), WATCH_ENABLE);
+    send(r#"{"class":"SKY","hdop":1.2,"vdop":2.0}"#);
+    send(r#"{"class":"TPV","mode":1}"#);
+    send(r#"{"class":"TPV","mode":3,"lat":59.9139,"lon":10.7522,"altMSL":41.2,"speed":1.5}"#);
+    let fix = next_location(&events);
+    assert_eq!(fix.coordinates.latitude, 59.9139);
+    assert_eq!(fix.coordinates.longitude, 10.7522);
+    assert_eq!(fix.altitude, Some(41.2));
+    assert_eq!(fix.speed, Some(1.5));
+    assert!((fix.horizontal_accuracy.unwrap() - 6.0).abs() < 1e-9);
+    assert!((fix.vertical_accuracy.unwrap() - 10.0).abs() < 1e-9);
+    assert_eq!(command(), WATCH_DISABLE);
+
+    // Continuous updates watch until they are stopped.
+    manager.start_updates().unwrap();
+    assert_eq!(command(), WATCH_ENABLE);
+    send("not json");
+    assert_eq!(next_error(&events).kind(), ErrorKind::Parse);
+    send(r#"{"class":"TPV","mode":2,"lat":1.0,"lon":2.0,"eph":3.0}"#);
+    assert_eq!(next_location(&events).horizontal_accuracy, Some(3.0));
+    manager.stop_updates().unwrap();
+    assert_eq!(command(), WATCH_DISABLE);
+
+    // gpsd going away is reported.
+    gpsd.shutdown(Shutdown::Both).unwrap();
+    assert_eq!(next_error(&events).kind(), ErrorKind::ServiceUnavailable);
+}
@@ -0,0 +1,142 @@
+//! Drives the NMEA backend against a stand-in for a receiver on a pseudoterminal.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    fs::File,
+    io::Write,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::PathBuf,
+    sync::mpsc::RecvTimeoutError,
+};
+
+use common::{next_error, next_location, temp_path, Events, TIMEOUT};
+use robius_location::{CacheConfig, ErrorKind, Manager};
+
+/// Opens a pseudoterminal in raw mode, returning its controlling side and the
+/// path of the device a receiver would show up as.
+fn open_pty() -> (File, PathBuf, OwnedFd) {
+    let (mut controller, mut device) = (0, 0);
+    // SAFETY: The pointers are valid, and the terminal settings and window size are
+    // optional.
+    let result = unsafe {
+        libc::openpty(
+            &mut controller,
+            &mut device,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(result, 0, "{}", std::io::Error::last_os_error());
+    // SAFETY: `openpty` returned two new file descriptors that nothing else owns.
+    let (controller, device) =
+        unsafe { (File::from_raw_fd(controller), OwnedFd::from_raw_fd(device)) };
+
+    // Receivers are configured not to translate line endings or echo.
+    // SAFETY: `termios` is plain data, and is initialized by `tcgetattr`.
+    unsafe {
+        let mut termios = std::mem::zeroed();
+        assert_eq!(libc::tcgetattr(device.as_raw_fd(), &mut termios), 0);
+        libc::cfmakeraw(&mut termios);
+        assert_eq!(
+            libc::tcsetattr(device.as_raw_fd(), libc::TCSANOW, &termios),
+            0
+        );
+    }
+
+    let path = std::fs::read_link(format!("/proc/self/fd/{}", device.as_raw_fd())).unwrap();
+    (controller, path, device)
+}
+
+#[test]
+fn nmea() {
+    let (mut receiver, path, device) = open_pty();
+    let (handler, events) = Events::new();
+    let mut manager = Manager::with_nmea(handler, &path).unwrap();
+    let mut send = |sentences: &[&str]| {
+        for sentence in sentences {
+            write!(receiver, "{sentence}\r\n").unwrap();
+        }
+    };
+
+    manager.update_once().unwrap();
+    send(&[
+        "$GNGSA,A,3,01,02,03,,,,,,,,,,1.8,1.0,1.5*20",
+        "$GNGGA,000001,3351.000,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*58",
+        "$GNRMC,000001,A,3351.000,S,15112.000,W,0.0,,010100,,*21",
+    ]);
+    let fix = next_location(&events);
+    assert!((fix.coordinates.latitude + 33.85).abs() < 1e-9);
+    assert!((fix.coordinates.longitude + 151.2).abs() < 1e-9);
+    assert_eq!(fix.altitude, Some(10.0));
+    assert_eq!(fix.horizontal_accuracy, Some(5.0));
+    assert!(fix.time.is_some());
+
+    // Garbage, as devices emit when they are first opened, is skipped.
+    manager.start_updates().unwrap();
+    send(&[
+        "\u{1}\u{7f}$GPG",
+        "$GNGGA,000002,3351.060,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*5D",
+        "$GNRMC,000002,A,3351.060,S,15112.000,W,10.0,180.0,010100,,*32",
+    ]);
+    let fix = next_location(&events);
+    assert!((fix.coordinates.latitude + 33.851).abs() < 1e-9);
+    assert_eq!(fix.bearing, Some(180.0));
+    manager.stop_updates().unwrap();
+
+    // The receiver being unplugged is reported.
+    drop(device);
+    drop(receiver);
+    assert_eq!(
+        next_error(&events).kind(),
+        ErrorKind::TemporarilyUnavailable
+    );
+}
+
+#[test]
+fn shutdown() {
+    let (mut receiver, path, _device) = open_pty();
+    let (handler, events) = Events::new();
+    let mut manager = Manager::with_nmea(handler, &path).unwrap();
+    let cache = temp_path("nmea-cache");
+    manager
+        .set_cache_config(CacheConfig {
+            path: Some(cache.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut send = |sentences: &[&str]| {
+        for sentence in sentences {
+            write!(receiver, "{sentence}\r\n").unwrap();
+        }
+    };
+
+    manager.start_updates().unwrap();
+    send(&[
+        "$GNGGA,000001,3351.000,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*58",
+        "$GNRMC,000001,A,3351.000,S,15112.000,W,0.0,,010100,,*21",
+    ]);
+    next_location(&events);
+    // The first location is saved straight away, but not the next one.
+    manager.update_once().unwrap();
+    send(&[
+        "$GNGGA,000002,3351.060,S,15112.000,W,1,08,1.0,10.0,M,0.0,M,,*5D",
+        "$GNRMC,000002,A,3351.060,S,15112.000,W,10.0,180.0,010100,,*32",
+    ]);
+    next_location(&events);
+
+    // Dropping the manager stops the reader while the receiver is still sending,
+    // which releases the handler and saves the last location.
+    drop(manager);
+    assert_eq!(
+        events.recv_timeout(TIMEOUT).unwrap_err(),
+        RecvTimeoutError::Disconnected
+    );
+    let saved = std::fs::read_to_string(&cache).unwrap();
+    std::fs::remove_file(&cache).unwrap();
+    assert!(saved.contains("latitude -33.851"), "{saved}");
+}
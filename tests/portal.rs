@@ -0,0 +1,208 @@
+//! Drives the portal backend against a stand-in for xdg-desktop-portal on a
+//! private session bus.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+};
+
+use common::{next_error, next_location, Bus, TIMEOUT};
+use robius_location::{Access, Accuracy, AuthorizationStatus, ErrorKind, Manager};
+use zbus::{
+    blocking::connection,
+    interface,
+    message::Header,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// A call made to the fake portal.
+#[derive(Debug, PartialEq)]
+enum Call {
+    CreateSession {
+        session: OwnedObjectPath,
+        accuracy: u32,
+    },
+    Start,
+    Close(OwnedObjectPath),
+}
+
+struct FakePortal {
+    calls: Sender<Call>,
+    /// The response to permission requests, where 0 grants them.
+    response: Arc<AtomicU32>,
+}
+
+#[interface(name = "org.freedesktop.portal.Location")]
+impl FakePortal {
+    async fn create_session(
+        &self,
+        options: HashMap<String, OwnedValue>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(object_server)] server: &zbus::ObjectServer,
+    ) -> OwnedObjectPath {
+        let session = object_path("session", &header, &options["session_handle_token"]);
+        server
+            .at(
+                &session,
+                FakeSession {
+                    calls: self.calls.clone(),
+                },
+            )
+            .await
+            .unwrap();
+        let accuracy = u32::try_from(&options["accuracy"]).unwrap();
+        let _ = self.calls.send(Call::CreateSession {
+            session: session.clone(),
+            accuracy,
+        });
+        session
+    }
+
+    async fn start(
+        &self,
+        _session_handle: ObjectPath<'_>,
+        _parent_window: &str,
+        options: HashMap<String, OwnedValue>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> OwnedObjectPath {
+        let request = object_path("request", &header, &options["handle_token"]);
+        let _ = self.calls.send(Call::Start);
+        // The client subscribes to the response before starting the session.
+        connection
+            .emit_signal(
+                None::<()>,
+                &request,
+                "org.freedesktop.portal.Request",
+                "Response",
+                &(
+                    self.response.load(Ordering::Acquire),
+                    HashMap::<&str, Value<'_>>::new(),
+                ),
+            )
+            .await
+            .unwrap();
+        request
+    }
+}
+
+struct FakeSession {
+    calls: Sender<Call>,
+}
+
+#[interface(name = "org.freedesktop.portal.Session")]
+impl FakeSession {
+    fn close(&self, #[zbus(header)] header: Header<'_>) {
+        let path = header.path().unwrap().to_owned().into();
+        let _ = self.calls.send(Call::Close(path));
+    }
+}
+
+/// The path of a portal object created for the sender of a call with `token`.
+fn object_path(kind: &str, header: &Header<'_>, token: &OwnedValue) -> OwnedObjectPath {
+    let sender = header.sender().unwrap();
+    let sender = sender.trim_start_matches(':').replace('.', "_");
+    let token = <&str>::try_from(token).unwrap();
+    OwnedObjectPath::try_from(format!("{PORTAL_PATH}/{kind}/{sender}/{token}")).unwrap()
+}
+
+#[test]
+fn portal() {
+    let Some(bus) = Bus::start() else {
+        eprintln!("skipping: dbus-daemon isn't installed");
+        return;
+    };
+    // The manager finds the bus through the environment, and only uses the portal
+    // when it is sandboxed.
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+    std::env::set_var("SNAP", "/snap/robius-location/1");
+
+    let (sender, calls) = mpsc::channel();
+    let response = Arc::new(AtomicU32::new(0));
+    let service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.portal.Desktop")
+        .unwrap()
+        .serve_at(
+            PORTAL_PATH,
+            FakePortal {
+                calls: sender,
+                response: response.clone(),
+            },
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let (manager, events) = Manager::channel().unwrap();
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::NotDetermined
+    );
+
+    // A single update runs a session until a location arrives.
+    manager.update_once().unwrap();
+    let Ok(Call::CreateSession { session, accuracy }) = calls.recv_timeout(TIMEOUT) else {
+        panic!("no session was created");
+    };
+    assert_eq!(accuracy, 5);
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    let location = HashMap::from([
+        ("Latitude", Value::from(59.9139)),
+        ("Longitude", Value::from(10.7522)),
+        ("Accuracy", Value::from(25.0)),
+        ("Altitude", Value::from(-f64::MAX)),
+        ("Speed", Value::from(1.5)),
+        ("Heading", Value::from(-1.0)),
+        ("Timestamp", Value::from((1_700_000_000_u64, 0_u64))),
+    ]);
+    service
+        .emit_signal(
+            None::<()>,
+            PORTAL_PATH,
+            "org.freedesktop.portal.Location",
+            "LocationUpdated",
+            &(session.as_ref(), location),
+        )
+        .unwrap();
+    let fix = next_location(&events);
+    assert_eq!(fix.coordinates.latitude, 59.9139);
+    assert_eq!(fix.coordinates.longitude, 10.7522);
+    assert_eq!(fix.horizontal_accuracy, Some(25.0));
+    assert_eq!(fix.altitude, None);
+    assert_eq!(fix.speed, Some(1.5));
+    assert_eq!(fix.bearing, None);
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Close(session)));
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::Background(Accuracy::Precise)
+    );
+
+    // The user dismissing the permission dialog denies authorization.
+    manager
+        .request_authorization(Access::Foreground, Accuracy::Approximate)
+        .unwrap();
+    response.store(1, Ordering::Release);
+    manager.update_once().unwrap();
+    let Ok(Call::CreateSession { session, accuracy }) = calls.recv_timeout(TIMEOUT) else {
+        panic!("no session was created");
+    };
+    assert_eq!(accuracy, 2);
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Start));
+    assert_eq!(next_error(&events).kind(), ErrorKind::AuthorizationDenied);
+    assert_eq!(calls.recv_timeout(TIMEOUT), Ok(Call::Close(session)));
+    assert_eq!(
+        manager.authorization_status().unwrap(),
+        AuthorizationStatus::Denied
+    );
+}